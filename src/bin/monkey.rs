@@ -1,8 +1,14 @@
 use chimpanzee::repl::ReplCli;
 use clap::Parser;
-use std::error::Error;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> ExitCode {
     let args = ReplCli::parse();
-    args.run()
+    match args.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
 }
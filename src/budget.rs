@@ -0,0 +1,106 @@
+//! Execution budgets, to stop programs that run for too long.
+//!
+//! Both engines take a [`Budget`]: the VM spends one unit of fuel per
+//! instruction, the evaluator one per statement or expression it evaluates.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// How often the deadline and the cancellation handle are checked, in units
+/// of fuel. Reading the clock at every step would slow everything down.
+const CHECK_INTERVAL: u64 = 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    /// Steps the program can take, `None` for no limit.
+    pub fuel: Option<u64>,
+    /// Time after which the program is interrupted.
+    pub deadline: Option<Instant>,
+    /// Lets another thread interrupt the program.
+    pub cancel: Option<CancelHandle>,
+}
+
+/// Interrupts a running program from anywhere, clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the program to stop, it does so at the next check.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Why a budget stopped a program.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetError {
+    /// The program used all of its fuel.
+    OutOfFuel,
+    /// The deadline passed or the program was cancelled.
+    Interrupted,
+}
+
+impl Display for BudgetError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BudgetError::OutOfFuel => write!(f, "out of fuel"),
+            BudgetError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl Error for BudgetError {}
+
+/// Keeps track of the fuel a program used.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Meter {
+    pub(crate) budget: Budget,
+    pub(crate) used: u64,
+}
+
+impl Meter {
+    pub(crate) fn new(budget: Budget) -> Self {
+        Meter { budget, used: 0 }
+    }
+
+    /// Spends one unit of fuel.
+    pub(crate) fn step(&mut self) -> Result<(), BudgetError> {
+        self.used += 1;
+        if matches!(self.budget.fuel, Some(fuel) if self.used > fuel) {
+            return Err(BudgetError::OutOfFuel);
+        }
+        // The first step is checked too, so that a program cancelled before it
+        // starts does not run at all
+        if (self.used - 1).is_multiple_of(CHECK_INTERVAL) && self.is_interrupted() {
+            return Err(BudgetError::Interrupted);
+        }
+        Ok(())
+    }
+
+    fn is_interrupted(&self) -> bool {
+        self.budget
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .budget
+                .cancel
+                .as_ref()
+                .is_some_and(CancelHandle::is_cancelled)
+    }
+}
@@ -0,0 +1,504 @@
+//! Textual assembly language for the VM.
+//!
+//! Every line holds at most one instruction, written like [`Instructions`]
+//! prints it: an optional offset, the opcode and its operands. The listing of
+//! any [`Instructions`] assembles back to the same bytes. On top of that:
+//!
+//! - `; ...` is a comment.
+//! - `name:` defines a label, jump operands can use it instead of an offset.
+//! - `#42` or `#"monkey"` is an inline constant: it is added to the constant
+//!   pool and replaced by its index.
+//! - `fn name params=1 locals=1 free=0 { ... }` defines a function, which is
+//!   added to the constant pool. `OpClosure` and `OpConstant` can refer to it
+//!   by name. `free` is optional, when it is given every `OpClosure` of the
+//!   function must capture that many variables.
+//! - `const 42` or `const "monkey"` adds a constant to the pool, even if it is
+//!   already there.
+//!
+//! Functions and `const` lines take the next slot of the pool in the order they
+//! are written, so the listing of the [`disassembler`] assembles back to the
+//! same program. Instructions outside of any function make the main program.
+//!
+//! [`disassembler`]: crate::compiler::disassembler
+//!
+//! ```text
+//! fn double params=1 locals=1 {
+//!     OpGetLocal 0
+//!     OpConstant #2
+//!     OpMul
+//!     OpReturnValue
+//! }
+//!
+//!     OpClosure double 0
+//!     OpConstant #21
+//!     OpCall 1
+//!     OpPop
+//! ```
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    compiler::{
+        code::{Instructions, Opcode},
+        Bytecode,
+    },
+    lexer::{token::Token, Lexer},
+    object::{CompiledFunction, DebugInfo, Object},
+};
+
+/// An error in the assembly source, with the line where it was found.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AssemblerError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        AssemblerError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssemblerError {}
+
+/// An operand as written in the source.
+#[derive(Debug, Clone)]
+enum Operand {
+    Number(i32),
+    Name(String),
+}
+
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    offset: Option<usize>,
+    opcode: Opcode,
+    operands: Vec<Operand>,
+    /// Size of the encoded instruction, names are assumed to fit in the usual
+    /// operand widths.
+    size: usize,
+}
+
+/// The instructions of the main program or of a function, before the names
+/// they use are resolved.
+#[derive(Debug, Default)]
+struct Block {
+    /// Index of the function in the constant pool, `None` for the main program.
+    index: Option<usize>,
+    name: Option<String>,
+    num_parameters: usize,
+    num_locals: usize,
+    /// Number of free variables, if the source gives it.
+    num_free: Option<usize>,
+    lines: Vec<Line>,
+    labels: HashMap<String, usize>,
+    size: usize,
+}
+
+impl Block {
+    fn push(&mut self, mut line: Line) -> Result<(), AssemblerError> {
+        if let Some(offset) = line.offset {
+            if offset != self.size {
+                return Err(AssemblerError::new(
+                    line.number,
+                    format!(
+                        "offset {offset:04} does not match position {:04}",
+                        self.size
+                    ),
+                ));
+            }
+        }
+        let widths = line.opcode.lookup_widths();
+        if line.operands.len() != widths.len() {
+            return Err(AssemblerError::new(
+                line.number,
+                format!(
+                    "{} takes {} operands, got {}",
+                    line.opcode,
+                    widths.len(),
+                    line.operands.len()
+                ),
+            ));
+        }
+        let mut operands = Vec::new();
+        for operand in &line.operands {
+            match operand {
+                Operand::Number(x) if *x < 0 => {
+                    return Err(AssemblerError::new(
+                        line.number,
+                        format!("operand {x} is negative"),
+                    ))
+                }
+                Operand::Number(x) => operands.push(*x),
+                Operand::Name(_) => operands.push(0),
+            }
+        }
+        line.size = line.opcode.make(operands).data.len();
+        self.size += line.size;
+        self.lines.push(line);
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: &str, number: usize) -> Result<(), AssemblerError> {
+        if self.labels.insert(name.to_string(), self.size).is_some() {
+            return Err(AssemblerError::new(
+                number,
+                format!("label `{name}` is already defined"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Resolves the names and encodes the instructions. `free` has the number
+    /// of free variables of the functions that declare it, by constant index.
+    fn encode(
+        &self,
+        functions: &HashMap<String, usize>,
+        free: &HashMap<usize, usize>,
+    ) -> Result<Instructions, AssemblerError> {
+        let mut instructions = Instructions::default();
+        for line in &self.lines {
+            let mut operands = Vec::new();
+            for operand in &line.operands {
+                operands.push(match operand {
+                    Operand::Number(x) => *x,
+                    Operand::Name(name) => self.resolve(line, name, functions)?,
+                });
+            }
+            if line.opcode == Opcode::Closure {
+                let (index, num_free) = (operands[0] as usize, operands[1] as usize);
+                match free.get(&index) {
+                    Some(expected) if *expected != num_free => {
+                        return Err(AssemblerError::new(
+                            line.number,
+                            format!(
+                                "function {index} has {expected} free variables, \
+                                 OpClosure gives it {num_free}"
+                            ),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+            let instruction = line.opcode.make(operands);
+            if instruction.data.len() != line.size {
+                return Err(AssemblerError::new(
+                    line.number,
+                    format!(
+                        "{} needs wide operands, use numbers instead of names",
+                        line.opcode
+                    ),
+                ));
+            }
+            instructions.append(instruction);
+        }
+        Ok(instructions)
+    }
+
+    fn resolve(
+        &self,
+        line: &Line,
+        name: &str,
+        functions: &HashMap<String, usize>,
+    ) -> Result<i32, AssemblerError> {
+        let value = match line.opcode {
+            Opcode::Jump | Opcode::JumpNotTruthy | Opcode::Try => self.labels.get(name),
+            Opcode::Closure | Opcode::Constant => functions.get(name),
+            _ => None,
+        };
+        value.map(|x| *x as i32).ok_or_else(|| {
+            AssemblerError::new(
+                line.number,
+                format!("cannot resolve `{name}` in {}", line.opcode),
+            )
+        })
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    constants: Vec<Object>,
+    functions: HashMap<String, usize>,
+    main: Block,
+    blocks: Vec<Block>,
+    /// The function being assembled, if any.
+    current: Option<Block>,
+}
+
+impl Assembler {
+    fn block(&mut self) -> &mut Block {
+        self.current.as_mut().unwrap_or(&mut self.main)
+    }
+
+    fn add_constant(&mut self, constant: Object) -> usize {
+        match self.constants.iter().position(|x| *x == constant) {
+            Some(index) => index,
+            None => {
+                self.constants.push(constant);
+                self.constants.len() - 1
+            }
+        }
+    }
+
+    fn line(&mut self, number: usize, text: &str) -> Result<(), AssemblerError> {
+        let words = split(text).map_err(|err| AssemblerError::new(number, err))?;
+        match words.as_slice() {
+            [] => Ok(()),
+            [fn_keyword, ..] if fn_keyword == "fn" => self.open_function(number, &words[1..]),
+            [const_keyword, literal] if const_keyword == "const" => {
+                let constant = parse_literal(literal).ok_or_else(|| {
+                    AssemblerError::new(number, format!("invalid literal `{literal}`"))
+                })?;
+                self.constants.push(constant);
+                Ok(())
+            }
+            [close] if close == "}" => match self.current.take() {
+                Some(block) => {
+                    self.blocks.push(block);
+                    Ok(())
+                }
+                None => Err(AssemblerError::new(number, "`}` outside of a function")),
+            },
+            [label] if label.ends_with(':') => {
+                let name = &label[..label.len() - 1];
+                if !is_name(name) {
+                    return Err(AssemblerError::new(
+                        number,
+                        format!("invalid label `{name}`"),
+                    ));
+                }
+                self.block().define_label(name, number)
+            }
+            [first, ..] => {
+                let (offset, rest) = match first.parse::<usize>() {
+                    Ok(offset) => (Some(offset), &words[1..]),
+                    Err(_) => (None, &words[..]),
+                };
+                let Some((name, operands)) = rest.split_first() else {
+                    return Err(AssemblerError::new(number, "missing opcode"));
+                };
+                let opcode = Opcode::try_from(name.as_str())
+                    .map_err(|_| AssemblerError::new(number, format!("unknown opcode `{name}`")))?;
+                let operands = operands
+                    .iter()
+                    .map(|operand| self.operand(number, operand))
+                    .collect::<Result<Vec<Operand>, AssemblerError>>()?;
+                if opcode == Opcode::Wide {
+                    return Err(AssemblerError::new(
+                        number,
+                        "OpWide is added when an operand needs it",
+                    ));
+                }
+                self.block().push(Line {
+                    number,
+                    offset,
+                    opcode,
+                    operands,
+                    size: 0,
+                })
+            }
+        }
+    }
+
+    fn open_function(&mut self, number: usize, words: &[String]) -> Result<(), AssemblerError> {
+        if self.current.is_some() {
+            return Err(AssemblerError::new(number, "functions cannot be nested"));
+        }
+        let Some((name, rest)) = words.split_first() else {
+            return Err(AssemblerError::new(number, "missing function name"));
+        };
+        if !is_name(name) || self.functions.contains_key(name) {
+            return Err(AssemblerError::new(
+                number,
+                format!("invalid or duplicated function name `{name}`"),
+            ));
+        }
+        if rest.last().map(String::as_str) != Some("{") {
+            return Err(AssemblerError::new(
+                number,
+                "expected `{` after the function",
+            ));
+        }
+
+        let mut block = Block::default();
+        for attribute in &rest[..rest.len() - 1] {
+            let (key, value) = attribute
+                .split_once('=')
+                .and_then(|(key, value)| Some((key, value.parse::<usize>().ok()?)))
+                .ok_or_else(|| {
+                    AssemblerError::new(number, format!("invalid attribute `{attribute}`"))
+                })?;
+            match key {
+                "params" => block.num_parameters = value,
+                "locals" => block.num_locals = value,
+                "free" => block.num_free = Some(value),
+                _ => {
+                    return Err(AssemblerError::new(
+                        number,
+                        format!("unknown attribute `{key}`"),
+                    ))
+                }
+            }
+        }
+
+        // The slot is filled once the function is encoded
+        self.constants.push(Object::NULL);
+        let index = self.constants.len() - 1;
+        block.index = Some(index);
+        block.name = Some(name.clone());
+        self.functions.insert(name.clone(), index);
+        self.current = Some(block);
+        Ok(())
+    }
+
+    fn operand(&mut self, number: usize, operand: &str) -> Result<Operand, AssemblerError> {
+        if let Some(literal) = operand.strip_prefix('#') {
+            let constant = parse_literal(literal).ok_or_else(|| {
+                AssemblerError::new(number, format!("invalid literal `{literal}`"))
+            })?;
+            Ok(Operand::Number(self.add_constant(constant) as i32))
+        } else if let Ok(x) = operand.parse::<i32>() {
+            Ok(Operand::Number(x))
+        } else if is_name(operand) {
+            Ok(Operand::Name(operand.to_string()))
+        } else {
+            Err(AssemblerError::new(
+                number,
+                format!("invalid operand `{operand}`"),
+            ))
+        }
+    }
+
+    fn finish(mut self, last_line: usize) -> Result<Bytecode, AssemblerError> {
+        if self.current.is_some() {
+            return Err(AssemblerError::new(last_line, "unclosed function"));
+        }
+        let free: HashMap<usize, usize> = self
+            .blocks
+            .iter()
+            .filter_map(|block| Some((block.index?, block.num_free?)))
+            .collect();
+        for block in &self.blocks {
+            let instructions = block.encode(&self.functions, &free)?;
+            self.constants[block.index.unwrap()] = Object::COMPILEDFUNCTION(CompiledFunction {
+                instructions: instructions.data,
+                num_locals: block.num_locals,
+                num_parameters: block.num_parameters,
+                debug: DebugInfo {
+                    name: block.name.clone(),
+                    lines: vec![],
+                },
+            });
+        }
+        let instructions = self.main.encode(&self.functions, &free)?;
+        Ok(Bytecode::new(instructions, self.constants))
+    }
+}
+
+/// Assembles a whole program.
+pub fn assemble(source: &str) -> Result<Bytecode, AssemblerError> {
+    let mut assembler = Assembler::default();
+    let mut last_line = 0;
+    for (i, line) in source.lines().enumerate() {
+        last_line = i + 1;
+        assembler.line(last_line, line)?;
+    }
+    assembler.finish(last_line)
+}
+
+/// Assembles a bare instruction listing, such as the one printed by
+/// [`Instructions`], without any constant pool.
+pub fn assemble_instructions(source: &str) -> Result<Instructions, AssemblerError> {
+    let bytecode = assemble(source)?;
+    if !bytecode.constants.is_empty() {
+        return Err(AssemblerError::new(
+            0,
+            "functions and literals need a constant pool, use `assemble`",
+        ));
+    }
+    Ok(bytecode.instructions)
+}
+
+/// Splits a line in words, dropping its comment. Strings are kept as a single
+/// word.
+fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => break,
+            '"' => {
+                word.push(c);
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            word.push('\\');
+                            word.extend(chars.next());
+                        }
+                        Some('"') => {
+                            word.push('"');
+                            break;
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err(String::from("unterminated string")),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn is_name(word: &str) -> bool {
+    word.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && word.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Integers and strings, strings use the escapes of Monkey.
+fn parse_literal(literal: &str) -> Option<Object> {
+    if literal.starts_with('"') {
+        let mut lexer = Lexer::new(literal);
+        let token = lexer.next_token();
+        if lexer.next_token() != Token::Eof || !lexer.take_errors().is_empty() {
+            return None;
+        }
+        match token {
+            Token::String(x) => Some(Object::STRING(x)),
+            _ => None,
+        }
+    } else if let Ok(x) = literal.parse::<i64>() {
+        Some(Object::INTEGER(x))
+    } else {
+        literal
+            .parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .map(Object::FLOAT)
+    }
+}
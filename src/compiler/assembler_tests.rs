@@ -0,0 +1,206 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        compiler::{
+            assembler::{assemble, assemble_instructions, AssemblerError},
+            code::{Instructions, Opcode},
+            disassembler::disassemble,
+            optimizer::optimize,
+            test_utils::{compile, flatten_instructions},
+        },
+        object::Object,
+        vm::VM,
+    };
+
+    fn run(source: &str) -> Object {
+        let mut vm = VM::new(assemble(source).unwrap());
+        vm.run().unwrap();
+        vm.last_popped_stack_element().unwrap().as_ref().clone()
+    }
+
+    const PROGRAMS: [&str; 10] = [
+        "1 + 2 * 3",
+        r#"let x = "monkey"; puts(x)"#,
+        "if (1 < 2) { 10 } else { 20 }",
+        "let a = 0; while (a < 10) { if (a == 5) { break; } let a = a + 1; } a",
+        r"
+        let new_adder = fn(x) { fn(y) { x + y } };
+        let add_two = new_adder(2);
+        add_two([1, 2][0])
+        ",
+        r#"{"a": 1}["a"]"#,
+        r#""tab\t" + "quote\" {brace}"; 2.5 * 1e-3; -7 % 4"#,
+        "let f = fn() { f() }; let g = fn() { 1 }; let h = fn() { 1 }; [g, h]",
+        "let a = [1]; a[0] += 1; try { throw a; } catch (e) { e }",
+        r#"let x = "line\nbreak \"quoted\" back\\slash;"; x"#,
+    ];
+
+    #[test]
+    fn test_round_trip_with_display() {
+        for input in PROGRAMS {
+            let bytecode = compile(input);
+
+            let mut listings = vec![bytecode.instructions.clone()];
+            for constant in &bytecode.constants {
+                if let Object::COMPILEDFUNCTION(function) = constant {
+                    listings.push(Instructions::new(function.instructions.clone()));
+                }
+            }
+
+            for instructions in listings {
+                let assembled = assemble_instructions(&instructions.to_string()).unwrap();
+                assert_eq!(assembled, instructions, "round trip failed for {input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_with_disassembler() {
+        let mut inputs: Vec<String> = PROGRAMS.iter().map(ToString::to_string).collect();
+        for entry in fs::read_dir("monkey_examples").unwrap() {
+            inputs.push(fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+
+        for input in inputs {
+            let bytecode = compile(&input);
+            for bytecode in [bytecode.clone(), optimize(bytecode).unwrap()] {
+                let listing = disassemble(&bytecode).unwrap();
+                match assemble(&listing) {
+                    Ok(assembled) => {
+                        assert_eq!(assembled, bytecode, "round trip failed for\n{listing}")
+                    }
+                    Err(err) => panic!("{err} in\n{listing}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = r"
+                OpTrue
+                OpJumpNotTruthy else
+                OpNull
+                OpJump end
+            else:
+                OpTrue
+            end:
+                OpPop
+        ";
+
+        let expected = flatten_instructions(vec![
+            Opcode::True.make(vec![]),
+            Opcode::JumpNotTruthy.make(vec![8]),
+            Opcode::Null.make(vec![]),
+            Opcode::Jump.make(vec![9]),
+            Opcode::True.make(vec![]),
+            Opcode::Pop.make(vec![]),
+        ]);
+
+        assert_eq!(assemble_instructions(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_inline_constants() {
+        let bytecode = assemble(
+            r#"
+            OpConstant #"mon"   ; strings use the Monkey escapes
+            OpConstant #"key\n"
+            OpAdd
+            OpConstant #-3
+            OpConstant #-3
+            OpPop
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytecode.constants,
+            vec![
+                Object::STRING("mon".to_string()),
+                Object::STRING("key\n".to_string()),
+                Object::INTEGER(-3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_functions() {
+        let source = r"
+            fn double params=1 locals=1 {
+                OpGetLocal 0
+                OpConstant #2
+                OpMul
+                OpReturnValue
+            }
+
+            fn apply params=2 locals=2 free=0 {
+                OpGetLocal 0
+                OpGetLocal 1
+                OpCall 1
+                OpReturnValue
+            }
+
+                OpClosure apply 0
+                OpClosure double 0
+                OpConstant #21
+                OpCall 2
+                OpPop
+        ";
+
+        assert_eq!(run(source), Object::INTEGER(42));
+    }
+
+    #[test]
+    fn test_wide_operands() {
+        let source = "0000 OpGetLocal 300\n0004 OpConstant 70000\n0010 OpPop\n";
+        let instructions = assemble_instructions(source).unwrap();
+        assert_eq!(
+            instructions,
+            flatten_instructions(vec![
+                Opcode::GetLocal.make(vec![300]),
+                Opcode::Constant.make(vec![70000]),
+                Opcode::Pop.make(vec![]),
+            ])
+        );
+        assert_eq!(instructions.to_string(), source);
+    }
+
+    #[test]
+    fn test_errors() {
+        let tests = vec![
+            ("OpAdd 1", 1, "OpAdd takes 0 operands, got 1"),
+            ("OpNop", 1, "unknown opcode `OpNop`"),
+            ("OpJump nowhere", 1, "cannot resolve `nowhere` in OpJump"),
+            ("OpConstant -1", 1, "operand -1 is negative"),
+            ("OpWide", 1, "OpWide is added when an operand needs it"),
+            (
+                "OpTrue\n0000 OpPop",
+                2,
+                "offset 0000 does not match position 0001",
+            ),
+            ("fn f {\nOpNull", 2, "unclosed function"),
+            ("OpConstant #\"a", 1, "unterminated string"),
+            ("a:\na:", 2, "label `a` is already defined"),
+            ("fn f free=x {\n}", 1, "invalid attribute `free=x`"),
+            (
+                "fn f free=1 {\n}\nOpClosure f 0",
+                3,
+                "function 0 has 1 free variables, OpClosure gives it 0",
+            ),
+        ];
+
+        for (source, line, message) in tests {
+            assert_eq!(
+                assemble(source),
+                Err(AssemblerError {
+                    line,
+                    message: message.to_string()
+                }),
+                "wrong error for {source}"
+            );
+        }
+    }
+}
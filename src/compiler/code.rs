@@ -19,17 +19,16 @@ impl Display for Instructions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut i = 0;
         while i < self.data.len() {
-            let op = Opcode::from_u8(self.data[i])
-                .unwrap_or_else(|| panic!("ERROR: Unknown opcode: {}", self.data[i]));
+            let (op, operands, size) =
+                read_instruction(&self.data[i..]).unwrap_or_else(|err| panic!("ERROR: {err}"));
             let widths = op.lookup_widths();
-            let (operands, read) = Opcode::read_operands(&widths, &self.data[i + 1..]);
             writeln!(
                 f,
                 "{:04} {}",
                 i,
                 self.format_instruction(op, &widths, &operands)
             )?;
-            i += 1 + read as usize;
+            i += size;
         }
         Ok(())
     }
@@ -80,6 +79,8 @@ pub enum Opcode {
     False,
     GreaterThan,
     GreaterEqualThan,
+    LessThan,
+    LessEqualThan,
     Equal,
     NotEqual,
     Or,
@@ -105,22 +106,50 @@ pub enum Opcode {
     GetLocal,
 
     GetFree,
+    SetFree,
     CurrentClosure,
 
     // Custom types
     Array,
     HashMap,
     Index,
+    /// Replaces an element of an array or a hash: pops the container, the
+    /// operand number of indices leading to the element and the new value,
+    /// then pushes the value and the new container.
+    SetIndex,
+    /// Pushes the element at the end of the operand number of indices on top
+    /// of the stack, leaving the indices and the container below them.
+    PeekIndex,
 
     // Functions
     Call,
+    /// A call whose result is returned right away, it reuses the frame of the
+    /// caller.
+    TailCall,
     ReturnValue,
     Return,
     GetBuiltin,
     Closure,
+    /// Adds a free variable to the closure on top of the stack, sharing a
+    /// local of the running function so that assignments reach both.
+    CaptureLocal,
+    CaptureFree,
+    CaptureCurrentClosure,
+
+    // Exceptions
+    /// Installs a handler in the current frame: until the matching `OpEndTry`,
+    /// a catchable error jumps to its operand with the exception on the stack.
+    Try,
+    EndTry,
+    Throw,
 
     // Stack
     Pop,
+
+    // Operands
+    /// Prefix for an instruction whose operands do not fit in their usual
+    /// widths, they are read with twice as many bytes.
+    Wide,
 }
 
 impl Opcode {
@@ -129,30 +158,60 @@ impl Opcode {
             Opcode::Constant
             | Opcode::Jump
             | Opcode::JumpNotTruthy
+            | Opcode::Try
             | Opcode::SetGlobal
             | Opcode::GetGlobal
             | Opcode::Array
             | Opcode::HashMap => vec![2],
 
             Opcode::Call
+            | Opcode::TailCall
             | Opcode::SetLocal
             | Opcode::GetLocal
             | Opcode::GetBuiltin
-            | Opcode::GetFree => vec![1],
+            | Opcode::GetFree
+            | Opcode::SetFree
+            | Opcode::SetIndex
+            | Opcode::PeekIndex
+            | Opcode::CaptureLocal
+            | Opcode::CaptureFree => vec![1],
             Opcode::Closure => vec![2, 1],
 
             _ => vec![],
         }
     }
 
+    /// Widths of the operands when the instruction follows `OpWide`.
+    pub fn lookup_wide_widths(&self) -> Vec<u32> {
+        self.lookup_widths().iter().map(|width| width * 2).collect()
+    }
+
+    /// Encodes the instruction, with the `OpWide` prefix if an operand does
+    /// not fit in its usual width.
+    ///
+    /// # Panics
+    ///
+    /// If an operand is negative.
     #[allow(clippy::needless_pass_by_value)]
     pub fn make(&self, operands: Vec<i32>) -> Instructions {
-        let widths = self.lookup_widths();
+        let mut widths = self.lookup_widths();
         let mut instructions: Vec<u8> = Vec::new();
+        if operands
+            .iter()
+            .zip(&widths)
+            .any(|(operand, width)| i64::from(*operand) >= 1i64 << (8 * width))
+        {
+            instructions.push(Opcode::Wide as u8);
+            widths = self.lookup_wide_widths();
+        }
         instructions.push(*self as u8);
 
         for (operand, width) in operands.iter().zip(widths) {
+            assert!(*operand >= 0, "Negative operand {operand} for {self}");
             match width {
+                4 => instructions
+                    .write_u32::<BigEndian>(*operand as u32)
+                    .unwrap(),
                 2 => instructions
                     .write_u16::<BigEndian>(*operand as u16)
                     .unwrap(),
@@ -164,12 +223,16 @@ impl Opcode {
         Instructions::new(instructions)
     }
 
-    fn read_operands(widths: &Vec<u32>, ins: &[u8]) -> (Vec<i32>, i32) {
+    pub fn read_operands(widths: &Vec<u32>, ins: &[u8]) -> (Vec<i32>, i32) {
         let mut operands: Vec<i32> = Vec::new();
         let mut offset = 0;
 
         for width in widths {
             match width {
+                4 => {
+                    operands.push(BigEndian::read_u32(&ins[offset..offset + 4]) as i32);
+                    offset += 4;
+                }
                 2 => {
                     operands.push(i32::from(read_u16(&ins[offset..offset + 2])));
                     offset += 2;
@@ -186,6 +249,35 @@ impl Opcode {
     }
 }
 
+/// Decodes the instruction at the start of `ins`.
+///
+/// # Returns
+///
+/// * The opcode, its operands and the size of the instruction in bytes,
+///   including the `OpWide` prefix if there is one.
+pub fn read_instruction(ins: &[u8]) -> Result<(Opcode, Vec<i32>, usize), String> {
+    let first = *ins.first().ok_or("Missing opcode")?;
+    let mut op = Opcode::from_u8(first).ok_or(format!("Unknown opcode {first}"))?;
+    let mut start = 1;
+    let mut widths = op.lookup_widths();
+    if op == Opcode::Wide {
+        let second = *ins.get(1).ok_or("Missing opcode after OpWide")?;
+        op = Opcode::from_u8(second).ok_or(format!("Unknown opcode {second}"))?;
+        if op == Opcode::Wide || op.lookup_widths().is_empty() {
+            return Err(format!("{op} cannot be wide"));
+        }
+        start = 2;
+        widths = op.lookup_wide_widths();
+    }
+
+    let size = start + widths.iter().sum::<u32>() as usize;
+    if size > ins.len() {
+        return Err(format!("Truncated operands for {op}"));
+    }
+    let (operands, _) = Opcode::read_operands(&widths, &ins[start..]);
+    Ok((op, operands, size))
+}
+
 /// This is a helper function to read a u16 from a byte slice, using
 /// big endian encoding.
 ///
@@ -244,7 +336,51 @@ mod tests {
         }
     }
 
-    fn check_instruction(expected: &Vec<u8>, actual: &Instructions) {
+    #[test]
+    fn test_make_wide() {
+        let tests = vec![
+            (
+                Opcode::Constant,
+                vec![65536],
+                vec![Opcode::Wide as u8, Opcode::Constant as u8, 0, 1, 0, 0],
+            ),
+            (
+                Opcode::GetLocal,
+                vec![256],
+                vec![Opcode::Wide as u8, Opcode::GetLocal as u8, 1, 0],
+            ),
+            (
+                Opcode::Closure,
+                vec![1, 300],
+                vec![Opcode::Wide as u8, Opcode::Closure as u8, 0, 0, 0, 1, 1, 44],
+            ),
+        ];
+
+        for (op, operands, expected) in tests {
+            let instructions = op.make(operands.clone());
+            check_instruction(&expected, &instructions);
+            assert_eq!(
+                read_instruction(&instructions.data),
+                Ok((op, operands, expected.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_invalid_wide_instructions() {
+        let tests = vec![
+            vec![Opcode::Wide as u8],
+            vec![Opcode::Wide as u8, Opcode::Add as u8],
+            vec![Opcode::Wide as u8, Opcode::Wide as u8],
+            vec![Opcode::Wide as u8, Opcode::GetLocal as u8, 1],
+        ];
+
+        for ins in tests {
+            assert!(read_instruction(&ins).is_err(), "{ins:?} was read");
+        }
+    }
+
+    fn check_instruction(expected: &[u8], actual: &Instructions) {
         let expected_len = expected.len();
         let actual_len = actual.data.len();
 
@@ -263,6 +399,8 @@ mod tests {
             Opcode::Constant.make(vec![2]),
             Opcode::Constant.make(vec![65535]),
             Opcode::Closure.make(vec![65535, 255]),
+            Opcode::GetLocal.make(vec![256]),
+            Opcode::Add.make(vec![]),
         ];
 
         let mut test_instruction = Instructions::default();
@@ -270,7 +408,7 @@ mod tests {
             test_instruction.append(instruction);
         }
 
-        let expected = "0000 OpAdd\n0001 OpGetLocal 1\n0003 OpConstant 2\n0006 OpConstant 65535\n0009 OpClosure 65535 255\n";
+        let expected = "0000 OpAdd\n0001 OpGetLocal 1\n0003 OpConstant 2\n0006 OpConstant 65535\n0009 OpClosure 65535 255\n0013 OpGetLocal 256\n0017 OpAdd\n";
 
         assert_eq!(test_instruction.to_string(), expected);
     }
@@ -5,9 +5,14 @@ pub mod tests {
     use crate::{
         compiler::{
             code::Opcode,
-            test_utils::{flatten_instructions, run_compiler, CompilerTestCase},
+            test_utils::{
+                flatten_instructions, flatten_u8_instructions, run_compiler, CompilerTestCase,
+            },
+            Compiler, MAX_CONSTANTS,
         },
-        object::Object,
+        object::{CompiledFunction, Object},
+        parser::parse,
+        vm::GLOBALS_SIZE,
     };
 
     #[test]
@@ -136,21 +141,21 @@ pub mod tests {
             },
             CompilerTestCase {
                 input: "1 < 2".to_string(),
-                expected_constants: vec![Object::INTEGER(2), Object::INTEGER(1)],
+                expected_constants: vec![Object::INTEGER(1), Object::INTEGER(2)],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Constant.make(vec![0]),
                     Opcode::Constant.make(vec![1]),
-                    Opcode::GreaterThan.make(vec![]),
+                    Opcode::LessThan.make(vec![]),
                     Opcode::Pop.make(vec![]),
                 ]),
             },
             CompilerTestCase {
                 input: "1 <= 2".to_string(),
-                expected_constants: vec![Object::INTEGER(2), Object::INTEGER(1)],
+                expected_constants: vec![Object::INTEGER(1), Object::INTEGER(2)],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Constant.make(vec![0]),
                     Opcode::Constant.make(vec![1]),
-                    Opcode::GreaterEqualThan.make(vec![]),
+                    Opcode::LessEqualThan.make(vec![]),
                     Opcode::Pop.make(vec![]),
                 ]),
             },
@@ -485,16 +490,14 @@ pub mod tests {
                     Object::INTEGER(1),
                     Object::INTEGER(2),
                     Object::INTEGER(3),
-                    Object::INTEGER(1),
-                    Object::INTEGER(1),
                 ],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Constant.make(vec![0]),
                     Opcode::Constant.make(vec![1]),
                     Opcode::Constant.make(vec![2]),
                     Opcode::Array.make(vec![3]),
-                    Opcode::Constant.make(vec![3]),
-                    Opcode::Constant.make(vec![4]),
+                    Opcode::Constant.make(vec![0]),
+                    Opcode::Constant.make(vec![0]),
                     Opcode::Add.make(vec![]),
                     Opcode::Index.make(vec![]),
                     Opcode::Pop.make(vec![]),
@@ -502,18 +505,13 @@ pub mod tests {
             },
             CompilerTestCase {
                 input: "{1: 2}[2 - 1]".to_string(),
-                expected_constants: vec![
-                    Object::INTEGER(1),
-                    Object::INTEGER(2),
-                    Object::INTEGER(2),
-                    Object::INTEGER(1),
-                ],
+                expected_constants: vec![Object::INTEGER(1), Object::INTEGER(2)],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Constant.make(vec![0]),
                     Opcode::Constant.make(vec![1]),
                     Opcode::HashMap.make(vec![2]),
-                    Opcode::Constant.make(vec![2]),
-                    Opcode::Constant.make(vec![3]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::Constant.make(vec![0]),
                     Opcode::Sub.make(vec![]),
                     Opcode::Index.make(vec![]),
                     Opcode::Pop.make(vec![]),
@@ -532,12 +530,12 @@ pub mod tests {
                 let a = a + 1;"
                 .to_string(),
 
-            expected_constants: vec![Object::INTEGER(1), Object::INTEGER(1)],
+            expected_constants: vec![Object::INTEGER(1)],
             expected_instructions: flatten_instructions(vec![
                 Opcode::Constant.make(vec![0]),
                 Opcode::SetGlobal.make(vec![0]),
                 Opcode::GetGlobal.make(vec![0]),
-                Opcode::Constant.make(vec![1]),
+                Opcode::Constant.make(vec![0]),
                 Opcode::Add.make(vec![]),
                 Opcode::SetGlobal.make(vec![0]),
             ]),
@@ -545,4 +543,268 @@ pub mod tests {
 
         run_compiler(tests);
     }
+
+    #[test]
+    fn test_constants_are_interned() {
+        let function = Object::COMPILEDFUNCTION(CompiledFunction {
+            instructions: flatten_u8_instructions(vec![
+                Opcode::Constant.make(vec![1]),
+                Opcode::ReturnValue.make(vec![]),
+            ]),
+            num_locals: 0,
+            num_parameters: 0,
+            ..Default::default()
+        });
+        let tests = vec![CompilerTestCase {
+            input: r#""a"; 1; "a"; 1; fn() { 1 }; fn() { 1 }"#.to_string(),
+            expected_constants: vec![
+                Object::STRING("a".to_string()),
+                Object::INTEGER(1),
+                function.clone(),
+                // Functions written at different places are kept apart
+                function,
+            ],
+            expected_instructions: flatten_instructions(vec![
+                Opcode::Constant.make(vec![0]),
+                Opcode::Pop.make(vec![]),
+                Opcode::Constant.make(vec![1]),
+                Opcode::Pop.make(vec![]),
+                Opcode::Constant.make(vec![0]),
+                Opcode::Pop.make(vec![]),
+                Opcode::Constant.make(vec![1]),
+                Opcode::Pop.make(vec![]),
+                Opcode::Closure.make(vec![2, 0]),
+                Opcode::Pop.make(vec![]),
+                Opcode::Closure.make(vec![3, 0]),
+                Opcode::Pop.make(vec![]),
+            ]),
+        }];
+
+        run_compiler(tests);
+    }
+
+    #[test]
+    fn test_constants_are_interned_across_compilations() {
+        let mut compiler = Compiler::new();
+        compiler.compile(parse(r#"1; "hello""#)).unwrap();
+        let constants = compiler.bytecode().constants;
+
+        let mut compiler = Compiler::new_with_state(compiler.symbol_table, constants.clone());
+        compiler.compile(parse(r#""hello"; 1; 2"#)).unwrap();
+
+        let mut expected = constants;
+        expected.push(Object::INTEGER(2));
+        assert_eq!(compiler.bytecode().constants, expected);
+    }
+
+    #[test]
+    fn test_too_many_constants() {
+        let fits: Vec<String> = (0..MAX_CONSTANTS).map(|x| x.to_string()).collect();
+        let mut compiler = Compiler::new();
+        assert_eq!(compiler.compile(parse(&fits.join(";"))), Ok(()));
+
+        let input = format!("{}; {MAX_CONSTANTS}", fits.join(";"));
+        let mut compiler = Compiler::new();
+        assert_eq!(
+            compiler.compile(parse(&input)),
+            Err(format!(
+                "Too many constants, a program can use at most {MAX_CONSTANTS}"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_wide_operands() {
+        let elements = vec!["1"; 70000];
+        let mut expected = vec![Opcode::Constant.make(vec![0]); 70000];
+        expected.push(Opcode::Array.make(vec![70000]));
+        expected.push(Opcode::Pop.make(vec![]));
+
+        let tests = vec![CompilerTestCase {
+            input: format!("[{}]", elements.join(", ")),
+            expected_constants: vec![Object::INTEGER(1)],
+            expected_instructions: flatten_instructions(expected),
+        }];
+
+        run_compiler(tests);
+    }
+
+    #[test]
+    fn test_too_many_globals() {
+        let lets: String = (0..=GLOBALS_SIZE)
+            .map(|i| {
+                // Identifiers cannot contain digits
+                let name: String = i
+                    .to_string()
+                    .chars()
+                    .map(|c| (b'a' + c.to_digit(10).unwrap() as u8) as char)
+                    .collect();
+                format!("let v{name} = 1;")
+            })
+            .collect();
+
+        let mut compiler = Compiler::new();
+        assert_eq!(
+            compiler.compile(parse(&lets)),
+            Err(format!(
+                "Too many global variables, a program can use at most {GLOBALS_SIZE}"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_long_forward_jumps() {
+        // The jump over the body is emitted before the body, with a 2-byte
+        // operand, and widened once its target is known
+        let input = format!("if (true) {{ {} }}", "1;".repeat(20000));
+
+        let mut instructions = vec![
+            Opcode::True.make(vec![]),               // 000000
+            Opcode::JumpNotTruthy.make(vec![80012]), // 000001
+        ];
+        for _ in 0..19999 {
+            instructions.push(Opcode::Constant.make(vec![0]));
+            instructions.push(Opcode::Pop.make(vec![]));
+        }
+        instructions.extend([
+            Opcode::Constant.make(vec![0]), // 080003
+            Opcode::Jump.make(vec![80013]), // 080006
+            Opcode::Null.make(vec![]),      // 080012
+            Opcode::Pop.make(vec![]),       // 080013
+        ]);
+
+        run_compiler(vec![CompilerTestCase {
+            input,
+            expected_constants: vec![Object::INTEGER(1)],
+            expected_instructions: flatten_instructions(instructions),
+        }]);
+    }
+
+    #[test]
+    fn test_try_statements() {
+        let tests = vec![
+            CompilerTestCase {
+                input: "try { throw 1; } catch (e) { e }".to_string(),
+                expected_constants: vec![Object::INTEGER(1)],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::Try.make(vec![11]),      // 000
+                    Opcode::Constant.make(vec![0]),  // 003
+                    Opcode::Throw.make(vec![]),      // 006
+                    Opcode::EndTry.make(vec![]),     // 007
+                    Opcode::Jump.make(vec![18]),     // 008
+                    Opcode::SetGlobal.make(vec![0]), // 011
+                    Opcode::GetGlobal.make(vec![0]), // 014
+                    Opcode::Pop.make(vec![]),        // 017
+                                                     // 018
+                ]),
+            },
+            // The handlers inside a loop are removed before leaving it
+            CompilerTestCase {
+                input: "while (true) { try { break; } catch (e) {} }".to_string(),
+                expected_constants: vec![],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::True.make(vec![]),            // 000
+                    Opcode::JumpNotTruthy.make(vec![21]), // 001
+                    Opcode::Try.make(vec![15]),           // 004
+                    Opcode::EndTry.make(vec![]),          // 007
+                    Opcode::Jump.make(vec![21]),          // 008
+                    Opcode::EndTry.make(vec![]),          // 011
+                    Opcode::Jump.make(vec![18]),          // 012
+                    Opcode::SetGlobal.make(vec![0]),      // 015
+                    Opcode::Jump.make(vec![0]),           // 018
+                                                          // 021
+                ]),
+            },
+        ];
+
+        run_compiler(tests);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let tests = vec![
+            CompilerTestCase {
+                input: "let a = 1; a = 2;".to_string(),
+                expected_constants: vec![Object::INTEGER(1), Object::INTEGER(2)],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::Constant.make(vec![0]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::GetGlobal.make(vec![0]),
+                    Opcode::Pop.make(vec![]),
+                ]),
+            },
+            CompilerTestCase {
+                input: "let a = 1; a += 2;".to_string(),
+                expected_constants: vec![Object::INTEGER(1), Object::INTEGER(2)],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::Constant.make(vec![0]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::GetGlobal.make(vec![0]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::Add.make(vec![]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::GetGlobal.make(vec![0]),
+                    Opcode::Pop.make(vec![]),
+                ]),
+            },
+            CompilerTestCase {
+                input: "let a = [[1]]; a[0][0] = 2;".to_string(),
+                expected_constants: vec![
+                    Object::INTEGER(1),
+                    Object::INTEGER(0),
+                    Object::INTEGER(2),
+                ],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::Constant.make(vec![0]),
+                    Opcode::Array.make(vec![1]),
+                    Opcode::Array.make(vec![1]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::GetGlobal.make(vec![0]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::Constant.make(vec![2]),
+                    Opcode::SetIndex.make(vec![2]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::Pop.make(vec![]),
+                ]),
+            },
+            CompilerTestCase {
+                input: r#"let h = {}; h["k"] *= 2;"#.to_string(),
+                expected_constants: vec![Object::STRING("k".to_string()), Object::INTEGER(2)],
+                expected_instructions: flatten_instructions(vec![
+                    Opcode::HashMap.make(vec![0]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::GetGlobal.make(vec![0]),
+                    Opcode::Constant.make(vec![0]),
+                    Opcode::PeekIndex.make(vec![1]),
+                    Opcode::Constant.make(vec![1]),
+                    Opcode::Mul.make(vec![]),
+                    Opcode::SetIndex.make(vec![1]),
+                    Opcode::SetGlobal.make(vec![0]),
+                    Opcode::Pop.make(vec![]),
+                ]),
+            },
+        ];
+
+        run_compiler(tests);
+    }
+
+    #[test]
+    fn test_invalid_assignments() {
+        let tests = vec![
+            ("a = 1", "Undefined variable: a"),
+            ("len = 1", "Cannot assign to len"),
+        ];
+
+        for (input, expected) in tests {
+            let mut compiler = Compiler::new();
+            assert_eq!(
+                compiler.compile(parse(input)),
+                Err(expected.to_string()),
+                "{input}"
+            );
+        }
+    }
 }
@@ -0,0 +1,217 @@
+//! Human readable listing of compiled programs.
+//!
+//! The main program is listed first, then the constant pool in order, with the
+//! instructions of every function after a header giving its number of
+//! parameters, locals and free variables. Instructions are printed like
+//! [`Instructions`] does, with the value of the constants they load, the name
+//! of the builtins they get and labels for the targets of the jumps.
+//!
+//! The listing is written in the syntax of the [`assembler`], and assembles
+//! back to the same program.
+//!
+//! [`assembler`]: crate::compiler::assembler
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    compiler::{
+        code::{read_instruction, Instructions, Opcode},
+        Bytecode,
+    },
+    lexer::escape,
+    object::{builtins::BuiltinFunction, CompiledFunction, Object},
+};
+
+/// A decoded instruction and the position of its opcode.
+struct Instruction {
+    position: usize,
+    opcode: Opcode,
+    operands: Vec<i32>,
+}
+
+fn decode(instructions: &[u8]) -> Result<Vec<Instruction>, String> {
+    let mut decoded = Vec::new();
+    let mut position = 0;
+    while position < instructions.len() {
+        let (opcode, operands, size) = read_instruction(&instructions[position..])
+            .map_err(|err| format!("{err} at {position:04}"))?;
+        decoded.push(Instruction {
+            position,
+            opcode,
+            operands,
+        });
+        position += size;
+    }
+    Ok(decoded)
+}
+
+/// Lists the whole program: the main instructions, then every constant, the
+/// functions with their instructions.
+pub fn disassemble(bytecode: &Bytecode) -> Result<String, String> {
+    let mut output = String::from("; main\n");
+    output.push_str(&disassemble_instructions(
+        &bytecode.instructions.data,
+        &bytecode.constants,
+    )?);
+
+    if !bytecode.constants.is_empty() {
+        output.push_str("\n; constants\n");
+    }
+    let free = free_counts(bytecode)?;
+    let mut names = HashSet::new();
+    let mut after_function = false;
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::COMPILEDFUNCTION(function) = constant {
+            // Names only need to be unique in the listing, the operands use
+            // the indices
+            let name = match &function.debug.name {
+                Some(name) if !names.contains(name) => name.clone(),
+                _ => format!("function_{index}"),
+            };
+            let mut header = format!(
+                "fn {name} params={} locals={}",
+                function.num_parameters, function.num_locals
+            );
+            if let Some(num_free) = free.get(&index) {
+                header.push_str(&format!(" free={num_free}"));
+            }
+            header.push_str(" {");
+            names.insert(name);
+            if index > 0 {
+                output.push('\n');
+            }
+            output.push_str(&annotate(&header, &format!("constant {index}")));
+            output.push_str(&disassemble_instructions(
+                &function.instructions,
+                &bytecode.constants,
+            )?);
+            output.push_str("}\n");
+            after_function = true;
+        } else {
+            let literal = literal(constant)
+                .ok_or_else(|| format!("Constant {index} cannot be listed: {constant}"))?;
+            let line = format!("const {literal}");
+            if after_function {
+                output.push('\n');
+            }
+            output.push_str(&annotate(&line, &format!("constant {index}")));
+            after_function = false;
+        }
+    }
+
+    Ok(output)
+}
+
+/// The number of free variables of the functions, as given by the
+/// `OpClosure` instructions that create them. A function that is never made
+/// into a closure has none.
+fn free_counts(bytecode: &Bytecode) -> Result<HashMap<usize, i32>, String> {
+    let mut free = HashMap::new();
+    let functions = bytecode
+        .constants
+        .iter()
+        .filter_map(|constant| match constant {
+            Object::COMPILEDFUNCTION(function) => Some(&function.instructions),
+            _ => None,
+        });
+    for instructions in std::iter::once(&bytecode.instructions.data).chain(functions) {
+        for instruction in decode(instructions)? {
+            if instruction.opcode == Opcode::Closure {
+                free.entry(instruction.operands[0] as usize)
+                    .or_insert(instruction.operands[1]);
+            }
+        }
+    }
+    Ok(free)
+}
+
+/// The constant written as a literal of the assembler.
+fn literal(constant: &Object) -> Option<String> {
+    match constant {
+        Object::INTEGER(x) => Some(x.to_string()),
+        Object::FLOAT(x) if x.is_finite() => Some(format!("{x:?}")),
+        Object::STRING(x) => Some(format!("\"{}\"", escape(x))),
+        _ => None,
+    }
+}
+
+fn disassemble_instructions(instructions: &[u8], constants: &[Object]) -> Result<String, String> {
+    let decoded = decode(instructions)?;
+
+    let labels: BTreeMap<usize, String> = {
+        let mut targets: Vec<usize> = decoded
+            .iter()
+            .filter(|instruction| is_jump(instruction.opcode))
+            .map(|instruction| instruction.operands[0] as usize)
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+            .into_iter()
+            .enumerate()
+            .map(|(i, target)| (target, format!("L{i}")))
+            .collect()
+    };
+
+    let mut output = String::new();
+    for instruction in &decoded {
+        if let Some(label) = labels.get(&instruction.position) {
+            output.push_str(&format!("{label}:\n"));
+        }
+        let widths = instruction.opcode.lookup_widths();
+        let text = Instructions::default().format_instruction(
+            instruction.opcode,
+            &widths,
+            &instruction.operands,
+        );
+        let line = format!("{:04} {text}", instruction.position);
+        match annotation(instruction, constants, &labels) {
+            Some(annotation) => output.push_str(&annotate(&line, &annotation)),
+            None => output.push_str(&format!("{line}\n")),
+        }
+    }
+    // A jump past the last instruction, at the end of a loop or a conditional
+    if let Some(label) = labels.get(&instructions.len()) {
+        output.push_str(&format!("{label}:\n"));
+    }
+    Ok(output)
+}
+
+/// The line with a comment, aligned with the others.
+fn annotate(line: &str, comment: &str) -> String {
+    format!("{line:<31} ; {comment}\n")
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Jump | Opcode::JumpNotTruthy | Opcode::Try)
+}
+
+fn annotation(
+    instruction: &Instruction,
+    constants: &[Object],
+    labels: &BTreeMap<usize, String>,
+) -> Option<String> {
+    let operand = *instruction.operands.first()?;
+    match instruction.opcode {
+        Opcode::Constant => Some(match constants.get(operand as usize) {
+            Some(Object::COMPILEDFUNCTION(function)) => describe_function(operand, function),
+            // Escaped, a raw newline or quote would end the comment or the
+            // string when the listing is read back
+            Some(constant) => literal(constant).unwrap_or_else(|| constant.to_string()),
+            None => String::from("<missing constant>"),
+        }),
+        Opcode::Closure => Some(format!("function {operand}")),
+        Opcode::GetBuiltin => Some(match BuiltinFunction::get_builtin_by_id(operand as usize) {
+            Some(builtin) => builtin.to_string(),
+            None => String::from("<unknown builtin>"),
+        }),
+        Opcode::Jump | Opcode::JumpNotTruthy | Opcode::Try => {
+            labels.get(&(operand as usize)).cloned()
+        }
+        _ => None,
+    }
+}
+
+fn describe_function(index: i32, function: &CompiledFunction) -> String {
+    format!("function {index} (parameters: {})", function.num_parameters)
+}
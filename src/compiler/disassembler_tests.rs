@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{disassembler::disassemble, test_utils::compile};
+
+    fn check_disassembly(input: &str, expected: &str) {
+        let got = disassemble(&compile(input)).unwrap();
+        println!("{got}");
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn test_constants_and_builtins() {
+        check_disassembly(
+            r#"puts("monkey", 1 + 2)"#,
+            r#"; main
+0000 OpGetBuiltin 5             ; puts
+0002 OpConstant 0               ; "monkey"
+0005 OpConstant 1               ; 1
+0008 OpConstant 2               ; 2
+0011 OpAdd
+0012 OpCall 2
+0014 OpPop
+
+; constants
+const "monkey"                  ; constant 0
+const 1                         ; constant 1
+const 2                         ; constant 2
+"#,
+        );
+    }
+
+    #[test]
+    fn test_escaped_strings() {
+        check_disassembly(
+            r#""a\n\"b\"\\""#,
+            r#"; main
+0000 OpConstant 0               ; "a\n\"b\"\\"
+0003 OpPop
+
+; constants
+const "a\n\"b\"\\"              ; constant 0
+"#,
+        );
+    }
+
+    #[test]
+    fn test_jump_labels() {
+        check_disassembly(
+            "if (true) { 10 } else { 20 }; 3333;",
+            r"; main
+0000 OpTrue
+0001 OpJumpNotTruthy 10         ; L0
+0004 OpConstant 0               ; 10
+0007 OpJump 13                  ; L1
+L0:
+0010 OpConstant 1               ; 20
+L1:
+0013 OpPop
+0014 OpConstant 2               ; 3333
+0017 OpPop
+
+; constants
+const 10                        ; constant 0
+const 20                        ; constant 1
+const 3333                      ; constant 2
+",
+        );
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        check_disassembly(
+            r"
+            let new_adder = fn(a) {
+                fn(b) { a + b }
+            };
+            new_adder(1)(2);
+            ",
+            r"; main
+0000 OpClosure 1 0              ; function 1
+0004 OpSetGlobal 0
+0007 OpGetGlobal 0
+0010 OpConstant 2               ; 1
+0013 OpCall 1
+0015 OpConstant 3               ; 2
+0018 OpCall 1
+0020 OpPop
+
+; constants
+fn function_0 params=1 locals=1 free=1 { ; constant 0
+0000 OpGetFree 0
+0002 OpGetLocal 0
+0004 OpAdd
+0005 OpReturnValue
+}
+
+fn new_adder params=1 locals=1 free=0 { ; constant 1
+0000 OpClosure 0 1              ; function 0
+0004 OpCaptureLocal 0
+0006 OpReturnValue
+}
+
+const 1                         ; constant 2
+const 2                         ; constant 3
+",
+        );
+    }
+}
@@ -29,6 +29,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -50,6 +51,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -71,6 +73,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -89,6 +92,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -108,6 +112,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -125,6 +130,7 @@ pub mod tests {
                         instructions: flatten_u8_instructions(vec![Opcode::Return.make(vec![0])]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                     Object::INTEGER(24),
                 ],
@@ -144,6 +150,7 @@ pub mod tests {
                         instructions: flatten_u8_instructions(vec![Opcode::Return.make(vec![0])]),
                         num_locals: 3,
                         num_parameters: 3,
+                        ..Default::default()
                     }),
                     Object::INTEGER(24),
                     Object::INTEGER(25),
@@ -170,6 +177,7 @@ pub mod tests {
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                     Object::INTEGER(24),
                 ],
@@ -196,6 +204,7 @@ pub mod tests {
                         ]),
                         num_locals: 3,
                         num_parameters: 3,
+                        ..Default::default()
                     }),
                     Object::INTEGER(24),
                     Object::INTEGER(25),
@@ -225,6 +234,7 @@ pub mod tests {
                 instructions: flatten_u8_instructions(vec![Opcode::Return.make(vec![])]),
                 num_locals: 0,
                 num_parameters: 0,
+                ..Default::default()
             })],
             expected_instructions: flatten_instructions(vec![
                 Opcode::Closure.make(vec![0, 0]),
@@ -252,6 +262,7 @@ pub mod tests {
                         ]),
                         num_locals: 0,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -279,6 +290,7 @@ pub mod tests {
                         ]),
                         num_locals: 1,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -310,6 +322,7 @@ pub mod tests {
                         ]),
                         num_locals: 2,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -345,11 +358,12 @@ pub mod tests {
                     instructions: flatten_u8_instructions(vec![
                         Opcode::GetBuiltin.make(vec![0]),
                         Opcode::Array.make(vec![0]),
-                        Opcode::Call.make(vec![1]),
+                        Opcode::TailCall.make(vec![1]),
                         Opcode::ReturnValue.make(vec![]),
                     ]),
                     num_locals: 0,
                     num_parameters: 0,
+                    ..Default::default()
                 })],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Closure.make(vec![0, 0]),
@@ -383,15 +397,17 @@ pub mod tests {
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
-                            Opcode::GetLocal.make(vec![0]),
                             Opcode::Closure.make(vec![0, 1]),
+                            Opcode::CaptureLocal.make(vec![0]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -422,25 +438,28 @@ pub mod tests {
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
-                            Opcode::GetFree.make(vec![0]),
-                            Opcode::GetLocal.make(vec![0]),
                             Opcode::Closure.make(vec![0, 2]),
+                            Opcode::CaptureFree.make(vec![0]),
+                            Opcode::CaptureLocal.make(vec![0]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
-                            Opcode::GetLocal.make(vec![0]),
                             Opcode::Closure.make(vec![1, 1]),
+                            Opcode::CaptureLocal.make(vec![0]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -483,29 +502,32 @@ pub mod tests {
                         ]),
                         num_locals: 1,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
                             Opcode::Constant.make(vec![2]),
                             Opcode::SetLocal.make(vec![0]),
-                            Opcode::GetFree.make(vec![0]),
-                            Opcode::GetLocal.make(vec![0]),
                             Opcode::Closure.make(vec![4, 2]),
+                            Opcode::CaptureFree.make(vec![0]),
+                            Opcode::CaptureLocal.make(vec![0]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
                             Opcode::Constant.make(vec![1]),
                             Opcode::SetLocal.make(vec![0]),
-                            Opcode::GetLocal.make(vec![0]),
                             Opcode::Closure.make(vec![5, 1]),
+                            Opcode::CaptureLocal.make(vec![0]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
@@ -538,19 +560,19 @@ pub mod tests {
                             Opcode::GetLocal.make(vec![0]),
                             Opcode::Constant.make(vec![0]),
                             Opcode::Sub.make(vec![]),
-                            Opcode::Call.make(vec![1]),
+                            Opcode::TailCall.make(vec![1]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
-                    Object::INTEGER(1),
                 ],
                 expected_instructions: flatten_instructions(vec![
                     Opcode::Closure.make(vec![1, 0]),
                     Opcode::SetGlobal.make(vec![0]),
                     Opcode::GetGlobal.make(vec![0]),
-                    Opcode::Constant.make(vec![2]),
+                    Opcode::Constant.make(vec![0]),
                     Opcode::Call.make(vec![1]),
                     Opcode::Pop.make(vec![]),
                 ]),
@@ -574,28 +596,29 @@ pub mod tests {
                             Opcode::GetLocal.make(vec![0]),
                             Opcode::Constant.make(vec![0]),
                             Opcode::Sub.make(vec![]),
-                            Opcode::Call.make(vec![1]),
+                            Opcode::TailCall.make(vec![1]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 1,
+                        ..Default::default()
                     }),
-                    Object::INTEGER(1),
                     Object::COMPILEDFUNCTION(CompiledFunction {
                         instructions: flatten_u8_instructions(vec![
                             Opcode::Closure.make(vec![1, 0]),
                             Opcode::SetLocal.make(vec![0]),
                             Opcode::GetLocal.make(vec![0]),
-                            Opcode::Constant.make(vec![2]),
-                            Opcode::Call.make(vec![1]),
+                            Opcode::Constant.make(vec![0]),
+                            Opcode::TailCall.make(vec![1]),
                             Opcode::ReturnValue.make(vec![]),
                         ]),
                         num_locals: 1,
                         num_parameters: 0,
+                        ..Default::default()
                     }),
                 ],
                 expected_instructions: flatten_instructions(vec![
-                    Opcode::Closure.make(vec![3, 0]),
+                    Opcode::Closure.make(vec![2, 0]),
                     Opcode::SetGlobal.make(vec![0]),
                     Opcode::GetGlobal.make(vec![0]),
                     Opcode::Call.make(vec![0]),
@@ -606,4 +629,43 @@ pub mod tests {
 
         run_compiler(tests);
     }
+
+    #[test]
+    fn test_assignment_to_free_variables() {
+        let tests = vec![CompilerTestCase {
+            input: "fn(a) { fn() { a += 1 } }".to_string(),
+            expected_constants: vec![
+                Object::INTEGER(1),
+                Object::COMPILEDFUNCTION(CompiledFunction {
+                    instructions: flatten_u8_instructions(vec![
+                        Opcode::GetFree.make(vec![0]),
+                        Opcode::Constant.make(vec![0]),
+                        Opcode::Add.make(vec![]),
+                        Opcode::SetFree.make(vec![0]),
+                        Opcode::GetFree.make(vec![0]),
+                        Opcode::ReturnValue.make(vec![]),
+                    ]),
+                    num_locals: 0,
+                    num_parameters: 0,
+                    ..Default::default()
+                }),
+                Object::COMPILEDFUNCTION(CompiledFunction {
+                    instructions: flatten_u8_instructions(vec![
+                        Opcode::Closure.make(vec![1, 1]),
+                        Opcode::CaptureLocal.make(vec![0]),
+                        Opcode::ReturnValue.make(vec![]),
+                    ]),
+                    num_locals: 1,
+                    num_parameters: 1,
+                    ..Default::default()
+                }),
+            ],
+            expected_instructions: flatten_instructions(vec![
+                Opcode::Closure.make(vec![2, 0]),
+                Opcode::Pop.make(vec![]),
+            ]),
+        }];
+
+        run_compiler(tests);
+    }
 }
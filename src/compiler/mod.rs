@@ -1,26 +1,35 @@
+pub mod assembler;
+mod assembler_tests;
 pub mod code;
 mod compiler_tests;
+pub mod disassembler;
+mod disassembler_tests;
 mod function_tests;
+pub mod optimizer;
+mod optimizer_tests;
+pub mod serialization;
+mod serialization_tests;
 pub mod symbol_table;
-mod test_utils;
+pub(crate) mod test_utils;
 mod while_tests;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
     compiler::{
-        code::{Instructions, Opcode},
+        code::{read_instruction, Instructions, Opcode},
         symbol_table::{Symbol, SymbolScope, SymbolTable},
     },
-    lexer::token::Token,
+    lexer::token::{Span, Token},
     object::{
         builtins::BuiltinFunction,
-        {CompiledFunction, Object},
+        {CompiledFunction, DebugInfo, LineTable, Object},
     },
     parser::ast::{
-        BlockStatement, Conditional, Expression, FunctionLiteral, InfixOperator, LetStatement,
-        LoopStatement, Primitive, Program, Statement, WhileStatement,
+        Assignment, BlockStatement, Conditional, Expression, FunctionLiteral, LetStatement,
+        LoopStatement, Primitive, Program, Statement, TryStatement, WhileStatement,
     },
+    vm::GLOBALS_SIZE,
 };
 
 use num_traits::FromPrimitive;
@@ -37,6 +46,14 @@ struct CompilerScope {
     last_instruction: Option<EmittedInstruction>,
     previous_instruction: Option<EmittedInstruction>,
     loop_scope: Option<Rc<RefCell<LoopScope>>>,
+    /// Number of `try` bodies the instructions being emitted are in.
+    try_depth: usize,
+    /// Line table of the instructions, see [`DebugInfo::lines`].
+    lines: LineTable,
+    /// Forward jumps emitted with a 2-byte operand whose target turned out to
+    /// be too far for it, with their targets. The instructions are laid out
+    /// again once the scope is compiled, see [`Compiler::lay_out_long_jumps`].
+    long_jumps: Vec<(usize, usize)>,
 }
 
 impl Default for CompilerScope {
@@ -52,11 +69,15 @@ impl CompilerScope {
             last_instruction: None,
             previous_instruction: None,
             loop_scope: None,
+            try_depth: 0,
+            lines: Vec::new(),
+            long_jumps: Vec::new(),
         }
     }
 
     fn enter_loop_scope(&mut self, start_position: usize) {
-        let loop_scope = LoopScope::new_enclosed(self.loop_scope.clone(), start_position);
+        let loop_scope =
+            LoopScope::new_enclosed(self.loop_scope.clone(), start_position, self.try_depth);
         self.loop_scope = Some(Rc::new(RefCell::new(loop_scope)));
     }
 
@@ -72,14 +93,22 @@ struct LoopScope {
     outer: Option<Rc<RefCell<LoopScope>>>,
     start_position: usize,
     breaks: Vec<usize>,
+    /// Number of `try` bodies the loop is in, the handlers of the ones inside
+    /// the loop are removed before leaving it with a `break` or a `continue`.
+    try_depth: usize,
 }
 
 impl LoopScope {
-    pub fn new_enclosed(outer: Option<Rc<RefCell<LoopScope>>>, start_position: usize) -> Self {
+    pub fn new_enclosed(
+        outer: Option<Rc<RefCell<LoopScope>>>,
+        start_position: usize,
+        try_depth: usize,
+    ) -> Self {
         Self {
             outer,
             start_position,
             breaks: vec![],
+            try_depth,
         }
     }
 
@@ -92,13 +121,21 @@ impl LoopScope {
     }
 }
 
+/// Number of constants that `OpConstant` and `OpClosure` can address.
+pub const MAX_CONSTANTS: usize = 1 << 16;
+
 pub struct Compiler {
     pub constants: Vec<Object>,
+    /// Index of each constant in `constants`, so that equal values share a slot.
+    constant_indices: HashMap<Object, usize>,
 
     pub symbol_table: SymbolTable,
 
     scopes: Vec<CompilerScope>,
     scope_index: usize,
+
+    /// Position of the expressions being compiled, the innermost one last.
+    spans: Vec<Span>,
 }
 
 impl Default for Compiler {
@@ -109,31 +146,46 @@ impl Default for Compiler {
 
 impl Compiler {
     pub fn new() -> Self {
+        Self::with_builtins(&BuiltinFunction::get_builtins_names())
+    }
+
+    /// Creates a compiler where builtin `i` is the one called `names[i]`.
+    pub(crate) fn with_builtins(names: &[String]) -> Self {
         let main_scope = CompilerScope::default();
         let mut symbol_table = SymbolTable::new();
-        for (i, builtin) in BuiltinFunction::get_builtins_names().iter().enumerate() {
+        for (i, builtin) in names.iter().enumerate() {
             symbol_table.define_builtin(i, builtin.to_string());
         }
 
         Compiler {
             constants: vec![],
+            constant_indices: HashMap::new(),
 
             symbol_table,
 
             scopes: vec![main_scope],
             scope_index: 0,
+
+            spans: vec![],
         }
     }
 
     pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Self {
         let mut compiler = Compiler::new();
         compiler.symbol_table = symbol_table;
+        for (i, constant) in constants.iter().enumerate() {
+            compiler
+                .constant_indices
+                .entry(constant.clone())
+                .or_insert(i);
+        }
         compiler.constants = constants;
         compiler
     }
 
     pub fn compile(&mut self, program: Program) -> Result<(), String> {
-        self.compile_statements(program.statements)
+        self.compile_statements(program.statements)?;
+        self.lay_out_long_jumps()
     }
 
     fn compile_block_statement(&mut self, block: BlockStatement) -> Result<(), String> {
@@ -165,18 +217,33 @@ impl Compiler {
                 self.compile_while_statement(wh)?;
             }
 
-            Statement::LoopStatements(smt) => self.compile_loop_statement(&smt),
+            Statement::LoopStatements(smt, _) => self.compile_loop_statement(&smt)?,
+            Statement::Try(tr) => {
+                self.compile_try_statement(tr)?;
+            }
+            Statement::Throw(th) => {
+                self.compile_expression(th.value)?;
+                self.emit(Opcode::Throw, vec![]);
+            }
         }
 
         Ok(())
     }
 
     fn compiler_let_statement(&mut self, s: LetStatement) -> Result<(), String> {
+        let symbol = self.define_variable(s.name.value)?;
+        self.compile_expression(s.value)?;
+        self.emit_set_variable(&symbol);
+        Ok(())
+    }
+
+    /// Symbol a `let` (or a `catch`) binds `name` to.
+    fn define_variable(&mut self, name: String) -> Result<Symbol, String> {
         // This step is extremely important. If it is not done then when shadowing variables
         // and using the previous value we get an error. Because we would have assigned
         // a new index to the symbol and the GetGlobal instruction would get a NULL
         // value instead of the previous value. (corresponds to issue #8)
-        let symbol = match self.symbol_table.resolve(&s.name.value) {
+        let symbol = match self.symbol_table.resolve(&name) {
             Some(symbol) => match symbol.scope {
                 SymbolScope::Global => {
                     // A Local variable should never replace a global one
@@ -184,7 +251,7 @@ impl Compiler {
                         // This means that the symbol will
                         // be local and not global, and thus not
                         // replace the global one
-                        self.symbol_table.define(s.name.value)
+                        self.symbol_table.define(name)
                     } else {
                         symbol
                     }
@@ -194,13 +261,21 @@ impl Compiler {
                 // We only want to do in in the case of "normal" variable assignation.
                 // The special cases should not be touched, since the program should not
                 // have access to them, only the compiler/vm
-                _ => self.symbol_table.define(s.name.value),
+                _ => self.symbol_table.define(name),
             },
-            None => self.symbol_table.define(s.name.value),
+            None => self.symbol_table.define(name),
         };
+        if symbol.scope == SymbolScope::Global && symbol.index >= GLOBALS_SIZE {
+            return Err(format!(
+                "Too many global variables, a program can use at most {GLOBALS_SIZE}"
+            ));
+        }
+        Ok(symbol)
+    }
 
-        self.compile_expression(s.value)?;
-
+    /// Emits the instruction that pops the top of the stack into `symbol`, a
+    /// free symbol only being set by an assignment.
+    fn emit_set_variable(&mut self, symbol: &Symbol) {
         match symbol.scope {
             SymbolScope::Global => {
                 self.emit(Opcode::SetGlobal, vec![symbol.index as i32]);
@@ -209,9 +284,7 @@ impl Compiler {
                 self.emit(Opcode::SetLocal, vec![symbol.index as i32]);
             }
             SymbolScope::Free => {
-                unreachable!(
-                    "Free symbols should not be set, the compiler should panic before this"
-                )
+                self.emit(Opcode::SetFree, vec![symbol.index as i32]);
             }
             SymbolScope::Builtin => {
                 unreachable!(
@@ -224,25 +297,29 @@ impl Compiler {
                 )
             }
         }
-
-        Ok(())
     }
 
     fn compile_expression(&mut self, expression: Expression) -> Result<(), String> {
+        // The instructions emitted after the operands, such as the operator
+        // itself, get back the position of the whole expression
+        self.spans.push(expression.span());
+        let result = self.compile_expression_kind(expression);
+        self.spans.pop();
+        result
+    }
+
+    fn compile_expression_kind(&mut self, expression: Expression) -> Result<(), String> {
         match expression {
-            Expression::Infix(infix) => match infix.token {
-                Token::LT | Token::LTE => self.compile_lt_and_lte(infix)?,
-                _ => {
-                    self.compile_expression(*infix.left)?;
-                    self.compile_expression(*infix.right)?;
-                    self.compile_infix_operator(&infix.token)?;
-                }
-            },
+            Expression::Infix(infix) => {
+                self.compile_expression(*infix.left)?;
+                self.compile_expression(*infix.right)?;
+                self.compile_infix_operator(&infix.token)?;
+            }
             Expression::Prefix(prefix) => {
                 self.compile_expression(*prefix.right)?;
                 self.compile_prefix_operator(&prefix.token)?;
             }
-            Expression::Primitive(primitive) => self.compile_primitive(primitive)?,
+            Expression::Primitive(primitive, _) => self.compile_primitive(primitive)?,
             Expression::Conditional(conditional) => self.compile_conditional(conditional)?,
             Expression::Identifier(ident) => {
                 let symbol = self.symbol_table.resolve(&ident.value);
@@ -274,6 +351,7 @@ impl Compiler {
                 self.compile_expression(*index.index)?;
                 self.emit(Opcode::Index, vec![]);
             }
+            Expression::Assignment(assignment) => self.compile_assignment(assignment)?,
             Expression::FunctionLiteral(fun) => {
                 self.compile_function_literal(fun)?;
             }
@@ -298,8 +376,14 @@ impl Compiler {
         match primitive {
             Primitive::IntegerLiteral(i) => {
                 let integer = Object::INTEGER(i);
-                let pos = self.add_constant(integer);
-                let pos = i32::from_usize(pos).ok_or("Invalid constant position")?;
+                let pos = self.add_constant(integer)?;
+                self.emit(Opcode::Constant, vec![pos]);
+            }
+            Primitive::FloatLiteral(x, _) => {
+                // Literals are finite and never negative, so equal floats are
+                // the same float and can share their constant
+                let float = Object::FLOAT(x);
+                let pos = self.add_constant(float)?;
                 self.emit(Opcode::Constant, vec![pos]);
             }
             Primitive::BooleanLiteral(true) => {
@@ -310,8 +394,7 @@ impl Compiler {
             }
             Primitive::StringLiteral(s) => {
                 let string = Object::STRING(s);
-                let pos = self.add_constant(string);
-                let pos = i32::from_usize(pos).ok_or("Invalid constant position")?;
+                let pos = self.add_constant(string)?;
                 self.emit(Opcode::Constant, vec![pos]);
             }
         }
@@ -327,6 +410,8 @@ impl Compiler {
             Token::Slash => self.emit(Opcode::Div, vec![]),
             Token::GT => self.emit(Opcode::GreaterThan, vec![]),
             Token::GTE => self.emit(Opcode::GreaterEqualThan, vec![]),
+            Token::LT => self.emit(Opcode::LessThan, vec![]),
+            Token::LTE => self.emit(Opcode::LessEqualThan, vec![]),
             Token::Equal => self.emit(Opcode::Equal, vec![]),
             Token::NotEqual => self.emit(Opcode::NotEqual, vec![]),
             Token::Or => self.emit(Opcode::Or, vec![]),
@@ -337,17 +422,6 @@ impl Compiler {
         Ok(())
     }
 
-    fn compile_lt_and_lte(&mut self, infix: InfixOperator) -> Result<(), String> {
-        self.compile_expression(*infix.right)?;
-        self.compile_expression(*infix.left)?;
-        match infix.token {
-            Token::LT => self.emit(Opcode::GreaterThan, vec![]),
-            Token::LTE => self.emit(Opcode::GreaterEqualThan, vec![]),
-            tk => return Err(format!("Unknown operator: {tk}")),
-        };
-        Ok(())
-    }
-
     fn compile_prefix_operator(&mut self, operator: &Token) -> Result<(), String> {
         match operator {
             Token::Bang => self.emit(Opcode::Bang, vec![]),
@@ -360,24 +434,16 @@ impl Compiler {
     fn compile_conditional(&mut self, conditional: Conditional) -> Result<(), String> {
         self.compile_expression(*conditional.condition)?;
 
-        let jump_not_truthy_pos = self.emit(Opcode::JumpNotTruthy, vec![9999]); // We emit a dummy value for the jump offset
-                                                                                // and we will fix it later
-        self.compile_block_statement(conditional.consequence)?;
-        if self.last_instruction_is(Opcode::Pop) {
-            self.remove_last_instruction();
-        }
+        let jump_not_truthy_pos = self.emit_jump_placeholder(Opcode::JumpNotTruthy);
+        self.compile_block_value(conditional.consequence)?;
 
-        let jump_pos = self.emit(Opcode::Jump, vec![9999]); // We emit a dummy value for the jump offset
-                                                            // and we will fix it later
+        let jump_pos = self.emit_jump_placeholder(Opcode::Jump);
 
         let after_consequence_pos = self.current_instructions().data.len();
         self.change_operand(jump_not_truthy_pos, after_consequence_pos as i32)?;
 
         if let Some(alternative) = conditional.alternative {
-            self.compile_block_statement(alternative)?;
-            if self.last_instruction_is(Opcode::Pop) {
-                self.remove_last_instruction();
-            }
+            self.compile_block_value(alternative)?;
         } else {
             self.emit(Opcode::Null, vec![]);
         }
@@ -388,11 +454,34 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compiles a block that leaves its value on the stack: the one of its
+    /// last expression, or null if it ends with another statement.
+    fn compile_block_value(&mut self, block: BlockStatement) -> Result<(), String> {
+        let ends_with_expression = Self::ends_with_expression(&block);
+        self.compile_block_statement(block)?;
+        if ends_with_expression {
+            self.remove_last_instruction();
+        } else {
+            self.emit(Opcode::Null, vec![]);
+        }
+        Ok(())
+    }
+
+    /// Whether the last instruction of the compiled block is the `OpPop` of
+    /// its last expression. Checking the instruction alone is not enough, a
+    /// block can end with a statement whose own code ends with an `OpPop`.
+    fn ends_with_expression(block: &BlockStatement) -> bool {
+        matches!(block.statements.last(), Some(Statement::Expression(_)))
+    }
+
     fn compile_function_literal(&mut self, fun: FunctionLiteral) -> Result<(), String> {
         self.enter_scope();
 
-        if let Some(name) = fun.name {
-            self.symbol_table.define_function_name(name);
+        let name = fun.name;
+        // A function that assigns to its own name changes the variable that
+        // holds it, so the name refers to that variable instead
+        if let Some(name) = name.as_ref().filter(|name| !fun.body.assigns(name)) {
+            self.symbol_table.define_function_name(name.clone());
         }
 
         let num_parameters = fun.parameters.len();
@@ -401,53 +490,113 @@ impl Compiler {
             self.symbol_table.define(param.value);
         }
 
+        let ends_with_expression = Self::ends_with_expression(&fun.body);
         self.compile_block_statement(fun.body)?;
 
-        if self.last_instruction_is(Opcode::Pop) {
+        if ends_with_expression {
             self.replace_last_pop_with_return();
         }
         if !self.last_instruction_is(Opcode::ReturnValue) {
             self.emit(Opcode::Return, vec![]);
         }
+        self.lay_out_long_jumps()?;
+        self.mark_tail_calls()?;
 
         let free_symbols = self.symbol_table.free_symbols.clone();
         let free_symbols_len = free_symbols.len();
 
         let num_locals = self.symbol_table.num_definitions;
+        let lines = std::mem::take(&mut self.scopes[self.scope_index].lines);
         let instructions = self.leave_scope().data;
 
-        for symbol in free_symbols {
-            // Te symbols must be loaded after the scope is left, but
-            // we need to get them before leaving the scope.
-            self.load_symbol(&symbol);
-        }
-
         let compiled_function = Object::COMPILEDFUNCTION(CompiledFunction {
             instructions,
             num_locals,
             num_parameters,
+            debug: DebugInfo { name, lines },
         });
 
-        let operands =
-            i32::from_usize(self.add_constant(compiled_function)).ok_or("Invalid integer type")?;
+        let operands = self.add_constant(compiled_function)?;
 
         self.emit(Opcode::Closure, vec![operands, free_symbols_len as i32]);
+        // The free symbols are captured after the scope is left, in the
+        // scope that defines them
+        for symbol in free_symbols {
+            self.capture_symbol(&symbol);
+        }
 
         Ok(())
     }
 
+    /// Emits the instruction that adds `symbol` to the free variables of the
+    /// closure on top of the stack.
+    fn capture_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            SymbolScope::Local => self.emit(Opcode::CaptureLocal, vec![symbol.index as i32]),
+            SymbolScope::Free => self.emit(Opcode::CaptureFree, vec![symbol.index as i32]),
+            SymbolScope::Function => self.emit(Opcode::CaptureCurrentClosure, vec![]),
+            SymbolScope::Global | SymbolScope::Builtin => {
+                unreachable!("Global and builtin symbols are never free")
+            }
+        };
+    }
+
+    /// Compiles `target = value` or `target op= value`.
+    ///
+    /// For an index target the variable, the indices and the new value go on
+    /// the stack, `OpSetIndex` leaves the value under the new container and
+    /// the container is stored back in the variable.
+    fn compile_assignment(&mut self, assignment: Assignment) -> Result<(), String> {
+        let operator = assignment.operator();
+        let (name, indices) = Assignment::split_target(*assignment.target);
+        let symbol = match self.symbol_table.resolve(&name.value) {
+            Some(symbol) => symbol,
+            None => return Err(format!("Undefined variable: {}", name.value)),
+        };
+        if let SymbolScope::Builtin | SymbolScope::Function = symbol.scope {
+            return Err(format!("Cannot assign to {}", name.value));
+        }
+
+        let depth = indices.len();
+        if depth == 0 {
+            if let Some(operator) = &operator {
+                self.load_symbol(&symbol);
+                self.compile_expression(*assignment.value)?;
+                self.compile_infix_operator(operator)?;
+            } else {
+                self.compile_expression(*assignment.value)?;
+            }
+            self.emit_set_variable(&symbol);
+            self.load_symbol(&symbol);
+            return Ok(());
+        }
+
+        self.load_symbol(&symbol);
+        for index in indices {
+            self.compile_expression(index)?;
+        }
+        if let Some(operator) = &operator {
+            self.emit(Opcode::PeekIndex, vec![depth as i32]);
+            self.compile_expression(*assignment.value)?;
+            self.compile_infix_operator(operator)?;
+        } else {
+            self.compile_expression(*assignment.value)?;
+        }
+        self.emit(Opcode::SetIndex, vec![depth as i32]);
+        self.emit_set_variable(&symbol);
+        Ok(())
+    }
+
     fn compile_while_statement(&mut self, wh: WhileStatement) -> Result<(), String> {
         let condition_pos = self.current_instructions().data.len();
         self.scopes[self.scope_index].enter_loop_scope(condition_pos);
 
         self.compile_expression(wh.condition)?;
 
-        let jump_not_truthy_pos = self.emit(Opcode::JumpNotTruthy, vec![9999]); // We emit a dummy value for the jump offset
-                                                                                // and we will fix it later
+        let jump_not_truthy_pos = self.emit_jump_placeholder(Opcode::JumpNotTruthy);
         self.compile_block_statement(wh.body)?;
 
-        self.emit(Opcode::Jump, vec![condition_pos as i32]); // We emit a dummy value for the jump offset
-                                                             // and we will fix it later
+        self.emit(Opcode::Jump, vec![condition_pos as i32]);
 
         let after_body_pos = self.current_instructions().data.len();
         self.change_operand(jump_not_truthy_pos, after_body_pos as i32)?;
@@ -468,30 +617,51 @@ impl Compiler {
         Ok(())
     }
 
-    fn compile_loop_statement(&mut self, smt: &LoopStatement) {
+    fn compile_try_statement(&mut self, tr: TryStatement) -> Result<(), String> {
+        let try_pos = self.emit_jump_placeholder(Opcode::Try);
+
+        self.scopes[self.scope_index].try_depth += 1;
+        let body = self.compile_block_statement(tr.body);
+        self.scopes[self.scope_index].try_depth -= 1;
+        body?;
+
+        self.emit(Opcode::EndTry, vec![]);
+        let jump_pos = self.emit_jump_placeholder(Opcode::Jump);
+
+        // The VM jumps here with the exception on the stack
+        let handler_pos = self.current_instructions().data.len();
+        self.change_operand(try_pos, handler_pos as i32)?;
+
+        let symbol = self.define_variable(tr.parameter.value)?;
+        self.emit_set_variable(&symbol);
+        self.compile_block_statement(tr.handler)?;
+
+        let after_handler_pos = self.current_instructions().data.len();
+        self.change_operand(jump_pos, after_handler_pos as i32)?;
+
+        Ok(())
+    }
+
+    fn compile_loop_statement(&mut self, smt: &LoopStatement) -> Result<(), String> {
+        let Some(loop_scope) = self.scopes[self.scope_index].loop_scope.clone() else {
+            return Err(format!("{smt} outside of a loop"));
+        };
+        let try_depth = loop_scope.borrow().try_depth;
+        for _ in try_depth..self.scopes[self.scope_index].try_depth {
+            self.emit(Opcode::EndTry, vec![]);
+        }
         match smt {
             LoopStatement::Break => {
-                let pos = self.emit(Opcode::Jump, vec![9999]); // We emit a dummy value for the jump offset
-                                                               // and we will fix it later
-                self.scopes[self.scope_index]
-                    .loop_scope
-                    .clone()
-                    .unwrap()
-                    .as_ref()
-                    .borrow_mut()
-                    .add_break(pos);
+                let pos = self.emit_jump_placeholder(Opcode::Jump);
+                loop_scope.borrow_mut().add_break(pos);
             }
             LoopStatement::Continue => {
-                let while_initial_pos = self.scopes[self.scope_index]
-                    .loop_scope
-                    .as_ref()
-                    .unwrap()
-                    .borrow()
-                    .start_position;
+                let while_initial_pos = loop_scope.borrow().start_position;
 
                 self.emit(Opcode::Jump, vec![while_initial_pos as i32]);
             }
         }
+        Ok(())
     }
 
     fn last_instruction_is(&self, opcode: Opcode) -> bool {
@@ -510,21 +680,70 @@ impl Compiler {
 
             self.scopes[self.scope_index].instructions.data = new;
             self.scopes[self.scope_index].last_instruction = previous;
+            self.scopes[self.scope_index]
+                .lines
+                .retain(|(offset, _)| *offset < last.position);
         }
     }
 
-    fn add_constant(&mut self, obj: Object) -> usize {
+    /// Returns the index of `obj` in the constant pool, adding it if no equal
+    /// constant is there yet.
+    fn add_constant(&mut self, obj: Object) -> Result<i32, String> {
+        if let Some(index) = self.constant_indices.get(&obj) {
+            // Identical functions written at different places stay apart, so
+            // that runtime errors point to the right one
+            let interned = match (&self.constants[*index], &obj) {
+                (Object::COMPILEDFUNCTION(a), Object::COMPILEDFUNCTION(b)) => {
+                    a.debug.same_origin(&b.debug)
+                }
+                _ => true,
+            };
+            if interned {
+                return Ok(*index as i32);
+            }
+        }
+        if self.constants.len() >= MAX_CONSTANTS {
+            return Err(format!(
+                "Too many constants, a program can use at most {MAX_CONSTANTS}"
+            ));
+        }
+        let index = self.constants.len();
+        self.constant_indices.insert(obj.clone(), index);
         self.constants.push(obj);
-        self.constants.len() - 1
+        Ok(index as i32)
     }
 
     fn emit(&mut self, opcode: Opcode, operands: Vec<i32>) -> usize {
         let instruction = opcode.make(operands);
         let pos = self.add_instruction(instruction);
         self.set_last_instruction(opcode, pos);
+        self.add_line(pos);
         pos
     }
 
+    /// Records that the instruction at `pos` comes from the expression being
+    /// compiled, if it is not where the previous instruction came from.
+    fn add_line(&mut self, pos: usize) {
+        let Some(span) = self.spans.last().copied() else {
+            return;
+        };
+        let lines = &mut self.scopes[self.scope_index].lines;
+        if lines.last().is_some_and(|(_, last)| *last == span) {
+            return;
+        }
+        lines.push((pos, span));
+    }
+
+    /// Emits a jump whose target is set later with `change_operand`.
+    ///
+    /// The dummy target is the position of the jump itself, so the jump is
+    /// only wide if it is past the range of 2-byte operands. A target that
+    /// does not fit is handled by `change_operand`.
+    fn emit_jump_placeholder(&mut self, opcode: Opcode) -> usize {
+        let pos = self.current_instructions().data.len();
+        self.emit(opcode, vec![pos as i32])
+    }
+
     fn add_instruction(&mut self, instruction: Instructions) -> usize {
         let pos_new_instruction = self.current_instructions().data.len();
         self.scopes[self.scope_index]
@@ -544,15 +763,41 @@ impl Compiler {
     }
 
     fn change_operand(&mut self, pos: usize, operand: i32) -> Result<(), String> {
-        let op = Opcode::from_u8(self.current_instructions().data[pos]).ok_or(format!(
-            "Unknown opcode: {opcode}",
-            opcode = self.current_instructions().data[pos]
-        ))?;
+        let (op, _, size) = read_instruction(&self.current_instructions().data[pos..])?;
         let new_instruction = op.make(vec![operand]);
+        // A jump emitted with a 2-byte operand cannot grow without moving the
+        // code after it, and the positions the compiler still holds with it.
+        // It keeps its dummy target until the whole scope is compiled.
+        if new_instruction.data.len() != size {
+            self.scopes[self.scope_index]
+                .long_jumps
+                .push((pos, operand as usize));
+            return Ok(());
+        }
         self.replace_instruction(pos, &new_instruction);
         Ok(())
     }
 
+    /// Widens the jumps of the current scope whose targets were too far for
+    /// their operands, moving the code after them.
+    fn lay_out_long_jumps(&mut self) -> Result<(), String> {
+        let scope = &mut self.scopes[self.scope_index];
+        if scope.long_jumps.is_empty() {
+            return Ok(());
+        }
+        let (data, lines) = optimizer::lay_out(
+            &scope.instructions.data,
+            &scope.lines,
+            &std::mem::take(&mut scope.long_jumps),
+        )?;
+        scope.instructions = Instructions::new(data);
+        scope.lines = lines;
+        // Their positions moved, and nothing can be removed at this point
+        scope.last_instruction = None;
+        scope.previous_instruction = None;
+        Ok(())
+    }
+
     fn replace_instruction(&mut self, pos: usize, new_instruction: &Instructions) {
         let ins = &mut self.scopes[self.scope_index].instructions;
         for (i, instruction) in new_instruction.data.iter().enumerate() {
@@ -604,6 +849,22 @@ impl Compiler {
             .opcode = Opcode::ReturnValue;
     }
 
+    /// Turns the calls whose result is returned right away, by `return` or as
+    /// the last value of a branch, into tail calls.
+    fn mark_tail_calls(&mut self) -> Result<(), String> {
+        let data = self.current_instructions().data;
+        let mut pos = 0;
+        while pos < data.len() {
+            let (op, operands, size) = read_instruction(&data[pos..])?;
+            if op == Opcode::Call && is_returned(&data, pos + size)? {
+                // Both opcodes have the same operands, the size does not change
+                self.replace_instruction(pos, &Opcode::TailCall.make(operands));
+            }
+            pos += size;
+        }
+        Ok(())
+    }
+
     fn load_symbol(&mut self, symbol: &Symbol) {
         let opcode = match symbol.scope {
             SymbolScope::Global => Opcode::GetGlobal,
@@ -617,13 +878,36 @@ impl Compiler {
     }
 
     pub fn bytecode(&self) -> Bytecode {
-        Bytecode::new(self.current_instructions(), self.constants.clone())
+        let mut bytecode = Bytecode::new(self.current_instructions(), self.constants.clone());
+        bytecode
+            .debug
+            .lines
+            .clone_from(&self.scopes[self.scope_index].lines);
+        bytecode
+    }
+}
+
+/// Whether the value on top of the stack at `pos` is returned without running
+/// anything else. Only forward jumps are followed, like the ones that leave a
+/// conditional, so this always ends.
+fn is_returned(data: &[u8], mut pos: usize) -> Result<bool, String> {
+    while pos < data.len() {
+        let (op, operands, _) = read_instruction(&data[pos..])?;
+        match op {
+            Opcode::ReturnValue => return Ok(true),
+            Opcode::Jump if operands[0] as usize > pos => pos = operands[0] as usize,
+            _ => return Ok(false),
+        }
     }
+    Ok(false)
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bytecode {
     pub instructions: Instructions,
     pub constants: Vec<Object>,
+    /// Line table of the main program.
+    pub debug: DebugInfo,
 }
 
 impl Bytecode {
@@ -631,6 +915,7 @@ impl Bytecode {
         Bytecode {
             instructions,
             constants,
+            debug: DebugInfo::default(),
         }
     }
 }
@@ -0,0 +1,465 @@
+//! Optimization passes over compiled bytecode.
+//!
+//! The compiler emits code as it walks the syntax tree, without looking at what
+//! it already emitted. These passes clean it up afterwards:
+//!
+//! - constant folding: arithmetic, comparisons, string concatenation and
+//!   prefix operators on constants are computed at compile time,
+//! - dead branches: jumps on a constant condition become unconditional or
+//!   disappear,
+//! - jump threading: a jump to a jump goes straight to the final target, and
+//!   a jump to the next instruction is removed,
+//! - dead code: instructions that cannot be reached are removed, such as the
+//!   code after an `OpReturnValue`.
+//!
+//! Operations that would fail at runtime, like a division by zero or an
+//! overflow, are never folded so that they still fail when the program runs.
+
+use crate::{
+    compiler::{
+        code::{read_instruction, Instructions, Opcode},
+        Bytecode,
+    },
+    lexer::token::Span,
+    object::{CompiledFunction, DebugInfo, LineTable, Object},
+};
+
+/// An instruction whose jump operand, if any, is the index of the target
+/// instruction instead of its byte offset.
+#[derive(Debug, Clone, PartialEq)]
+struct Instruction {
+    opcode: Opcode,
+    operands: Vec<i32>,
+    /// Position of the code it comes from, from the line table.
+    span: Option<Span>,
+}
+
+impl Instruction {
+    fn new(opcode: Opcode, operands: Vec<i32>) -> Self {
+        Instruction {
+            opcode,
+            operands,
+            span: None,
+        }
+    }
+
+    fn target(&self) -> Option<usize> {
+        match self.opcode {
+            Opcode::Jump | Opcode::JumpNotTruthy | Opcode::Try => Some(self.operands[0] as usize),
+            _ => None,
+        }
+    }
+
+    /// Whether the next instruction can run after this one.
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self.opcode,
+            Opcode::Jump | Opcode::ReturnValue | Opcode::Return | Opcode::Throw
+        )
+    }
+}
+
+/// A value known at compile time.
+enum Value {
+    Constant(Object),
+    Boolean(bool),
+    Null,
+}
+
+/// Optimizes the main program and every function of the constant pool.
+pub fn optimize(bytecode: Bytecode) -> Result<Bytecode, String> {
+    let mut constants = bytecode.constants;
+
+    for i in 0..constants.len() {
+        if let Object::COMPILEDFUNCTION(function) = &constants[i] {
+            let function = function.clone();
+            let (instructions, lines) =
+                optimize_instructions(&function.instructions, &function.debug, &mut constants)?;
+            constants[i] = Object::COMPILEDFUNCTION(CompiledFunction {
+                instructions,
+                debug: DebugInfo {
+                    lines,
+                    ..function.debug
+                },
+                ..function
+            });
+        }
+    }
+    let (instructions, lines) =
+        optimize_instructions(&bytecode.instructions.data, &bytecode.debug, &mut constants)?;
+
+    Ok(Bytecode {
+        instructions: Instructions::new(instructions),
+        constants,
+        debug: DebugInfo {
+            lines,
+            ..bytecode.debug
+        },
+    })
+}
+
+/// Optimizes one function, returns its instructions and their line table.
+fn optimize_instructions(
+    data: &[u8],
+    debug: &DebugInfo,
+    constants: &mut Vec<Object>,
+) -> Result<(Vec<u8>, LineTable), String> {
+    let mut optimizer = Optimizer {
+        instructions: decode(data, debug, &[])?,
+        constants,
+    };
+    while optimizer.fold_constants()
+        | optimizer.fold_branches()
+        | optimizer.thread_jumps()
+        | optimizer.remove_dead_code()
+    {}
+    Ok(encode(&optimizer.instructions))
+}
+
+/// Lays out `data` again with the jumps at the offsets of `targets` going to
+/// the given offsets instead of their operands, the jumps growing wide where
+/// they need to. The compiler uses it for the jumps it could not widen while
+/// emitting them.
+pub(super) fn lay_out(
+    data: &[u8],
+    lines: &LineTable,
+    targets: &[(usize, usize)],
+) -> Result<(Vec<u8>, LineTable), String> {
+    let debug = DebugInfo {
+        lines: lines.clone(),
+        ..DebugInfo::default()
+    };
+    Ok(encode(&decode(data, &debug, targets)?))
+}
+
+/// `targets` replaces the operands of the jumps at the given offsets.
+fn decode(
+    data: &[u8],
+    debug: &DebugInfo,
+    targets: &[(usize, usize)],
+) -> Result<Vec<Instruction>, String> {
+    let mut offsets = Vec::new();
+    let mut instructions = Vec::new();
+    let mut position = 0;
+    while position < data.len() {
+        let (opcode, mut operands, size) = read_instruction(&data[position..])?;
+        if let Some((_, target)) = targets.iter().find(|(at, _)| *at == position) {
+            operands[0] = *target as i32;
+        }
+        offsets.push(position);
+        instructions.push(Instruction {
+            opcode,
+            operands,
+            span: debug.position(position),
+        });
+        position += size;
+    }
+    // Jumps can target the end of the instructions
+    offsets.push(data.len());
+
+    for instruction in &mut instructions {
+        if let Some(target) = instruction.target() {
+            let index = offsets
+                .binary_search(&target)
+                .map_err(|_| format!("Jump to {target} is not an instruction"))?;
+            instruction.operands[0] = index as i32;
+        }
+    }
+    Ok(instructions)
+}
+
+fn encode(instructions: &[Instruction]) -> (Vec<u8>, LineTable) {
+    // The size of a jump depends on the offset of its target, which depends on
+    // the size of the jumps before it. Starting from the smallest sizes, they
+    // only grow until every jump fits.
+    let mut offsets = vec![0; instructions.len() + 1];
+    loop {
+        let mut position = 0;
+        let mut next = Vec::with_capacity(offsets.len());
+        for instruction in instructions {
+            next.push(position);
+            position += make(instruction, &offsets).data.len();
+        }
+        next.push(position);
+        if next == offsets {
+            break;
+        }
+        offsets = next;
+    }
+
+    let mut lines: LineTable = Vec::new();
+    for (instruction, offset) in instructions.iter().zip(&offsets) {
+        let Some(span) = instruction.span else {
+            continue;
+        };
+        if lines.last().map(|(_, last)| *last) != Some(span) {
+            lines.push((*offset, span));
+        }
+    }
+    let data = instructions
+        .iter()
+        .flat_map(|instruction| make(instruction, &offsets).data)
+        .collect();
+    (data, lines)
+}
+
+fn make(instruction: &Instruction, offsets: &[usize]) -> Instructions {
+    let mut operands = instruction.operands.clone();
+    if let Some(target) = instruction.target() {
+        operands[0] = offsets[target] as i32;
+    }
+    instruction.opcode.make(operands)
+}
+
+struct Optimizer<'a> {
+    instructions: Vec<Instruction>,
+    constants: &'a mut Vec<Object>,
+}
+
+impl Optimizer<'_> {
+    /// Whether each instruction is the target of a jump.
+    fn targets(&self) -> Vec<bool> {
+        let mut targets = vec![false; self.instructions.len() + 1];
+        for target in self.instructions.iter().filter_map(Instruction::target) {
+            targets[target] = true;
+        }
+        targets
+    }
+
+    /// Keeps the instructions for which `keep` is true, jumps to a removed
+    /// instruction go to the next one that is kept.
+    fn retain(&mut self, keep: &[bool]) -> bool {
+        if keep.iter().all(|x| *x) {
+            return false;
+        }
+        // Number of kept instructions before each index, which is the new
+        // index of the first kept instruction from there on
+        let mut new_index = Vec::with_capacity(keep.len() + 1);
+        let mut count = 0;
+        for kept in keep {
+            new_index.push(count);
+            count += usize::from(*kept);
+        }
+        new_index.push(count);
+
+        let instructions = std::mem::take(&mut self.instructions);
+        self.instructions = instructions
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, kept)| **kept)
+            .map(|(mut instruction, _)| {
+                if let Some(target) = instruction.target() {
+                    instruction.operands[0] = new_index[target] as i32;
+                }
+                instruction
+            })
+            .collect();
+        true
+    }
+
+    fn value(&self, instruction: &Instruction) -> Option<Value> {
+        match instruction.opcode {
+            Opcode::Constant => match self.constants.get(instruction.operands[0] as usize)? {
+                x @ (Object::INTEGER(_) | Object::STRING(_)) => Some(Value::Constant(x.clone())),
+                _ => None,
+            },
+            Opcode::True => Some(Value::Boolean(true)),
+            Opcode::False => Some(Value::Boolean(false)),
+            Opcode::Null => Some(Value::Null),
+            _ => None,
+        }
+    }
+
+    fn load(&mut self, value: Value) -> Instruction {
+        match value {
+            Value::Boolean(true) => Instruction::new(Opcode::True, vec![]),
+            Value::Boolean(false) => Instruction::new(Opcode::False, vec![]),
+            Value::Null => Instruction::new(Opcode::Null, vec![]),
+            Value::Constant(constant) => {
+                let index = match self.constants.iter().position(|x| *x == constant) {
+                    Some(index) => index,
+                    None => {
+                        self.constants.push(constant);
+                        self.constants.len() - 1
+                    }
+                };
+                Instruction::new(Opcode::Constant, vec![index as i32])
+            }
+        }
+    }
+
+    /// Replaces an operation on values known at compile time by its result.
+    fn fold_constants(&mut self) -> bool {
+        let targets = self.targets();
+        let mut keep = vec![true; self.instructions.len()];
+        let mut i = 0;
+        while i < self.instructions.len() {
+            let folded = self
+                .fold_binary(i, &targets)
+                .map(|x| (x, 3))
+                .or_else(|| self.fold_unary(i, &targets).map(|x| (x, 2)));
+            match folded {
+                Some((value, length)) => {
+                    let span = self.instructions[i].span;
+                    self.instructions[i] = Instruction {
+                        span,
+                        ..self.load(value)
+                    };
+                    keep[i + 1..i + length].iter_mut().for_each(|x| *x = false);
+                    i += length;
+                }
+                None => i += 1,
+            }
+        }
+        self.retain(&keep)
+    }
+
+    fn fold_binary(&self, i: usize, targets: &[bool]) -> Option<Value> {
+        let [left, right, operation] = self.instructions.get(i..i + 3)? else {
+            return None;
+        };
+        if targets[i + 1] || targets[i + 2] {
+            return None;
+        }
+
+        match (self.value(left)?, self.value(right)?) {
+            (Value::Constant(Object::INTEGER(x)), Value::Constant(Object::INTEGER(y))) => {
+                Some(match operation.opcode {
+                    Opcode::Add => Value::Constant(Object::INTEGER(x.checked_add(y)?)),
+                    Opcode::Sub => Value::Constant(Object::INTEGER(x.checked_sub(y)?)),
+                    Opcode::Mul => Value::Constant(Object::INTEGER(x.checked_mul(y)?)),
+                    Opcode::Div => Value::Constant(Object::INTEGER(x.checked_div(y)?)),
+                    Opcode::Modulo if y == 0 => return None,
+                    Opcode::Modulo => Value::Constant(Object::INTEGER(x.wrapping_rem(y))),
+                    Opcode::Equal => Value::Boolean(x == y),
+                    Opcode::NotEqual => Value::Boolean(x != y),
+                    Opcode::GreaterThan => Value::Boolean(x > y),
+                    Opcode::GreaterEqualThan => Value::Boolean(x >= y),
+                    Opcode::LessThan => Value::Boolean(x < y),
+                    Opcode::LessEqualThan => Value::Boolean(x <= y),
+                    _ => return None,
+                })
+            }
+            (Value::Boolean(x), Value::Boolean(y)) => {
+                Some(Value::Boolean(match operation.opcode {
+                    Opcode::Equal => x == y,
+                    Opcode::NotEqual => x != y,
+                    Opcode::And => x && y,
+                    Opcode::Or => x || y,
+                    _ => return None,
+                }))
+            }
+            (Value::Constant(Object::STRING(x)), Value::Constant(Object::STRING(y)))
+                if operation.opcode == Opcode::Add =>
+            {
+                Some(Value::Constant(Object::STRING(x + &y)))
+            }
+            _ => None,
+        }
+    }
+
+    fn fold_unary(&self, i: usize, targets: &[bool]) -> Option<Value> {
+        let [operand, operation] = self.instructions.get(i..i + 2)? else {
+            return None;
+        };
+        if targets[i + 1] {
+            return None;
+        }
+
+        match (operation.opcode, self.value(operand)?) {
+            (Opcode::Bang, value) => Some(Value::Boolean(!is_truthy(&value))),
+            (Opcode::Minus, Value::Constant(Object::INTEGER(x))) => {
+                Some(Value::Constant(Object::INTEGER(x.checked_neg()?)))
+            }
+            _ => None,
+        }
+    }
+
+    /// A conditional jump on a value known at compile time either always jumps
+    /// or never does.
+    fn fold_branches(&mut self) -> bool {
+        let targets = self.targets();
+        let mut keep = vec![true; self.instructions.len()];
+        for i in 0..self.instructions.len().saturating_sub(1) {
+            if self.instructions[i + 1].opcode != Opcode::JumpNotTruthy
+                || targets[i + 1]
+                || !keep[i]
+            {
+                continue;
+            }
+            let Some(value) = self.value(&self.instructions[i]) else {
+                continue;
+            };
+
+            keep[i] = false;
+            if is_truthy(&value) {
+                keep[i + 1] = false;
+            } else {
+                self.instructions[i + 1].opcode = Opcode::Jump;
+            }
+        }
+        self.retain(&keep)
+    }
+
+    /// Jumps to an unconditional jump go directly to its target, and jumps to
+    /// the next instruction are removed.
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for i in 0..self.instructions.len() {
+            let Some(mut target) = self.instructions[i].target() else {
+                continue;
+            };
+            // Bounded, an infinite loop of jumps would never end otherwise
+            for _ in 0..self.instructions.len() {
+                match self.instructions.get(target) {
+                    Some(next) if next.opcode == Opcode::Jump && next.target() != Some(target) => {
+                        target = next.operands[0] as usize;
+                    }
+                    _ => break,
+                }
+            }
+            if self.instructions[i].operands[0] != target as i32 {
+                self.instructions[i].operands[0] = target as i32;
+                changed = true;
+            }
+        }
+
+        let keep: Vec<bool> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(i, instruction)| {
+                !(instruction.opcode == Opcode::Jump && instruction.target() == Some(i + 1))
+            })
+            .collect();
+        self.retain(&keep) || changed
+    }
+
+    /// Removes the instructions that no path from the start reaches.
+    fn remove_dead_code(&mut self) -> bool {
+        let mut reachable = vec![false; self.instructions.len()];
+        let mut pending = vec![0];
+        while let Some(i) = pending.pop() {
+            if i >= self.instructions.len() || reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            let instruction = &self.instructions[i];
+            if let Some(target) = instruction.target() {
+                pending.push(target);
+            }
+            if instruction.falls_through() {
+                pending.push(i + 1);
+            }
+        }
+        self.retain(&reachable)
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Boolean(x) => *x,
+        Value::Constant(_) => true,
+    }
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{disassembler::disassemble, optimizer::optimize, test_utils::compile};
+
+    /// Folded constants are appended to the pool, the expected listings are
+    /// disassembled so that the values can be read directly.
+    fn check_optimized(input: &str, expected: &str) {
+        let optimized = optimize(compile(input)).unwrap();
+        let got = disassemble(&optimized).unwrap();
+        println!("{got}");
+        assert_eq!(got, expected, "wrong optimization of {input}");
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let tests = vec![
+            (
+                "1 + 2 * 3 - 4",
+                "; main
+0000 OpConstant 2               ; 3
+0003 OpPop
+
+; constants
+const 1                         ; constant 0
+const 2                         ; constant 1
+const 3                         ; constant 2
+const 4                         ; constant 3
+const 6                         ; constant 4
+const 7                         ; constant 5
+",
+            ),
+            (
+                "-(7 % 4) / 2",
+                "; main
+0000 OpConstant 5               ; -1
+0003 OpPop
+
+; constants
+const 7                         ; constant 0
+const 4                         ; constant 1
+const 2                         ; constant 2
+const 3                         ; constant 3
+const -3                        ; constant 4
+const -1                        ; constant 5
+",
+            ),
+            (
+                r#""mon" + "key""#,
+                r#"; main
+0000 OpConstant 2               ; "monkey"
+0003 OpPop
+
+; constants
+const "mon"                     ; constant 0
+const "key"                     ; constant 1
+const "monkey"                  ; constant 2
+"#,
+            ),
+            (
+                "1 < 2 == !false",
+                "; main
+0000 OpTrue
+0001 OpPop
+
+; constants
+const 1                         ; constant 0
+const 2                         ; constant 1
+",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            check_optimized(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors_are_not_folded() {
+        check_optimized(
+            "1 / 0; 1 + true; -true; 9223372036854775807 + 1",
+            "; main
+0000 OpConstant 0               ; 1
+0003 OpConstant 1               ; 0
+0006 OpDiv
+0007 OpPop
+0008 OpConstant 0               ; 1
+0011 OpTrue
+0012 OpAdd
+0013 OpPop
+0014 OpTrue
+0015 OpMinus
+0016 OpPop
+0017 OpConstant 2               ; 9223372036854775807
+0020 OpConstant 0               ; 1
+0023 OpAdd
+0024 OpPop
+
+; constants
+const 1                         ; constant 0
+const 0                         ; constant 1
+const 9223372036854775807       ; constant 2
+",
+        );
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        let tests = vec![
+            (
+                "if (true) { 10 } else { 20 }; 30",
+                "; main
+0000 OpConstant 0               ; 10
+0003 OpPop
+0004 OpConstant 2               ; 30
+0007 OpPop
+
+; constants
+const 10                        ; constant 0
+const 20                        ; constant 1
+const 30                        ; constant 2
+",
+            ),
+            (
+                "if (1 > 2) { 10 }",
+                "; main
+0000 OpNull
+0001 OpPop
+
+; constants
+const 1                         ; constant 0
+const 2                         ; constant 1
+const 10                        ; constant 2
+",
+            ),
+            (
+                "while (false) { puts(1) }",
+                "; main
+
+; constants
+const 1                         ; constant 0
+",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            check_optimized(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        // The inner `break` jumped to the end of the inner loop, which jumped
+        // back to the condition of the outer one
+        check_optimized(
+            "while (len([])) { while (len([])) { break; } }",
+            "; main
+L0:
+0000 OpGetBuiltin 0             ; len
+0002 OpArray 0
+0005 OpCall 1
+0007 OpJumpNotTruthy 23         ; L1
+0010 OpGetBuiltin 0             ; len
+0012 OpArray 0
+0015 OpCall 1
+0017 OpJumpNotTruthy 0          ; L0
+0020 OpJump 0                   ; L0
+L1:
+",
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        check_optimized(
+            "fn() { return 1; 2; 3 }",
+            "; main
+0000 OpClosure 3 0              ; function 3
+0004 OpPop
+
+; constants
+const 1                         ; constant 0
+const 2                         ; constant 1
+const 3                         ; constant 2
+
+fn function_3 params=0 locals=0 free=0 { ; constant 3
+0000 OpConstant 0               ; 1
+0003 OpReturnValue
+}
+",
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Binary format of compiled programs, the `.mkc` files.
+//!
+//! A file starts with a header: the `MKC` magic number, the format version and
+//! a CRC-32 checksum of the rest of the file. Then comes the instruction stream
+//! of the main program and the constant pool. All integers are big endian, like
+//! the operands in the instructions.
+//!
+//! ```text
+//! magic     b"MKC\0"
+//! version   u16
+//! checksum  u32
+//! main      u32 length, instructions, debug information
+//! constants u32 count, tagged constants
+//! ```
+//!
+//! The debug information of the main program and of every function is its name,
+//! as a u8 flag and a string, and its line table: a u32 count of entries, each
+//! of them an offset, a line and a column.
+//!
+//! Loading a file decodes every instruction, so that a file with a valid
+//! checksum but broken code is rejected before it runs. A file can only call
+//! the builtins of the language, not the functions registered by a host.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::{Display, Formatter},
+    io::{Cursor, Read},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    compiler::{
+        code::{read_instruction, Instructions, Opcode},
+        Bytecode,
+    },
+    lexer::token::Span,
+    object::{builtins::BuiltinFunction, CompiledFunction, DebugInfo, Object},
+    vm::GLOBALS_SIZE,
+};
+
+pub const MAGIC: &[u8; 4] = b"MKC\0";
+
+/// Version of the format written by this build. Bump it whenever the layout of
+/// the file or the meaning of an opcode changes.
+pub const FORMAT_VERSION: u16 = 7;
+
+const HEADER_SIZE: usize = MAGIC.len() + 2 + 4;
+
+const INTEGER_TAG: u8 = 0;
+const STRING_TAG: u8 = 1;
+const FUNCTION_TAG: u8 = 2;
+const FLOAT_TAG: u8 = 3;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum BytecodeError {
+    /// The file does not start with the `.mkc` magic number.
+    NotBytecode,
+    /// The file was written by a different version of the format.
+    UnsupportedVersion { found: u16 },
+    /// The content does not match the checksum of the header.
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// The file ends in the middle of a value.
+    Truncated,
+    /// A constant with an unknown tag.
+    UnknownConstant { tag: u8 },
+    /// A string constant or a function name that is not valid UTF-8.
+    InvalidString,
+    /// Only integers, floats, strings and functions can be stored in the constant pool.
+    UnsupportedConstant { object: String },
+    /// An instruction that cannot be decoded: an unknown opcode, or operands
+    /// cut off by the end of the code.
+    ///
+    /// `function` is the constant index of the function the code belongs to,
+    /// `None` for the main program.
+    InvalidInstruction {
+        function: Option<usize>,
+        offset: usize,
+        message: String,
+    },
+    /// A jump that does not land on the start of an instruction.
+    InvalidJump {
+        function: Option<usize>,
+        offset: usize,
+        target: usize,
+    },
+    /// An operand that refers to a constant, a global or a builtin that does
+    /// not exist, e.g. `constant 7`.
+    OperandOutOfRange {
+        function: Option<usize>,
+        offset: usize,
+        operand: String,
+    },
+    /// A closure made from a constant that is not a function.
+    NotAFunction {
+        function: Option<usize>,
+        offset: usize,
+        constant: usize,
+    },
+}
+
+/// Where an instruction is, for the messages of [`BytecodeError`].
+fn location(function: Option<usize>, offset: usize) -> String {
+    match function {
+        Some(index) => format!("offset {offset} of function {index}"),
+        None => format!("offset {offset} of the main program"),
+    }
+}
+
+impl Display for BytecodeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BytecodeError::NotBytecode => write!(f, "not a compiled Monkey file"),
+            BytecodeError::UnsupportedVersion { found } => write!(
+                f,
+                "unsupported bytecode version {found}, expected version {FORMAT_VERSION}: \
+                 rebuild the file with `monkey build`"
+            ),
+            BytecodeError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "corrupted bytecode: checksum is {found:#010x}, expected {expected:#010x}"
+            ),
+            BytecodeError::Truncated => write!(f, "corrupted bytecode: unexpected end of file"),
+            BytecodeError::UnknownConstant { tag } => {
+                write!(f, "corrupted bytecode: unknown constant tag {tag}")
+            }
+            BytecodeError::InvalidString => {
+                write!(f, "corrupted bytecode: string is not valid UTF-8")
+            }
+            BytecodeError::UnsupportedConstant { object } => {
+                write!(f, "{object} cannot be stored in a bytecode file")
+            }
+            BytecodeError::InvalidInstruction {
+                function,
+                offset,
+                message,
+            } => write!(
+                f,
+                "corrupted bytecode: {message} at {}",
+                location(*function, *offset)
+            ),
+            BytecodeError::InvalidJump {
+                function,
+                offset,
+                target,
+            } => write!(
+                f,
+                "corrupted bytecode: jump to {target}, which is not an instruction, at {}",
+                location(*function, *offset)
+            ),
+            BytecodeError::OperandOutOfRange {
+                function,
+                offset,
+                operand,
+            } => write!(
+                f,
+                "corrupted bytecode: unknown {operand} at {}",
+                location(*function, *offset)
+            ),
+            BytecodeError::NotAFunction {
+                function,
+                offset,
+                constant,
+            } => write!(
+                f,
+                "corrupted bytecode: closure of constant {constant}, which is not a function, at {}",
+                location(*function, *offset)
+            ),
+        }
+    }
+}
+
+impl Error for BytecodeError {}
+
+impl From<std::io::Error> for BytecodeError {
+    fn from(_: std::io::Error) -> Self {
+        // Reading from memory can only fail by running out of input
+        BytecodeError::Truncated
+    }
+}
+
+impl Bytecode {
+    /// Encodes the program in the `.mkc` format.
+    pub fn serialize(&self) -> Result<Vec<u8>, BytecodeError> {
+        let mut body = Vec::new();
+        write_bytes(&mut body, &self.instructions.data);
+        write_debug_info(&mut body, &self.debug);
+        write_length(&mut body, self.constants.len());
+        for constant in &self.constants {
+            write_constant(&mut body, constant)?;
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.write_u16::<BigEndian>(FORMAT_VERSION).unwrap();
+        bytes.write_u32::<BigEndian>(crc32(&body)).unwrap();
+        bytes.append(&mut body);
+        Ok(bytes)
+    }
+
+    /// Decodes a program written by [`Bytecode::serialize`], checking its
+    /// version and checksum first, then its instructions.
+    pub fn deserialize(bytes: &[u8]) -> Result<Bytecode, BytecodeError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(BytecodeError::NotBytecode);
+        }
+
+        let mut reader = Cursor::new(&bytes[MAGIC.len()..]);
+        let version = reader.read_u16::<BigEndian>()?;
+        if version != FORMAT_VERSION {
+            return Err(BytecodeError::UnsupportedVersion { found: version });
+        }
+        let expected = reader.read_u32::<BigEndian>()?;
+
+        let body = &bytes[HEADER_SIZE..];
+        let found = crc32(body);
+        if found != expected {
+            return Err(BytecodeError::ChecksumMismatch { expected, found });
+        }
+
+        let mut reader = Cursor::new(body);
+        let instructions = Instructions::new(read_bytes(&mut reader)?);
+        let debug = read_debug_info(&mut reader)?;
+        let count = reader.read_u32::<BigEndian>()?;
+        let constants = (0..count)
+            .map(|_| read_constant(&mut reader))
+            .collect::<Result<Vec<Object>, BytecodeError>>()?;
+
+        validate(&instructions.data, None, &constants)?;
+        for (index, constant) in constants.iter().enumerate() {
+            if let Object::COMPILEDFUNCTION(function) = constant {
+                validate(&function.instructions, Some(index), &constants)?;
+            }
+        }
+
+        Ok(Bytecode {
+            instructions,
+            constants,
+            debug,
+        })
+    }
+}
+
+fn write_length(bytes: &mut Vec<u8>, length: usize) {
+    bytes.write_u32::<BigEndian>(length as u32).unwrap();
+}
+
+fn write_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    write_length(bytes, data.len());
+    bytes.extend_from_slice(data);
+}
+
+fn write_constant(bytes: &mut Vec<u8>, constant: &Object) -> Result<(), BytecodeError> {
+    match constant {
+        Object::INTEGER(x) => {
+            bytes.push(INTEGER_TAG);
+            bytes.write_i64::<BigEndian>(*x).unwrap();
+        }
+        Object::STRING(x) => {
+            bytes.push(STRING_TAG);
+            write_bytes(bytes, x.as_bytes());
+        }
+        Object::FLOAT(x) => {
+            bytes.push(FLOAT_TAG);
+            bytes.write_f64::<BigEndian>(*x).unwrap();
+        }
+        Object::COMPILEDFUNCTION(function) => {
+            bytes.push(FUNCTION_TAG);
+            write_length(bytes, function.num_locals);
+            write_length(bytes, function.num_parameters);
+            write_bytes(bytes, &function.instructions);
+            write_debug_info(bytes, &function.debug);
+        }
+        x => {
+            return Err(BytecodeError::UnsupportedConstant {
+                object: x.get_type(),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn write_debug_info(bytes: &mut Vec<u8>, debug: &DebugInfo) {
+    match &debug.name {
+        Some(name) => {
+            bytes.push(1);
+            write_bytes(bytes, name.as_bytes());
+        }
+        None => bytes.push(0),
+    }
+    write_length(bytes, debug.lines.len());
+    for (offset, span) in &debug.lines {
+        write_length(bytes, *offset);
+        write_length(bytes, span.line);
+        write_length(bytes, span.column);
+    }
+}
+
+fn read_bytes(reader: &mut Cursor<&[u8]>) -> Result<Vec<u8>, BytecodeError> {
+    let length = reader.read_u32::<BigEndian>()? as usize;
+    let remaining = reader.get_ref().len() - reader.position() as usize;
+    if length > remaining {
+        return Err(BytecodeError::Truncated);
+    }
+    let mut data = vec![0; length];
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn read_constant(reader: &mut Cursor<&[u8]>) -> Result<Object, BytecodeError> {
+    match reader.read_u8()? {
+        INTEGER_TAG => Ok(Object::INTEGER(reader.read_i64::<BigEndian>()?)),
+        STRING_TAG => String::from_utf8(read_bytes(reader)?)
+            .map(Object::STRING)
+            .map_err(|_| BytecodeError::InvalidString),
+        FLOAT_TAG => Ok(Object::FLOAT(reader.read_f64::<BigEndian>()?)),
+        FUNCTION_TAG => {
+            let num_locals = reader.read_u32::<BigEndian>()? as usize;
+            let num_parameters = reader.read_u32::<BigEndian>()? as usize;
+            let instructions = read_bytes(reader)?;
+            let debug = read_debug_info(reader)?;
+            Ok(Object::COMPILEDFUNCTION(CompiledFunction {
+                instructions,
+                num_locals,
+                num_parameters,
+                debug,
+            }))
+        }
+        tag => Err(BytecodeError::UnknownConstant { tag }),
+    }
+}
+
+fn read_debug_info(reader: &mut Cursor<&[u8]>) -> Result<DebugInfo, BytecodeError> {
+    let name = match reader.read_u8()? {
+        0 => None,
+        _ => {
+            Some(String::from_utf8(read_bytes(reader)?).map_err(|_| BytecodeError::InvalidString)?)
+        }
+    };
+    let count = reader.read_u32::<BigEndian>()?;
+    let mut lines = Vec::new();
+    for _ in 0..count {
+        let offset = reader.read_u32::<BigEndian>()? as usize;
+        let line = reader.read_u32::<BigEndian>()? as usize;
+        let column = reader.read_u32::<BigEndian>()? as usize;
+        // The offset in the source is not needed to report errors
+        lines.push((offset, Span::new(0, line, column)));
+    }
+    Ok(DebugInfo { name, lines })
+}
+
+/// Decodes the instructions of the main program or of the function at index
+/// `function` of the constant pool, and checks that their jumps land on
+/// instructions and that their operands refer to things that exist.
+fn validate(
+    code: &[u8],
+    function: Option<usize>,
+    constants: &[Object],
+) -> Result<(), BytecodeError> {
+    let out_of_range = |offset, operand| BytecodeError::OperandOutOfRange {
+        function,
+        offset,
+        operand,
+    };
+
+    // Jumping to the end of the code is how a function without a return ends
+    let mut starts = HashSet::from([code.len()]);
+    let mut jumps = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        starts.insert(offset);
+        let (op, operands, size) = read_instruction(&code[offset..]).map_err(|message| {
+            BytecodeError::InvalidInstruction {
+                function,
+                offset,
+                message,
+            }
+        })?;
+        // Wide operands are unsigned 32 bits integers
+        let operand = operands
+            .first()
+            .map_or(0, |operand| *operand as u32 as usize);
+        match op {
+            Opcode::Jump | Opcode::JumpNotTruthy | Opcode::Try => jumps.push((offset, operand)),
+            Opcode::Constant if operand >= constants.len() => {
+                return Err(out_of_range(offset, format!("constant {operand}")));
+            }
+            Opcode::Closure => match constants.get(operand) {
+                Some(Object::COMPILEDFUNCTION(_)) => {}
+                Some(_) => {
+                    return Err(BytecodeError::NotAFunction {
+                        function,
+                        offset,
+                        constant: operand,
+                    })
+                }
+                None => return Err(out_of_range(offset, format!("constant {operand}"))),
+            },
+            Opcode::GetGlobal | Opcode::SetGlobal if operand >= GLOBALS_SIZE => {
+                return Err(out_of_range(offset, format!("global {operand}")));
+            }
+            Opcode::GetBuiltin if BuiltinFunction::get_builtin_by_id(operand).is_none() => {
+                return Err(out_of_range(offset, format!("builtin {operand}")));
+            }
+            _ => {}
+        }
+        offset += size;
+    }
+
+    match jumps
+        .into_iter()
+        .find(|(_, target)| !starts.contains(target))
+    {
+        Some((offset, target)) => Err(BytecodeError::InvalidJump {
+            function,
+            offset,
+            target,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// CRC-32 (IEEE) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,344 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compiler::{
+            code::{Instructions, Opcode},
+            serialization::{BytecodeError, FORMAT_VERSION},
+            test_utils::{compile, flatten_instructions, flatten_u8_instructions},
+            Bytecode,
+        },
+        errors::MonkeyError,
+        object::{CompiledFunction, DebugInfo, Object},
+        vm::{GLOBALS_SIZE, VM},
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let inputs = vec![
+            "1 + 2",
+            r#""hello" + " " + "world""#,
+            "let x = -9223372036854775807; x",
+            "1.5 * 2e-3",
+            r"
+            let new_adder = fn(x) { fn(y) { x + y } };
+            let add_two = new_adder(2);
+            add_two(40)
+            ",
+            r"
+            let a = 0;
+            while (a < 10) {
+                let a = a + 1;
+            }
+            [a, {1: true}]
+            ",
+            r#"
+            let counter = fn() { let n = 0; fn() { n += 1; n } };
+            let h = {"k": [1]};
+            h["k"][0] *= counter()();
+            h
+            "#,
+        ];
+
+        for input in inputs {
+            let bytecode = compile(input);
+            let bytes = bytecode.serialize().unwrap();
+            let loaded = Bytecode::deserialize(&bytes).unwrap();
+            assert_eq!(loaded, bytecode, "round trip failed for {input}");
+        }
+    }
+
+    #[test]
+    fn test_run_loaded_bytecode() {
+        let bytecode = compile(
+            r"
+            let fibonacci = fn(x) {
+                if (x < 2) { x } else { fibonacci(x - 1) + fibonacci(x - 2) }
+            };
+            fibonacci(15)
+            ",
+        );
+        let bytes = bytecode.serialize().unwrap();
+
+        let mut vm = VM::new(Bytecode::deserialize(&bytes).unwrap());
+        vm.run().unwrap();
+        assert_eq!(
+            *vm.last_popped_stack_element().unwrap(),
+            Object::INTEGER(610)
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let bytes = compile("let x = 1; x * 2").serialize().unwrap();
+
+        assert_eq!(
+            Bytecode::deserialize(b"let x = 1;"),
+            Err(BytecodeError::NotBytecode)
+        );
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        assert_eq!(
+            Bytecode::deserialize(&wrong_version),
+            Err(BytecodeError::UnsupportedVersion {
+                found: FORMAT_VERSION + 1
+            })
+        );
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            Bytecode::deserialize(&corrupted),
+            Err(BytecodeError::ChecksumMismatch { .. })
+        ));
+
+        assert!(matches!(
+            Bytecode::deserialize(&bytes[..bytes.len() - 1]),
+            Err(BytecodeError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(
+            Bytecode::deserialize(&bytes[..6]),
+            Err(BytecodeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constant() {
+        let bytecode = Bytecode {
+            instructions: compile("1").instructions,
+            constants: vec![Object::ARRAY(vec![])],
+            debug: DebugInfo::default(),
+        };
+
+        assert_eq!(
+            bytecode.serialize(),
+            Err(BytecodeError::UnsupportedConstant {
+                object: String::from("ARRAY")
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_operands() {
+        let function = Object::COMPILEDFUNCTION(CompiledFunction {
+            instructions: flatten_u8_instructions(vec![
+                Opcode::GetFree.make(vec![3]),
+                Opcode::ReturnValue.make(vec![]),
+            ]),
+            ..Default::default()
+        });
+        // Loading a file rejects most of them, the VM still checks code that
+        // was built by hand
+        let tests = vec![
+            (
+                vec![Opcode::Constant.make(vec![7])],
+                "Unknown constant 7, the program has 1",
+            ),
+            (
+                vec![Opcode::Constant.make(vec![70000])],
+                "Unknown constant 70000, the program has 1",
+            ),
+            (
+                vec![Opcode::Closure.make(vec![7, 0])],
+                "Unknown constant 7, the program has 1",
+            ),
+            (
+                vec![Opcode::Closure.make(vec![0, 0]), Opcode::Call.make(vec![0])],
+                "Unknown free variable 3, the closure has 0",
+            ),
+        ];
+
+        for (instructions, expected) in tests {
+            let bytecode = Bytecode {
+                instructions: flatten_instructions(instructions),
+                constants: vec![function.clone()],
+                debug: DebugInfo::default(),
+            };
+            assert_invalid_bytecode(bytecode, expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_instructions() {
+        let function = |instructions: Vec<Instructions>| {
+            Object::COMPILEDFUNCTION(CompiledFunction {
+                instructions: flatten_u8_instructions(instructions),
+                ..Default::default()
+            })
+        };
+        let truncated = |mut instructions: Instructions| {
+            instructions.data.pop();
+            instructions
+        };
+        let tests = vec![
+            (
+                vec![truncated(Opcode::Constant.make(vec![0]))],
+                vec![Object::INTEGER(1)],
+                BytecodeError::InvalidInstruction {
+                    function: None,
+                    offset: 0,
+                    message: String::from("Truncated operands for OpConstant"),
+                },
+            ),
+            (
+                vec![
+                    Opcode::Null.make(vec![]),
+                    truncated(Opcode::Closure.make(vec![0, 0])),
+                ],
+                vec![function(vec![Opcode::Return.make(vec![])])],
+                BytecodeError::InvalidInstruction {
+                    function: None,
+                    offset: 1,
+                    message: String::from("Truncated operands for OpClosure"),
+                },
+            ),
+            (
+                vec![Opcode::Closure.make(vec![0, 0])],
+                vec![function(vec![
+                    Opcode::True.make(vec![]),
+                    truncated(Opcode::GetLocal.make(vec![0])),
+                ])],
+                BytecodeError::InvalidInstruction {
+                    function: Some(0),
+                    offset: 1,
+                    message: String::from("Truncated operands for OpGetLocal"),
+                },
+            ),
+            (
+                vec![Instructions::new(vec![255])],
+                vec![],
+                BytecodeError::InvalidInstruction {
+                    function: None,
+                    offset: 0,
+                    message: String::from("Unknown opcode 255"),
+                },
+            ),
+            (
+                vec![Instructions::new(vec![
+                    Opcode::Wide as u8,
+                    Opcode::Pop as u8,
+                ])],
+                vec![],
+                BytecodeError::InvalidInstruction {
+                    function: None,
+                    offset: 0,
+                    message: String::from("OpPop cannot be wide"),
+                },
+            ),
+            (
+                vec![Opcode::Constant.make(vec![0]), Opcode::Jump.make(vec![1])],
+                vec![Object::INTEGER(1)],
+                BytecodeError::InvalidJump {
+                    function: None,
+                    offset: 3,
+                    target: 1,
+                },
+            ),
+            (
+                vec![Opcode::Try.make(vec![9])],
+                vec![],
+                BytecodeError::InvalidJump {
+                    function: None,
+                    offset: 0,
+                    target: 9,
+                },
+            ),
+            (
+                vec![Opcode::Constant.make(vec![1])],
+                vec![Object::INTEGER(1)],
+                BytecodeError::OperandOutOfRange {
+                    function: None,
+                    offset: 0,
+                    operand: String::from("constant 1"),
+                },
+            ),
+            (
+                vec![Opcode::Closure.make(vec![0, 0])],
+                vec![Object::INTEGER(1)],
+                BytecodeError::NotAFunction {
+                    function: None,
+                    offset: 0,
+                    constant: 0,
+                },
+            ),
+            (
+                vec![Opcode::GetGlobal.make(vec![GLOBALS_SIZE as i32])],
+                vec![],
+                BytecodeError::OperandOutOfRange {
+                    function: None,
+                    offset: 0,
+                    operand: format!("global {GLOBALS_SIZE}"),
+                },
+            ),
+            (
+                vec![Opcode::GetBuiltin.make(vec![200])],
+                vec![],
+                BytecodeError::OperandOutOfRange {
+                    function: None,
+                    offset: 0,
+                    operand: String::from("builtin 200"),
+                },
+            ),
+        ];
+
+        for (instructions, constants, expected) in tests {
+            // Serializing computes the checksum of the broken code
+            let bytes = Bytecode {
+                instructions: flatten_instructions(instructions),
+                constants,
+                debug: DebugInfo::default(),
+            }
+            .serialize()
+            .unwrap();
+
+            assert_eq!(Bytecode::deserialize(&bytes), Err(expected));
+        }
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let function = Object::COMPILEDFUNCTION(CompiledFunction {
+            instructions: flatten_u8_instructions(vec![
+                Opcode::GetLocal.make(vec![5]),
+                Opcode::ReturnValue.make(vec![]),
+            ]),
+            ..Default::default()
+        });
+        let tests = vec![
+            (vec![Opcode::Array.make(vec![2])], "Stack underflow"),
+            (vec![Opcode::HashMap.make(vec![2])], "Stack underflow"),
+            (vec![Opcode::Call.make(vec![3])], "Stack underflow"),
+            (
+                vec![Opcode::Null.make(vec![]), Opcode::TailCall.make(vec![3])],
+                "Stack underflow",
+            ),
+            (vec![Opcode::GetLocal.make(vec![0])], "Unknown local 0"),
+            (
+                vec![Opcode::Closure.make(vec![0, 0]), Opcode::Call.make(vec![0])],
+                "Unknown local 5",
+            ),
+            (vec![Opcode::Return.make(vec![])], "There was no frame"),
+        ];
+
+        for (instructions, expected) in tests {
+            let bytecode = Bytecode {
+                instructions: flatten_instructions(instructions),
+                constants: vec![function.clone()],
+                debug: DebugInfo::default(),
+            };
+            let loaded = Bytecode::deserialize(&bytecode.serialize().unwrap()).unwrap();
+            assert_invalid_bytecode(loaded, expected);
+        }
+    }
+
+    fn assert_invalid_bytecode(bytecode: Bytecode, expected: &str) {
+        let mut vm = VM::new(bytecode);
+        match vm.run() {
+            Err(err) => assert!(
+                matches!(&err.error, MonkeyError::InvalidBytecode(message) if message == expected),
+                "{err}"
+            ),
+            Ok(()) => panic!("{expected}: the program ran"),
+        }
+    }
+}
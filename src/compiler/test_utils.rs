@@ -1,11 +1,19 @@
 use std::rc::Rc;
 
 use crate::{
-    compiler::{code::Instructions, Compiler},
+    compiler::{code::Instructions, Bytecode, Compiler},
     object::{test_utils::check_constants, Object},
     parser::parse,
 };
 
+/// Compiles a program that is known to be valid.
+#[allow(dead_code)]
+pub(crate) fn compile(input: &str) -> Bytecode {
+    let mut compiler = Compiler::new();
+    compiler.compile(parse(input)).unwrap();
+    compiler.bytecode()
+}
+
 #[allow(dead_code)]
 pub(crate) fn check_instructions(instructions: &Instructions, expected: &Instructions) {
     assert_eq!(
@@ -6,8 +6,10 @@ pub mod tests {
         compiler::{
             code::Opcode,
             test_utils::{flatten_instructions, run_compiler, CompilerTestCase},
+            Compiler,
         },
         object::Object,
+        parser::parse,
     };
 
     #[test]
@@ -164,4 +166,21 @@ pub mod tests {
 
         run_compiler(tests);
     }
+
+    #[test]
+    fn test_loop_statements_outside_of_loops() {
+        let tests = vec![
+            ("break;", "break outside of a loop"),
+            ("continue;", "continue outside of a loop"),
+            (
+                "while (true) { let f = fn() { break; }; f(); }",
+                "break outside of a loop",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut compiler = Compiler::new();
+            assert_eq!(compiler.compile(parse(input)), Err(expected.to_string()));
+        }
+    }
 }
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        differential::{check, Outcome},
+        object::Object,
+    };
+
+    fn error(kind: &'static str, message: &str) -> Outcome {
+        Outcome::Error {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    // The engines used to disagree on each of these programs
+    #[test]
+    fn test_former_divergences() {
+        let tests = vec![
+            ("7 % 3", Outcome::Value(Object::INTEGER(1))),
+            ("-7 % 3", Outcome::Value(Object::INTEGER(-1))),
+            ("1 / 0", error("DivisionByZero", "division by zero")),
+            ("5 % 0", error("DivisionByZero", "division by zero")),
+            (
+                "let f = fn(a) { a }; f(1, 2)",
+                error("ArityError", "wrong number of arguments: want=1, got=2"),
+            ),
+            ("return 5; 10", Outcome::Value(Object::INTEGER(5))),
+            (
+                "1 && 2",
+                error("TypeError", "unknown operator: INTEGER && INTEGER"),
+            ),
+            (
+                "{[1]: 2}",
+                error("TypeError", "unusable as hash key: ARRAY"),
+            ),
+            (
+                "len(1)",
+                error("TypeError", "argument to `len` not supported, got INTEGER"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match check(input) {
+                Ok(outcome) => assert_eq!(outcome, expected, "wrong outcome for {input}"),
+                Err(mismatch) => panic!("{mismatch}"),
+            }
+        }
+    }
+}
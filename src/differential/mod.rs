@@ -0,0 +1,215 @@
+//! Differential testing of the two execution engines.
+//!
+//! The same program is run by the tree-walking [`Evaluator`] and by the
+//! [`Compiler`] + [`VM`] pipeline, and their results are compared. Both engines
+//! implement the same language, so any difference is a bug in one of them.
+//!
+//! The generator of random programs that feeds the harness lives with the
+//! integration tests, in `tests/generator`.
+
+mod differential_tests;
+
+use std::fmt::Display;
+
+use crate::{
+    compiler::{optimizer::optimize, Bytecode, Compiler},
+    errors::MonkeyError,
+    interpreter::evaluator::Evaluator,
+    object::Object,
+    parser::{ast::Statement, parse},
+    vm::VM,
+};
+
+/// What running a program produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Value(Object),
+    /// The kind of the error, see [`MonkeyError::kind`], and its message.
+    Error {
+        kind: &'static str,
+        message: String,
+    },
+}
+
+impl Outcome {
+    /// Two outcomes are equivalent if they are equal values or errors of the
+    /// same kind with the same message.
+    ///
+    /// The compiler rejects undefined variables and loop statements outside of
+    /// loops before anything runs, so its errors only match the evaluator's
+    /// errors for these.
+    ///
+    /// Functions cannot be compared across engines, as one keeps the syntax
+    /// tree and the other the bytecode, so only their arity is compared.
+    pub fn is_equivalent(&self, other: &Outcome) -> bool {
+        match (self, other) {
+            (Outcome::Value(x), Outcome::Value(y)) => equivalent_objects(x, y),
+            (
+                Outcome::Error {
+                    kind: x,
+                    message: m,
+                },
+                Outcome::Error {
+                    kind: y,
+                    message: n,
+                },
+            ) => (x == y && m == n) || found_by_compiler(x, y) || found_by_compiler(y, x),
+            _ => false,
+        }
+    }
+
+    fn error(err: &MonkeyError) -> Self {
+        Outcome::Error {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+
+    fn compile_error(message: String) -> Self {
+        Outcome::Error {
+            kind: "Compile",
+            message,
+        }
+    }
+}
+
+/// True if `compiled` is a compile error for what the evaluator reported as
+/// `evaluated`.
+fn found_by_compiler(compiled: &str, evaluated: &str) -> bool {
+    compiled == "Compile" && matches!(evaluated, "UndefinedVariable" | "LoopControl")
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Value(x) => write!(f, "{x}"),
+            Outcome::Error { kind, message } => write!(f, "{kind}: {message}"),
+        }
+    }
+}
+
+fn equivalent_objects(x: &Object, y: &Object) -> bool {
+    match (x, y) {
+        (Object::ARRAY(x), Object::ARRAY(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| equivalent_objects(x, y))
+        }
+        (Object::HASHMAP(x), Object::HASHMAP(y)) => {
+            x.len() == y.len()
+                && x.iter().all(|(key, x)| {
+                    y.get(key)
+                        .is_some_and(|y: &Object| equivalent_objects(x, y))
+                })
+        }
+        _ => match (arity(x), arity(y)) {
+            (Some(x), Some(y)) => x == y,
+            (None, None) => x == y,
+            _ => false,
+        },
+    }
+}
+
+/// Number of parameters of a user defined function, from either engine.
+fn arity(object: &Object) -> Option<usize> {
+    match object {
+        Object::FUNCTION(function) => Some(function.parameters.len()),
+        Object::COMPILEDFUNCTION(function) => Some(function.num_parameters),
+        Object::CLOSURE(closure) => Some(closure.function.num_parameters),
+        _ => None,
+    }
+}
+
+/// Two engines that did not agree on a program.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub input: String,
+    /// Name of each engine and what it produced.
+    pub left: (&'static str, Outcome),
+    pub right: (&'static str, Outcome),
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "the {} and the {} disagree on:",
+            self.left.0, self.right.0
+        )?;
+        writeln!(f, "{}", self.input)?;
+        writeln!(f, "{}: {}", self.left.0, self.left.1)?;
+        write!(f, "{}: {}", self.right.0, self.right.1)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+pub fn run_evaluator(input: &str) -> Outcome {
+    match Evaluator::new().eval(parse(input)) {
+        Ok(x) => Outcome::Value(x),
+        Err(err) => Outcome::error(&err),
+    }
+}
+
+pub fn run_vm(input: &str) -> Outcome {
+    let mut compiler = Compiler::new();
+    if let Err(err) = compiler.compile(parse(input)) {
+        return Outcome::compile_error(err);
+    }
+    execute(compiler.bytecode())
+}
+
+/// Runs `input` with the VM after the optimization passes.
+pub fn run_optimized_vm(input: &str) -> Outcome {
+    let mut compiler = Compiler::new();
+    if let Err(err) = compiler.compile(parse(input)) {
+        return Outcome::compile_error(err);
+    }
+    match optimize(compiler.bytecode()) {
+        Ok(bytecode) => execute(bytecode),
+        Err(err) => Outcome::compile_error(err),
+    }
+}
+
+fn execute(bytecode: Bytecode) -> Outcome {
+    let mut vm = VM::new(bytecode);
+    if let Err(err) = vm.run() {
+        return Outcome::error(&err.error);
+    }
+    match vm.last_popped_stack_element() {
+        Ok(x) => Outcome::Value(x.as_ref().clone()),
+        Err(err) => Outcome::error(&err),
+    }
+}
+
+/// Runs `input` with both engines, and with the VM on optimized bytecode, and
+/// returns the evaluator's outcome if they all agree.
+///
+/// Only the value of a final expression statement is compared: after a `let`
+/// or a loop the VM has no value to report, it only remembers the last value it
+/// discarded.
+pub fn check(input: &str) -> Result<Outcome, Box<Mismatch>> {
+    let has_value = matches!(
+        parse(input).statements.last(),
+        Some(Statement::Expression(_))
+    );
+    let compare = |left: (&'static str, Outcome), right: (&'static str, Outcome)| {
+        let agree = match (&left.1, &right.1) {
+            (Outcome::Value(_), Outcome::Value(_)) if !has_value => true,
+            (x, y) => x.is_equivalent(y),
+        };
+        if agree {
+            Ok(left.1)
+        } else {
+            Err(Box::new(Mismatch {
+                input: input.to_string(),
+                left,
+                right,
+            }))
+        }
+    };
+
+    let vm = compare(
+        ("VM", run_vm(input)),
+        ("optimized VM", run_optimized_vm(input)),
+    )?;
+    compare(("evaluator", run_evaluator(input)), ("VM", vm))
+}
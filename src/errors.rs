@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::{budget::BudgetError, object::Object, parser::parser_errors::ParserErrors};
+
+/// Anything that can go wrong with Monkey code, in either engine.
+///
+/// The runtime errors are the same for the evaluator and the VM, and builtins
+/// and native functions fail with them too, so a host can match on the kind
+/// of a failure whatever ran the code.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum MonkeyError {
+    /// The code does not parse.
+    Syntax(ParserErrors),
+    /// The compiler rejected the program.
+    Compile(String),
+    /// An operation got values of types it does not work on, like `1 + true`.
+    TypeError(String),
+    /// A value that cannot be indexed, or not with that index.
+    IndexError(String),
+    /// A function called with the wrong number of arguments.
+    ArityError {
+        expected: usize,
+        got: usize,
+    },
+    DivisionByZero,
+    /// An integer operation whose result does not fit in 64 bits.
+    IntegerOverflow,
+    /// A name that is not bound to anything.
+    UndefinedVariable(String),
+    /// A `break` or a `continue` that is not inside a loop.
+    LoopControl(String),
+    /// A native function failed for a reason of its own.
+    Native(String),
+    /// A limit of the VM or of the budget stopped the program.
+    Limit {
+        limit: Limit,
+        message: String,
+    },
+    /// The VM was given bytecode it cannot run.
+    InvalidBytecode(String),
+    /// A value thrown by a `throw` statement that no `catch` handled.
+    Thrown(Box<Object>),
+}
+
+impl MonkeyError {
+    /// The limit that stopped the program, if that is what happened: the
+    /// program itself did not fail then.
+    pub fn limit(&self) -> Option<Limit> {
+        match self {
+            MonkeyError::Limit { limit, .. } => Some(*limit),
+            _ => None,
+        }
+    }
+
+    /// An infix operator applied to operands it does not work on, like
+    /// `1 + true`.
+    pub fn unsupported_infix(left: &Object, operator: impl Display, right: &Object) -> Self {
+        let (left, right) = (left.get_type(), right.get_type());
+        if left == right {
+            MonkeyError::TypeError(format!("unknown operator: {left} {operator} {right}"))
+        } else {
+            MonkeyError::TypeError(format!("type mismatch: {left} {operator} {right}"))
+        }
+    }
+
+    /// A prefix operator applied to an operand it does not work on, like
+    /// `-true`.
+    pub fn unsupported_prefix(operator: impl Display, right: &Object) -> Self {
+        MonkeyError::TypeError(format!("unknown operator: {operator}{}", right.get_type()))
+    }
+
+    /// An index expression on a value that cannot be indexed, or not with that
+    /// index.
+    pub fn unsupported_index(left: &Object, index: &Object) -> Self {
+        MonkeyError::IndexError(format!(
+            "index operator not supported: {}[{}]",
+            left.get_type(),
+            index.get_type()
+        ))
+    }
+
+    /// A value used as a hash key that cannot be one.
+    pub fn unusable_key(key: &Object) -> Self {
+        MonkeyError::TypeError(format!("unusable as hash key: {}", key.get_type()))
+    }
+
+    /// A call on a value that is not a function.
+    pub fn not_a_function(callee: &Object) -> Self {
+        MonkeyError::TypeError(format!("not a function: {}", callee.get_type()))
+    }
+
+    /// Name of the variant, given to Monkey code as the `kind` of a caught
+    /// error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MonkeyError::Syntax(_) => "Syntax",
+            MonkeyError::Compile(_) => "Compile",
+            MonkeyError::TypeError(_) => "TypeError",
+            MonkeyError::IndexError(_) => "IndexError",
+            MonkeyError::ArityError { .. } => "ArityError",
+            MonkeyError::DivisionByZero => "DivisionByZero",
+            MonkeyError::IntegerOverflow => "IntegerOverflow",
+            MonkeyError::UndefinedVariable(_) => "UndefinedVariable",
+            MonkeyError::LoopControl(_) => "LoopControl",
+            MonkeyError::Native(_) => "Native",
+            MonkeyError::Limit { .. } => "Limit",
+            MonkeyError::InvalidBytecode(_) => "InvalidBytecode",
+            MonkeyError::Thrown(_) => "Thrown",
+        }
+    }
+
+    /// True if a `try` statement can handle the error.
+    ///
+    /// Only errors of the program itself are: limits must stop it whatever
+    /// it does, and the others are found before the VM runs anything, so the
+    /// engines could not agree on them.
+    pub fn is_catchable(&self) -> bool {
+        matches!(
+            self,
+            MonkeyError::TypeError(_)
+                | MonkeyError::IndexError(_)
+                | MonkeyError::ArityError { .. }
+                | MonkeyError::DivisionByZero
+                | MonkeyError::IntegerOverflow
+                | MonkeyError::Native(_)
+                | MonkeyError::Thrown(_)
+        )
+    }
+
+    /// The value a `catch` binds for this error: the thrown value itself, or
+    /// a hash with the `kind` and the `message` of the error.
+    pub fn into_exception(self) -> Object {
+        match self {
+            MonkeyError::Thrown(value) => *value,
+            err => Object::HASHMAP(HashMap::from([
+                (
+                    Object::STRING(String::from("kind")),
+                    Object::STRING(err.kind().to_string()),
+                ),
+                (
+                    Object::STRING(String::from("message")),
+                    Object::STRING(err.to_string()),
+                ),
+            ])),
+        }
+    }
+}
+
+impl Display for MonkeyError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            MonkeyError::Syntax(errors) => write!(f, "{errors}"),
+            MonkeyError::Compile(message)
+            | MonkeyError::TypeError(message)
+            | MonkeyError::IndexError(message)
+            | MonkeyError::Native(message)
+            | MonkeyError::Limit { message, .. }
+            | MonkeyError::InvalidBytecode(message) => write!(f, "{message}"),
+            MonkeyError::ArityError { expected, got } => {
+                write!(f, "wrong number of arguments: want={expected}, got={got}")
+            }
+            MonkeyError::DivisionByZero => write!(f, "division by zero"),
+            MonkeyError::IntegerOverflow => write!(f, "integer overflow"),
+            MonkeyError::UndefinedVariable(name) => write!(f, "identifier not found: {name}"),
+            MonkeyError::LoopControl(statement) => write!(f, "{statement} outside of a loop"),
+            MonkeyError::Thrown(value) => write!(f, "uncaught exception: {value}"),
+        }
+    }
+}
+
+impl Error for MonkeyError {}
+
+/// A limit of the [`VmConfig`](crate::vm::config::VmConfig) or of the
+/// [`Budget`](crate::budget::Budget).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Stack,
+    Frames,
+    /// The program created as many objects as its allocation budget allows.
+    Allocations,
+    /// The program took as many steps as its budget allows.
+    OutOfFuel,
+    /// The deadline of the budget passed or the program was cancelled.
+    Interrupted,
+}
+
+impl From<BudgetError> for Limit {
+    fn from(err: BudgetError) -> Self {
+        match err {
+            BudgetError::OutOfFuel => Limit::OutOfFuel,
+            BudgetError::Interrupted => Limit::Interrupted,
+        }
+    }
+}
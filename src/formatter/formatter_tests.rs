@@ -124,6 +124,21 @@ let d = !!true;
         assert_eq!(formatted, expected);
     }
 
+    #[test]
+    fn test_string_escapes() {
+        let input = r#"let x = "line\n\t\"tab\"\u{5c}";
+puts(["a\\b", {"\r": "\u{1}"}]);
+"#;
+
+        let formatted = format(input);
+        let expected = r#"let x = "line\n\t\"tab\"\\";
+puts(["a\\b", {"\r": "\u{1}"}]);
+"#;
+
+        println!("{formatted}");
+        assert_eq!(formatted, expected);
+    }
+
     #[test]
     fn test_fibonacci_it_formatting() {
         let input = r"
@@ -368,6 +383,64 @@ let b = 20;
         assert_eq!(format(input), expected);
     }
 
+    #[test]
+    fn test_floats() {
+        let input = r"
+            let a = 3.14;
+            let b = 1e-9 * 2.5E3;
+            let c = -0.5 + 10.0;
+        ";
+
+        let expected = r"let a = 3.14;
+let b = 1e-9 * 2.5E3;
+let c = -0.5 + 10.0;
+";
+
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_float_literals_keep_their_spelling() {
+        let input = r"
+            let a = [1e3, 2.5E-3, 6.02e+23, 1.50];
+            let b = 15e-1 == 1.5;
+        ";
+
+        let expected = r"let a = [1e3, 2.5E-3, 6.02e+23, 1.50];
+let b = 15e-1 == 1.5;
+";
+
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let input = r#"
+            let a = 1;
+            a=2;
+            a +=   1 + 2 * 3;
+            a -= -1;
+            a = b = c;
+            let x = [[1]];
+            x[0][0] *= (2 + 3);
+            h["k"] %= 2;
+            f(a /= 2) + (a = 1);
+        "#;
+
+        let expected = r#"let a = 1;
+a = 2;
+a += 1 + 2 * 3;
+a -= -1;
+a = b = c;
+let x = [[1]];
+x[0][0] *= 2 + 3;
+h["k"] %= 2;
+f(a /= 2) + (a = 1);
+"#;
+
+        assert_eq!(format(input), expected);
+    }
+
     #[test]
     fn test_basic_operators() {
         let input = r"
@@ -580,4 +653,98 @@ a(12);
 
         assert_eq!(format(input), expected);
     }
+
+    #[test]
+    fn test_try_catch() {
+        let input = r#"
+    let check = fn(x){
+    try { if (x<0){throw "negative"} x }catch(e){
+            puts(e);
+      0
+        }
+    };
+        "#;
+
+        let expected = r#"let check = fn (x) {
+    try {
+        if (x < 0) {
+            throw "negative";
+        }
+        x;
+    } catch (e) {
+        puts(e);
+        0;
+    }
+};
+"#;
+
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = r"// Compute things
+/* block
+   comment */
+let a = 1;   // trailing a
+let f = fn(x) { // body
+        // leading in body
+    let y = x * 2; /* inline */
+    y // returned
+      // end of body
+};
+if (a > 0) {
+    puts(a);
+    // after puts
+} else {
+    // empty else
+}
+while (a<3) {
+// inside while
+    let a = a + 1;
+}
+let arr = [1, // one
+  2];
+f(a) / 2 // division
+// The end
+";
+
+        let expected = r"// Compute things
+/* block
+   comment */
+let a = 1; // trailing a
+let f = fn (x) { // body
+    // leading in body
+    let y = x * 2; /* inline */
+    y // returned
+    // end of body
+};
+if (a > 0) {
+    puts(a);
+    // after puts
+} else {
+    // empty else
+}
+while (a < 3) {
+    // inside while
+    let a = a + 1;
+}
+let arr = [1, 2]; // one
+f(a) / 2; // division
+// The end
+";
+
+        assert_eq!(format(input), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_only_comments() {
+        let input = r"
+    // nothing to see
+  /* here */
+";
+
+        assert_eq!(format(input), "// nothing to see\n/* here */\n");
+    }
 }
@@ -1,9 +1,14 @@
 pub mod cli;
 mod formatter_tests;
 
-use crate::parser::{
-    ast::{BlockStatement, Expression, FunctionLiteral, Precedence, Program, Statement},
-    parse,
+use std::collections::VecDeque;
+
+use crate::{
+    lexer::token::Comment,
+    parser::{
+        ast::{BlockStatement, Expression, FunctionLiteral, Precedence, Program, Statement},
+        parse,
+    },
 };
 
 /// A formatter function scope.
@@ -53,6 +58,9 @@ pub struct Formatter {
     /// The current formatter function scope.
     formatter_function_scope: Option<Box<FormatterFunctionScope>>,
 
+    /// Comments not written yet, in source order.
+    comments: VecDeque<Comment>,
+
     /// The output buffer.
     output: String,
 }
@@ -64,6 +72,7 @@ impl Formatter {
             preference: Precedence::Lowest,
             last_expression: None,
             formatter_function_scope: None,
+            comments: VecDeque::new(),
             output: String::new(),
         }
     }
@@ -75,6 +84,7 @@ impl Formatter {
 
     pub fn format_program(program: Program) -> String {
         let mut formatter = Self::new();
+        formatter.comments = program.comments.clone().into();
 
         formatter.visit_program(program);
         formatter.output.clone()
@@ -84,9 +94,11 @@ impl Formatter {
         for stmt in program.statements {
             self.visit_statement(&stmt);
         }
+        self.push_comments(usize::MAX);
     }
 
     fn visit_statement(&mut self, stmt: &Statement) {
+        self.push_comments(Self::statement_start(stmt));
         self.push_indent();
         match stmt {
             Statement::Let(let_stmt) => {
@@ -122,10 +134,34 @@ impl Formatter {
                 self.push_indent();
                 self.push("}");
             }
-            Statement::LoopStatements(cf) => {
+            Statement::LoopStatements(cf, _) => {
                 self.push(cf.to_string().as_str());
                 self.push(";");
             }
+            Statement::Try(tr) => {
+                // Nothing in the blocks is the value of the function, every
+                // expression gets its semicolon
+                let function_scope = self.formatter_function_scope.take();
+                self.push("try {\n");
+                self.indent += 1;
+                self.visit_block_statement(&tr.body);
+                self.indent -= 1;
+                self.push_indent();
+                self.push("} catch (");
+                self.push(tr.parameter.value.as_str());
+                self.push(") {\n");
+                self.indent += 1;
+                self.visit_block_statement(&tr.handler);
+                self.indent -= 1;
+                self.push_indent();
+                self.push("}");
+                self.formatter_function_scope = function_scope;
+            }
+            Statement::Throw(th) => {
+                self.push("throw ");
+                self.visit_expression(&th.value);
+                self.push(";");
+            }
         }
         self.push("\n");
         self.last_expression = None;
@@ -136,7 +172,7 @@ impl Formatter {
             Expression::Identifier(ident) => {
                 self.push(ident.value.as_str());
             }
-            Expression::Primitive(primitive) => {
+            Expression::Primitive(primitive, _) => {
                 self.push(primitive.to_string().as_str());
             }
             Expression::Prefix(prefix) => {
@@ -153,12 +189,12 @@ impl Formatter {
                             self.push("(");
                             needs_parenthesis = true;
                         }
-                        Expression::Infix(last_infix) => {
-                            if Precedence::from(&last_infix.token) > Precedence::from(&infix.token)
-                            {
-                                self.push("(");
-                                needs_parenthesis = true;
-                            }
+                        Expression::Infix(last_infix)
+                            if Precedence::from(&last_infix.token)
+                                > Precedence::from(&infix.token) =>
+                        {
+                            self.push("(");
+                            needs_parenthesis = true;
                         }
                         _ => {}
                     }
@@ -232,6 +268,29 @@ impl Formatter {
                 self.visit_expression(&index.index);
                 self.push("]");
             }
+            Expression::Assignment(assignment) => {
+                // An assignment binds looser than any operator
+                let needs_parenthesis = matches!(
+                    self.last_expression,
+                    Some(Expression::Prefix(_) | Expression::Infix(_))
+                );
+                if needs_parenthesis {
+                    self.push("(");
+                }
+
+                self.last_expression = Some(exp.clone());
+                self.visit_expression(&assignment.target);
+                self.push(" ");
+                self.push(assignment.token.to_string().as_str());
+                self.push(" ");
+
+                self.last_expression = Some(exp.clone());
+                self.visit_expression(&assignment.value);
+
+                if needs_parenthesis {
+                    self.push(")");
+                }
+            }
         }
 
         self.last_expression = Some(exp.clone());
@@ -255,6 +314,7 @@ impl Formatter {
             self.visit_statement(stmt);
             self.formatter_function_scope.as_mut().unwrap().next();
         }
+        self.push_comments(func.body.end.offset);
         self.leave_function();
 
         self.push_indent();
@@ -265,6 +325,48 @@ impl Formatter {
         for stmt in &block.statements {
             self.visit_statement(stmt);
         }
+        self.push_comments(block.end.offset);
+    }
+
+    /// Writes the comments found before the byte `offset` of the source.
+    ///
+    /// Comments that followed some code on their line are appended to the
+    /// last written line, the others get their own line.
+    fn push_comments(&mut self, offset: usize) {
+        while self
+            .comments
+            .front()
+            .is_some_and(|comment| comment.span.offset < offset)
+        {
+            let comment = self.comments.pop_front().unwrap();
+            if comment.trailing && self.output.ends_with('\n') {
+                self.output.pop();
+                self.push(" ");
+            } else {
+                self.push_indent();
+            }
+            self.push(comment.text.as_str());
+            self.push("\n");
+        }
+    }
+
+    /// Offset of the first token of a statement.
+    ///
+    /// The span of some expressions points to their operator, so we look for
+    /// their leftmost operand instead.
+    fn statement_start(stmt: &Statement) -> usize {
+        let mut exp = match stmt {
+            Statement::Expression(exp) => exp,
+            _ => return stmt.span().offset,
+        };
+        loop {
+            exp = match exp {
+                Expression::Infix(infix) => &infix.left,
+                Expression::FunctionCall(call) => &call.function,
+                Expression::IndexExpression(index) => &index.left,
+                _ => return exp.span().offset,
+            };
+        }
     }
 
     fn get_precedence(&self, exp: &Expression) -> Precedence {
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        budget::{Budget, CancelHandle},
+        errors::{Limit, MonkeyError},
+        interpreter::evaluator::Evaluator,
+        object::Object,
+        parser::parse,
+    };
+
+    #[test]
+    fn test_budgets() {
+        let cancel = CancelHandle::new();
+        cancel.cancel();
+        let tests = vec![
+            (
+                Budget {
+                    fuel: Some(100),
+                    ..Default::default()
+                },
+                Limit::OutOfFuel,
+                "out of fuel: a program can evaluate at most 100 statements and expressions",
+            ),
+            (
+                Budget {
+                    deadline: Some(Instant::now() + Duration::from_millis(20)),
+                    ..Default::default()
+                },
+                Limit::Interrupted,
+                "interrupted",
+            ),
+            (
+                Budget {
+                    cancel: Some(cancel),
+                    ..Default::default()
+                },
+                Limit::Interrupted,
+                "interrupted",
+            ),
+        ];
+
+        for (budget, expected, message) in tests {
+            let mut evaluator = Evaluator::with_budget(budget);
+            let err = evaluator
+                .eval(parse("let f = fn(x) { x }; while (true) { f(1) }"))
+                .unwrap_err();
+
+            assert_eq!(err.to_string(), message);
+            assert_eq!(err.limit(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_cancel_from_another_thread() {
+        let cancel = CancelHandle::new();
+        let mut evaluator = Evaluator::with_budget(Budget {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        });
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        });
+
+        let err = evaluator.eval(parse("while (true) {}")).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::Interrupted));
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_fuel_is_per_program() {
+        let mut evaluator = Evaluator::with_budget(Budget {
+            fuel: Some(20),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            evaluator.eval(parse("let a = 1 + 2;")).unwrap(),
+            Object::NULL
+        );
+        assert_eq!(evaluator.fuel_used(), 4);
+
+        assert_eq!(evaluator.eval(parse("a * 2")).unwrap(), Object::INTEGER(6));
+        assert!(matches!(
+            evaluator.eval(parse("1 / 0")),
+            Err(MonkeyError::DivisionByZero)
+        ));
+
+        let err = evaluator.eval(parse("while (true) {}")).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::OutOfFuel));
+        // The state of the interrupted program is kept
+        assert_eq!(evaluator.eval(parse("a")).unwrap(), Object::INTEGER(3));
+    }
+}
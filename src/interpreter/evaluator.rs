@@ -1,22 +1,55 @@
 use crate::{
+    budget::{Budget, BudgetError, Meter},
+    errors::{Limit, MonkeyError},
     lexer::token::Token,
     object::{
-        builtins::BuiltinFunction,
         enviroment::Environment,
         {Function, Object, FALSE, NULL, TRUE},
     },
     parser::ast::{
-        BlockStatement, Conditional, Expression, HashMapLiteral, Identifier, IndexExpression,
-        Primitive, Program, Statement,
+        Assignment, BlockStatement, Conditional, Expression, HashMapLiteral, Identifier,
+        IndexExpression, LoopStatement, Primitive, Program, Statement,
     },
+    runtime::Runtime,
 };
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
 
 pub struct Evaluator {
     env: Rc<RefCell<Environment>>,
+    meter: Meter,
+    pub(crate) runtime: Runtime,
 }
 
+/// Why the evaluation of a block stopped before its end.
+enum Flow {
+    Return(Object),
+    Break,
+    Continue,
+    Error(MonkeyError),
+}
+
+impl From<MonkeyError> for Flow {
+    fn from(err: MonkeyError) -> Self {
+        Flow::Error(err)
+    }
+}
+
+impl Flow {
+    /// What the flow becomes when it reaches a function or the program
+    /// without meeting a loop.
+    fn into_result(self) -> Result<Object, MonkeyError> {
+        match self {
+            Flow::Return(x) => Ok(x),
+            Flow::Break => Err(MonkeyError::LoopControl(String::from("break"))),
+            Flow::Continue => Err(MonkeyError::LoopControl(String::from("continue"))),
+            Flow::Error(err) => Err(err),
+        }
+    }
+}
+
+type Eval = Result<Object, Flow>;
+
 impl Default for Evaluator {
     fn default() -> Self {
         Self::new()
@@ -25,127 +58,168 @@ impl Default for Evaluator {
 
 impl Evaluator {
     pub fn new() -> Self {
+        Self::with_budget(Budget::default())
+    }
+
+    /// Creates an evaluator that stops every program that goes over `budget`,
+    /// with one unit of fuel per statement or expression.
+    pub fn with_budget(budget: Budget) -> Self {
         Evaluator {
             env: Rc::new(RefCell::new(Environment::new())),
+            meter: Meter::new(budget),
+            runtime: Runtime::default(),
         }
     }
 
-    pub fn eval(&mut self, program: Program) -> Object {
+    /// Fuel used by the last program.
+    pub fn fuel_used(&self) -> u64 {
+        self.meter.used
+    }
+
+    /// Value of the global variable `name`, if it is defined.
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.env.borrow().get(name)
+    }
+
+    /// Defines the global variable `name`, or changes its value.
+    pub fn set_global(&mut self, name: &str, value: Object) {
+        self.env.borrow_mut().set(name.to_string(), value);
+    }
+
+    pub fn eval(&mut self, program: Program) -> Result<Object, MonkeyError> {
+        // Every program gets the whole budget, the environment is all that
+        // is kept between them
+        self.meter.used = 0;
+
+        // Rejected before anything runs, like the compiler does
+        if let Some(statement) = program.stray_loop_statement() {
+            return Err(MonkeyError::LoopControl(statement.to_string()));
+        }
+
         let mut result = NULL;
         for statement in program.statements {
-            result = self.eval_statement(statement);
-            match result {
-                Object::RETURN(x) => return *x,
-                Object::ERROR(x) => return Object::ERROR(x),
-                _ => (),
+            match self.eval_statement(statement) {
+                Ok(x) => result = x,
+                Err(flow) => return flow.into_result(),
             }
         }
-        result
+        Ok(result)
     }
 
-    fn eval_block_statemet(&mut self, block: BlockStatement) -> Object {
+    fn eval_block_statemet(&mut self, block: BlockStatement) -> Eval {
         let mut result = NULL;
         for statement in block.statements {
-            result = self.eval_statement(statement);
-            match result {
-                Object::RETURN(_) | Object::ERROR(_) => return result,
-                _ => (),
-            }
+            result = self.eval_statement(statement)?;
         }
-        result
+        Ok(result)
     }
 
-    #[allow(clippy::match_wildcard_for_single_variants, unreachable_patterns)]
-    fn eval_statement(&mut self, statement: Statement) -> Object {
+    fn eval_statement(&mut self, statement: Statement) -> Eval {
+        self.step()?;
         match statement {
             Statement::Expression(x) => self.eval_expression(x),
             Statement::Return(x) => {
-                let value = self.eval_expression(x.return_value);
-                if Self::is_error(&value) {
-                    return value;
-                }
-                Object::RETURN(Box::new(value))
+                let value = self.eval_expression(x.return_value)?;
+                Err(Flow::Return(value))
             }
             Statement::Let(x) => {
-                let value = self.eval_expression(x.value);
-                if Self::is_error(&value) {
-                    return value;
-                }
+                let value = self.eval_expression(x.value)?;
                 self.env.borrow_mut().set(x.name.to_string(), value);
-                NULL
+                Ok(NULL)
             }
             Statement::While(stm) => {
                 let mut result = NULL;
-                while Self::is_truthy(&self.eval_expression(stm.condition.clone())) {
-                    result = self.eval_block_statemet(stm.body.clone());
-                    match result {
-                        Object::RETURN(_) | Object::ERROR(_) => return result,
-                        _ => (),
+                loop {
+                    let condition = self.eval_expression(stm.condition.clone())?;
+                    if !Self::is_truthy(&condition) {
+                        break;
+                    }
+
+                    match self.eval_block_statemet(stm.body.clone()) {
+                        Ok(x) => result = x,
+                        Err(Flow::Break) => return Ok(NULL),
+                        Err(Flow::Continue) => result = NULL,
+                        Err(flow) => return Err(flow),
                     }
                 }
-                result
+                Ok(result)
+            }
+            Statement::LoopStatements(LoopStatement::Break, _) => Err(Flow::Break),
+            Statement::LoopStatements(LoopStatement::Continue, _) => Err(Flow::Continue),
+            Statement::Try(stm) => {
+                match self.eval_block_statemet(stm.body) {
+                    Err(Flow::Error(err)) if err.is_catchable() => {
+                        self.env
+                            .borrow_mut()
+                            .set(stm.parameter.to_string(), err.into_exception());
+                        self.eval_block_statemet(stm.handler)?;
+                    }
+                    Err(flow) => return Err(flow),
+                    Ok(_) => {}
+                }
+                Ok(NULL)
+            }
+            Statement::Throw(stm) => {
+                let value = self.eval_expression(stm.value)?;
+                Err(Flow::Error(MonkeyError::Thrown(Box::new(value))))
             }
-
-            _ => unimplemented!(), // I have decided not to implement the rest of the expressions,
-                                   // I will focus on the compiler
         }
     }
 
+    /// Spends fuel for a statement or an expression, the error stops the
+    /// program.
+    fn step(&mut self) -> Result<(), MonkeyError> {
+        self.meter.step().map_err(|err| MonkeyError::Limit {
+            limit: Limit::from(err),
+            message: match err {
+                BudgetError::OutOfFuel => format!(
+                    "out of fuel: a program can evaluate at most {} statements and expressions",
+                    self.meter.budget.fuel.unwrap_or_default()
+                ),
+                BudgetError::Interrupted => String::from("interrupted"),
+            },
+        })
+    }
+
     #[allow(clippy::match_wildcard_for_single_variants, unreachable_patterns)]
-    fn eval_expression(&mut self, expression: Expression) -> Object {
+    fn eval_expression(&mut self, expression: Expression) -> Eval {
+        self.step()?;
         match expression {
-            Expression::Primitive(x) => Self::eval_primitive_expression(x),
+            Expression::Primitive(x, _) => Ok(Self::eval_primitive_expression(x)),
             Expression::Prefix(operator) => {
-                let right = self.eval_expression(*operator.right);
-                if Self::is_error(&right) {
-                    return right;
-                }
-                Self::eval_prefix_expression(&operator.token, &right)
+                let right = self.eval_expression(*operator.right)?;
+                Ok(Self::eval_prefix_expression(&operator.token, &right)?)
             }
             Expression::Infix(operator) => {
-                let left = self.eval_expression(*operator.left);
-                if Self::is_error(&left) {
-                    return left;
-                }
-                let right = self.eval_expression(*operator.right);
-                if Self::is_error(&right) {
-                    return right;
-                }
-                Self::eval_infix_expression(&operator.token, left, right)
+                let left = self.eval_expression(*operator.left)?;
+                let right = self.eval_expression(*operator.right)?;
+                Ok(Self::eval_infix_expression(&operator.token, left, right)?)
             }
             Expression::Conditional(conditional) => self.eval_conditional_expression(conditional),
-            Expression::Identifier(x) => self.eval_identifier(&x),
+            Expression::Identifier(x) => Ok(self.eval_identifier(&x)?),
             Expression::FunctionLiteral(x) => {
                 let parameters = x.parameters;
                 let body = x.body;
-                Object::FUNCTION(Function {
+                Ok(Object::FUNCTION(Function {
                     parameters,
                     body,
                     environment: Rc::clone(&self.env),
-                })
+                }))
             }
             Expression::FunctionCall(x) => {
-                let function = self.eval_expression(*x.function);
-                if Self::is_error(&function) {
-                    return function;
-                }
-                let args = self.eval_expressions(x.arguments);
-                if args.len() == 1 && Self::is_error(&args[0]) {
-                    return args[0].clone();
-                }
-                self.apply_function(function, args)
+                let function = self.eval_expression(*x.function)?;
+                let args = self.eval_expressions(x.arguments)?;
+                Ok(self.apply_function(function, args)?)
             }
             Expression::ArrayLiteral(array) => {
-                let elements = self.eval_expressions(array.elements);
-                if elements.len() == 1 && Self::is_error(&elements[0]) {
-                    return elements[0].clone();
-                }
-                Object::ARRAY(elements)
+                let elements = self.eval_expressions(array.elements)?;
+                Ok(Object::ARRAY(elements))
             }
             Expression::IndexExpression(index_expression) => {
                 self.eval_index_expression(index_expression)
             }
             Expression::HashMapLiteral(hashmap) => self.eval_hashmap_literal(hashmap),
+            Expression::Assignment(assignment) => self.eval_assignment(assignment),
             _ => unimplemented!(), // I have decided not to implement the rest of the expressions,
                                    // I will focus on the compiler
         }
@@ -154,6 +228,7 @@ impl Evaluator {
     fn eval_primitive_expression(expression: Primitive) -> Object {
         match expression {
             Primitive::IntegerLiteral(x) => Object::INTEGER(x),
+            Primitive::FloatLiteral(x, _) => Object::FLOAT(x),
             Primitive::BooleanLiteral(x) => {
                 if x {
                     TRUE
@@ -165,11 +240,11 @@ impl Evaluator {
         }
     }
 
-    fn eval_prefix_expression(operator: &Token, right: &Object) -> Object {
+    fn eval_prefix_expression(operator: &Token, right: &Object) -> Result<Object, MonkeyError> {
         match operator {
-            Token::Bang => Self::eval_bang_operator_expression(right),
+            Token::Bang => Ok(Self::eval_bang_operator_expression(right)),
             Token::Minus => Self::eval_minus_operator_expression(right),
-            _ => Object::ERROR(format!("unknown operator: {operator}{right}")),
+            _ => Err(MonkeyError::unsupported_prefix(operator, right)),
         }
     }
 
@@ -181,81 +256,160 @@ impl Evaluator {
         }
     }
 
-    fn eval_minus_operator_expression(right: &Object) -> Object {
+    fn eval_minus_operator_expression(right: &Object) -> Result<Object, MonkeyError> {
         match right {
-            Object::INTEGER(x) => Object::INTEGER(-x),
-            _ => Object::ERROR(format!("unknown operator: -{right}")),
+            Object::INTEGER(x) => x
+                .checked_neg()
+                .map(Object::INTEGER)
+                .ok_or(MonkeyError::IntegerOverflow),
+            Object::FLOAT(x) => Ok(Object::FLOAT(-x)),
+            _ => Err(MonkeyError::unsupported_prefix(Token::Minus, right)),
         }
     }
 
-    fn eval_infix_expression(operator: &Token, left: Object, right: Object) -> Object {
+    fn eval_infix_expression(
+        operator: &Token,
+        left: Object,
+        right: Object,
+    ) -> Result<Object, MonkeyError> {
         match (left, right) {
             (Object::INTEGER(x), Object::INTEGER(y)) => {
                 Self::eval_integer_infix_expression(operator, x, y)
             }
+            (left, right) if left.as_float().is_some() && right.as_float().is_some() => {
+                Self::eval_float_infix_expression(operator, &left, &right)
+            }
             (Object::BOOLEAN(x), Object::BOOLEAN(y)) => {
                 Self::eval_boolean_infix_expression(operator, x, y)
             }
             (Object::STRING(x), Object::STRING(y)) => {
                 Self::eval_string_infix_expression(operator, x, &y)
             }
-            (left, right) => Object::ERROR(format!(
-                "type mismatch: {} {} {}",
-                left.get_type(),
-                operator,
-                right.get_type()
-            )),
+            (left, right) => Err(MonkeyError::unsupported_infix(&left, operator, &right)),
         }
     }
 
-    fn eval_integer_infix_expression(operator: &Token, left: i64, right: i64) -> Object {
-        match operator {
-            Token::Plus => Object::INTEGER(left + right),
-            Token::Minus => Object::INTEGER(left - right),
-            Token::Asterisk => Object::INTEGER(left * right),
-            Token::Slash => Object::INTEGER(left / right),
+    fn eval_integer_infix_expression(
+        operator: &Token,
+        left: i64,
+        right: i64,
+    ) -> Result<Object, MonkeyError> {
+        let result = match operator {
+            Token::Plus => left.checked_add(right),
+            Token::Minus => left.checked_sub(right),
+            Token::Asterisk => left.checked_mul(right),
+            Token::Slash | Token::Modulo if right == 0 => return Err(MonkeyError::DivisionByZero),
+            Token::Slash => left.checked_div(right),
+            // Only the quotient of `i64::MIN / -1` overflows, the remainder is 0
+            Token::Modulo => Some(left.wrapping_rem(right)),
+            _ => return Self::eval_integer_comparison(operator, left, right),
+        };
+        result
+            .map(Object::INTEGER)
+            .ok_or(MonkeyError::IntegerOverflow)
+    }
+
+    fn eval_integer_comparison(
+        operator: &Token,
+        left: i64,
+        right: i64,
+    ) -> Result<Object, MonkeyError> {
+        Ok(match operator {
             Token::LT => Object::BOOLEAN(left < right),
             Token::GT => Object::BOOLEAN(left > right),
             Token::LTE => Object::BOOLEAN(left <= right),
             Token::GTE => Object::BOOLEAN(left >= right),
             Token::Equal => Object::BOOLEAN(left == right),
             Token::NotEqual => Object::BOOLEAN(left != right),
-            _ => Object::ERROR(format!("unknown operator: INTEGER {operator} INTEGER")),
-        }
+            _ => {
+                return Err(MonkeyError::unsupported_infix(
+                    &Object::INTEGER(left),
+                    operator,
+                    &Object::INTEGER(right),
+                ))
+            }
+        })
+    }
+
+    /// An operation between two floats, or an integer and a float which is
+    /// then converted to a float for arithmetic.
+    fn eval_float_infix_expression(
+        operator: &Token,
+        left: &Object,
+        right: &Object,
+    ) -> Result<Object, MonkeyError> {
+        let (Some(x), Some(y)) = (left.as_float(), right.as_float()) else {
+            unreachable!("Both operands should be numbers")
+        };
+        // Comparisons are exact, the integer is not converted
+        let ordering = left.compare_numbers(right);
+        Ok(match operator {
+            Token::Plus => Object::FLOAT(x + y),
+            Token::Minus => Object::FLOAT(x - y),
+            Token::Asterisk => Object::FLOAT(x * y),
+            Token::Slash | Token::Modulo if y == 0.0 => return Err(MonkeyError::DivisionByZero),
+            Token::Slash => Object::FLOAT(x / y),
+            Token::Modulo => Object::FLOAT(x % y),
+            Token::LT => Object::BOOLEAN(ordering == Some(Ordering::Less)),
+            Token::GT => Object::BOOLEAN(ordering == Some(Ordering::Greater)),
+            Token::LTE => {
+                Object::BOOLEAN(matches!(ordering, Some(Ordering::Less | Ordering::Equal)))
+            }
+            Token::GTE => Object::BOOLEAN(matches!(
+                ordering,
+                Some(Ordering::Greater | Ordering::Equal)
+            )),
+            Token::Equal => Object::BOOLEAN(ordering == Some(Ordering::Equal)),
+            Token::NotEqual => Object::BOOLEAN(ordering != Some(Ordering::Equal)),
+            _ => return Err(MonkeyError::unsupported_infix(left, operator, right)),
+        })
     }
 
-    fn eval_boolean_infix_expression(operator: &Token, left: bool, right: bool) -> Object {
+    fn eval_boolean_infix_expression(
+        operator: &Token,
+        left: bool,
+        right: bool,
+    ) -> Result<Object, MonkeyError> {
         match operator {
-            Token::Equal => Object::BOOLEAN(left == right),
-            Token::NotEqual => Object::BOOLEAN(left != right),
-            Token::And => Object::BOOLEAN(left && right),
-            Token::Or => Object::BOOLEAN(left || right),
-            _ => Object::ERROR(format!("unknown operator: BOOLEAN {operator} BOOLEAN")),
+            Token::Equal => Ok(Object::BOOLEAN(left == right)),
+            Token::NotEqual => Ok(Object::BOOLEAN(left != right)),
+            Token::And => Ok(Object::BOOLEAN(left && right)),
+            Token::Or => Ok(Object::BOOLEAN(left || right)),
+            _ => Err(MonkeyError::unsupported_infix(
+                &Object::BOOLEAN(left),
+                operator,
+                &Object::BOOLEAN(right),
+            )),
         }
     }
 
-    fn eval_string_infix_expression(operator: &Token, mut left: String, right: &str) -> Object {
+    fn eval_string_infix_expression(
+        operator: &Token,
+        mut left: String,
+        right: &str,
+    ) -> Result<Object, MonkeyError> {
         match operator {
             Token::Plus => {
                 left.push_str(right);
-                Object::STRING(left)
+                Ok(Object::STRING(left))
             }
 
-            _ => Object::ERROR(format!("unknown operator: STRING {operator} STRING")),
+            _ => Err(MonkeyError::unsupported_infix(
+                &Object::STRING(left),
+                operator,
+                &Object::STRING(right.to_string()),
+            )),
         }
     }
 
-    fn eval_conditional_expression(&mut self, conditional: Conditional) -> Object {
-        let condition = self.eval_expression(*conditional.condition);
-        if Self::is_error(&condition) {
-            return condition;
-        }
+    fn eval_conditional_expression(&mut self, conditional: Conditional) -> Eval {
+        let condition = self.eval_expression(*conditional.condition)?;
         if Self::is_truthy(&condition) {
             self.eval_block_statemet(conditional.consequence)
         } else if let Some(alternative) = conditional.alternative {
             self.eval_block_statemet(alternative)
         } else {
-            NULL
+            Ok(NULL)
         }
     }
 
@@ -267,44 +421,47 @@ impl Evaluator {
         }
     }
 
-    fn is_error(object: &Object) -> bool {
-        matches!(object, Object::ERROR(_))
-    }
-
-    fn eval_identifier(&self, identifier: &Identifier) -> Object {
+    fn eval_identifier(&self, identifier: &Identifier) -> Result<Object, MonkeyError> {
         match self.env.borrow().get(&identifier.to_string()) {
-            Some(x) => x,
-            None => match BuiltinFunction::get_builtin(&identifier.to_string()) {
-                Some(x) => x,
-                None => Object::ERROR(format!("identifier not found: {identifier}")),
-            },
+            Some(x) => Ok(x),
+            None => self
+                .runtime
+                .get_builtin(&identifier.to_string())
+                .ok_or_else(|| MonkeyError::UndefinedVariable(identifier.to_string())),
         }
     }
 
-    fn eval_expressions(&mut self, expressions: Vec<Expression>) -> Vec<Object> {
+    fn eval_expressions(&mut self, expressions: Vec<Expression>) -> Result<Vec<Object>, Flow> {
         let mut result = vec![];
         for expression in expressions {
-            let evaluated = self.eval_expression(expression);
-            if Self::is_error(&evaluated) {
-                return vec![evaluated];
-            }
-            result.push(evaluated);
+            result.push(self.eval_expression(expression)?);
         }
-        result
+        Ok(result)
     }
 
-    fn apply_function(&mut self, function: Object, args: Vec<Object>) -> Object {
+    fn apply_function(
+        &mut self,
+        function: Object,
+        args: Vec<Object>,
+    ) -> Result<Object, MonkeyError> {
         match function {
             Object::FUNCTION(function) => {
+                if function.parameters.len() != args.len() {
+                    return Err(MonkeyError::ArityError {
+                        expected: function.parameters.len(),
+                        got: args.len(),
+                    });
+                }
                 let extended_env = Self::extend_function_env(&function, args);
                 let env = Rc::clone(&self.env);
                 self.env = Rc::new(RefCell::new(extended_env));
                 let evaluated = self.eval_block_statemet(function.body);
                 self.env = env;
-                evaluated
+                evaluated.or_else(Flow::into_result)
             }
             Object::BUILTIN(function) => function.call(args),
-            _ => Object::ERROR(format!("not a function: {function}")),
+            Object::NATIVE(function) => function.call(&args),
+            _ => Err(MonkeyError::not_a_function(&function)),
         }
     }
 
@@ -316,58 +473,77 @@ impl Evaluator {
         env
     }
 
-    fn eval_index_expression(&mut self, index_expression: IndexExpression) -> Object {
-        let left = self.eval_expression(*index_expression.left);
-        if Self::is_error(&left) {
-            return left;
-        }
-        let index = self.eval_expression(*index_expression.index);
-        if Self::is_error(&index) {
-            return index;
-        }
-        match (&left, &index) {
+    fn eval_index_expression(&mut self, index_expression: IndexExpression) -> Eval {
+        let left = self.eval_expression(*index_expression.left)?;
+        let index = self.eval_expression(*index_expression.index)?;
+        Ok(Self::index(&left, &index)?)
+    }
+
+    fn index(left: &Object, index: &Object) -> Result<Object, MonkeyError> {
+        match (left, index) {
             (Object::ARRAY(x), Object::INTEGER(y)) => {
                 if *y < 0 || *y >= x.len() as i64 {
-                    return NULL;
+                    return Ok(NULL);
                 }
                 let index = usize::try_from(*y).unwrap();
-                x[index].clone()
+                Ok(x[index].clone())
             }
             (Object::HASHMAP(x), _) => {
                 if !index.is_hashable() {
-                    return Object::ERROR(format!("unusable as hash key: {}", index.get_type()));
+                    return Err(MonkeyError::unusable_key(index));
                 }
-                match x.get(&index) {
-                    Some(x) => x.clone(),
-                    None => NULL,
+                match x.get(index) {
+                    Some(x) => Ok(x.clone()),
+                    None => Ok(NULL),
                 }
             }
 
-            _ => Object::ERROR(format!(
-                "index operator not supported: {}[{}]",
-                left.get_type(),
-                index.get_type()
-            )),
+            _ => Err(MonkeyError::unsupported_index(left, index)),
         }
     }
 
-    fn eval_hashmap_literal(&mut self, hashmap_pairs: HashMapLiteral) -> Object {
+    /// Evaluates the indices of the target, then the value, and stores the
+    /// new value in the environment that defines the variable.
+    fn eval_assignment(&mut self, assignment: Assignment) -> Eval {
+        let operator = assignment.operator();
+        let value = *assignment.value;
+        let (name, indices) = Assignment::split_target(*assignment.target);
+        let root = self
+            .env
+            .borrow()
+            .get(&name.value)
+            .ok_or_else(|| MonkeyError::UndefinedVariable(name.value.clone()))?;
+        let indices = self.eval_expressions(indices)?;
+
+        // The old value is read before the right side runs, like the VM does
+        let value = match operator {
+            Some(operator) => {
+                let mut old = root.clone();
+                for index in &indices {
+                    old = Self::index(&old, index)?;
+                }
+                let value = self.eval_expression(value)?;
+                Self::eval_infix_expression(&operator, old, value)?
+            }
+            None => self.eval_expression(value)?,
+        };
+
+        let root = root.with_element(&indices, value.clone())?;
+        self.env.borrow_mut().assign(&name.value, root);
+        Ok(value)
+    }
+
+    fn eval_hashmap_literal(&mut self, hashmap_pairs: HashMapLiteral) -> Eval {
         let mut hashmap = HashMap::new();
         for (key, value) in hashmap_pairs.pairs {
-            let key = self.eval_expression(key);
-            if Self::is_error(&key) {
-                return key;
-            }
+            let key = self.eval_expression(key)?;
             if !key.is_hashable() {
-                return Object::ERROR(format!("unusable as hash key: {}", key.get_type()));
+                return Err(MonkeyError::unusable_key(&key).into());
             }
 
-            let value = self.eval_expression(value);
-            if Self::is_error(&value) {
-                return value;
-            }
+            let value = self.eval_expression(value)?;
             hashmap.insert(key, value);
         }
-        Object::HASHMAP(hashmap)
+        Ok(Object::HASHMAP(hashmap))
     }
 }
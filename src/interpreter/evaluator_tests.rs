@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod tests {
 
-    use crate::{interpreter::evaluator::Evaluator, lexer::Lexer, object::Object, parser::Parser};
+    use crate::{
+        differential, errors::MonkeyError, interpreter::evaluator::Evaluator, lexer::Lexer,
+        object::Object, parser::Parser,
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -30,6 +33,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_integer_overflow() {
+        let tests = vec![
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4611686018427387904 * 2",
+            "-9223372036854775808 / -1",
+            "let min = -9223372036854775808; -min",
+        ];
+
+        for input in tests {
+            assert!(
+                matches!(test_eval_error(input), MonkeyError::IntegerOverflow),
+                "{input} did not overflow"
+            );
+        }
+
+        test_integer_object(test_eval("9223372036854775806 + 1"), i64::MAX);
+        test_integer_object(test_eval("-9223372036854775807 - 1"), i64::MIN);
+        test_integer_object(test_eval("-9223372036854775808"), i64::MIN);
+        test_integer_object(test_eval("-9223372036854775808 % -1"), 0);
+        test_integer_object(
+            test_eval(
+                r#"try { 9223372036854775807 * 2; } catch (e) { let x = e; } {"IntegerOverflow": 1}[x["kind"]]"#,
+            ),
+            1,
+        );
+    }
+
+    #[test]
+    fn test_eval_float_expression() {
+        let tests = vec![
+            ("3.5", 3.5),
+            ("-2.25", -2.25),
+            ("1e-3 * 1000", 1.0),
+            ("1 / 4.0", 0.25),
+            ("7.5 % 2", 1.5),
+            ("2 + 0.5 * 3", 3.5),
+            ("float(3) / 2", 1.5),
+            (r#"float("2.5") - 1"#, 1.5),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::FLOAT(x) => assert!((x - expected).abs() < f64::EPSILON, "{input}"),
+                x => panic!("{input} did not return a float, it returned {x:#?}"),
+            }
+        }
+
+        let tests = vec![
+            ("1 == 1.0", true),
+            ("1.5 > 1", true),
+            ("2 <= 1.99", false),
+            ("0.1 + 0.2 != 0.3", true),
+            ("9007199254740993 == 9007199254740992.0", false),
+            ("9223372036854775807 < 9223372036854775808.0", true),
+        ];
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+
+        let tests = vec![
+            ("int(3.99)", 3),
+            ("int(-3.99)", -3),
+            (r#"int("42")"#, 42),
+            ("int(7)", 7),
+        ];
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+
+        test_error("1.5 / 0", "division by zero");
+        test_error("1 % 0.0", "division by zero");
+        test_error("1.5 + true", "type mismatch: FLOAT + BOOLEAN");
+        test_error("int(1e300)", "cannot convert 1e300 to INTEGER");
+        test_error(r#"float("pi")"#, "cannot convert \"pi\" to FLOAT");
+        test_error("{1.5: 1}", "unusable as hash key: FLOAT");
+    }
+
     #[test]
     fn test_eval_boolean_expression() {
         let tests = vec![
@@ -137,7 +219,7 @@ mod tests {
         let tests = vec![
             ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
             ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
-            ("-true", "unknown operator: -true"),
+            ("-true", "unknown operator: -BOOLEAN"),
             ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
             ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
             (
@@ -160,14 +242,58 @@ mod tests {
                 r#"{"name": "Monkey"}[fn(x) { x }];"#,
                 "unusable as hash key: FUNCTION",
             ),
+            ("{}[[1]]", "unusable as hash key: ARRAY"),
+            ("5[0]", "index operator not supported: INTEGER[INTEGER]"),
+            ("1()", "not a function: INTEGER"),
+            ("1 < true", "type mismatch: INTEGER < BOOLEAN"),
+            ("true >= false", "unknown operator: BOOLEAN >= BOOLEAN"),
+            (r#""a" == "a""#, "unknown operator: STRING == STRING"),
+            ("1.5 && 2", "type mismatch: FLOAT && INTEGER"),
         ];
 
         for (input, expected) in tests {
-            let evaluated = test_eval(input);
-            test_error_object(evaluated, expected.to_string());
+            test_error(input, expected);
         }
     }
 
+    #[test]
+    fn test_error_kinds() {
+        assert!(matches!(
+            test_eval_error("5 + true"),
+            MonkeyError::TypeError(_)
+        ));
+        assert!(matches!(
+            test_eval_error("1[0]"),
+            MonkeyError::IndexError(_)
+        ));
+        assert!(matches!(
+            test_eval_error("fn(x) { x }(1, 2)"),
+            MonkeyError::ArityError {
+                expected: 1,
+                got: 2
+            }
+        ));
+        assert!(matches!(
+            test_eval_error("let f = fn(x) { x % 0 }; f(1)"),
+            MonkeyError::DivisionByZero
+        ));
+        assert!(matches!(
+            test_eval_error("foobar"),
+            MonkeyError::UndefinedVariable(name) if name == "foobar"
+        ));
+        assert!(matches!(
+            test_eval_error("first(1)"),
+            MonkeyError::TypeError(_)
+        ));
+        assert!(matches!(
+            test_eval_error("last([1, 2, 3], [4, 5, 6])"),
+            MonkeyError::ArityError {
+                expected: 1,
+                got: 2
+            }
+        ));
+    }
+
     #[test]
     fn test_let_stateemtns() {
         let tests = vec![
@@ -187,6 +313,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_assignments() {
+        let tests = vec![
+            ("let a = 5; a = 6; a;", 6),
+            ("let a = 5; a = a * 2;", 10),
+            ("let a = 1; let b = 2; a = b = 3; a + b;", 6),
+            ("let a = 10; a += 5; a -= 3; a *= 2; a /= 4; a %= 4; a;", 2),
+            ("let a = 1; let f = fn() { a += 1; }; f(); f(); a;", 3),
+            (
+                "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); c(); c();",
+                2,
+            ),
+            ("let i = 0; while (i < 3) { i += 1; } i;", 3),
+            ("let a = [1, [2, 3]]; a[1][0] += 5; a[1][0];", 7),
+            (r#"let h = {"a": 1}; h["a"] *= 3; h["b"] = 1; h["a"] + h["b"];"#, 4),
+            ("let a = [1]; let b = a; b[0] = 2; a[0];", 1),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+
+        test_error("b = 1;", "identifier not found: b");
+        test_error(
+            "let a = [1]; a[1] = 2;",
+            "index 1 out of bounds for an array of length 1",
+        );
+        test_error(
+            r#"let a = "x"; a[0] = 1;"#,
+            "index assignment not supported: STRING[INTEGER]",
+        );
+    }
+
     #[test]
     fn test_function_object() {
         let input = "fn(x) { x + 2; };";
@@ -244,6 +403,15 @@ mod tests {
         test_string_object(evaluated, "Hello World!".to_string());
     }
 
+    #[test]
+    fn test_string_literal_escapes() {
+        let input = r#""\"Hello\"\n\tWorld\u{21}\\""#;
+
+        let evaluated = test_eval(input);
+
+        test_string_object(evaluated, "\"Hello\"\n\tWorld!\\".to_string());
+    }
+
     #[test]
     fn test_string_concatenationm() {
         let input = "\"Hello\" + \" \" + \"World!\"";
@@ -273,12 +441,92 @@ mod tests {
             (r"len(1)", "argument to `len` not supported, got INTEGER"),
             (
                 r#"len("one", "two")"#,
-                "wrong number of arguments. got=2, want=1",
+                "wrong number of arguments: want=1, got=2",
             ),
         ];
 
         for (input, expected) in tests_striung {
-            test_error_object(test_eval(input), expected.to_string());
+            test_error(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_builtin_functions() {
+        let tests = vec![
+            (
+                r#"keys({"b": 1, "a": 2, 10: 3, 2: 4, true: 5, false: 6})"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(2),
+                    Object::INTEGER(10),
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(true),
+                    Object::STRING("a".to_string()),
+                    Object::STRING("b".to_string()),
+                ]),
+            ),
+            (
+                r#"values({"b": 1, "a": 2, 10: 3, 2: 4, true: 5, false: 6})"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(4),
+                    Object::INTEGER(3),
+                    Object::INTEGER(6),
+                    Object::INTEGER(5),
+                    Object::INTEGER(2),
+                    Object::INTEGER(1),
+                ]),
+            ),
+            (r"keys({})", Object::ARRAY(vec![])),
+            (r"values({})", Object::ARRAY(vec![])),
+            (r#"len({"a": 1, "b": 2})"#, Object::INTEGER(2)),
+            (r"len({})", Object::INTEGER(0)),
+            (
+                r#"let h = {"a": 1}; let g = add(h, "b", 2); [len(h), len(g), g["b"]]"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(1),
+                    Object::INTEGER(2),
+                    Object::INTEGER(2),
+                ]),
+            ),
+            (r#"add({"a": 1}, "a", 5)["a"]"#, Object::INTEGER(5)),
+            (
+                r#"let h = {"a": 1, "b": 2}; let g = delete(h, "a"); [len(h), len(g), g["a"], g["b"]]"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(2),
+                    Object::INTEGER(1),
+                    Object::NULL,
+                    Object::INTEGER(2),
+                ]),
+            ),
+            (r#"len(delete({"a": 1}, "z"))"#, Object::INTEGER(1)),
+            (r#"contains({"a": 1}, "a")"#, Object::BOOLEAN(true)),
+            (r#"contains({"a": 1}, "b")"#, Object::BOOLEAN(false)),
+            (r"contains({1: 1}, true)", Object::BOOLEAN(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                r"keys([1])",
+                "argument to `keys` not supported, must be HASHMAP, got ARRAY",
+            ),
+            (
+                r"values(1)",
+                "argument to `values` not supported, must be HASHMAP, got INTEGER",
+            ),
+            (r"add({}, 1)", "wrong number of arguments: want=3, got=2"),
+            (r"add({}, [1], 1)", "unusable as hash key: ARRAY"),
+            (
+                r#"delete("a", "a")"#,
+                "argument to `delete` not supported, must be HASHMAP, got STRING",
+            ),
+            (r"contains({}, [])", "unusable as hash key: ARRAY"),
+        ];
+
+        for (input, expected) in errors {
+            test_error(input, expected);
         }
     }
 
@@ -334,8 +582,6 @@ mod tests {
             ("first([1, 2, 3])", Some(1)),
             ("first([1])", Some(1)),
             ("first([])", None),
-            ("first(1)", None),
-            ("first([1, 2, 3], [4, 5, 6])", None),
         ];
 
         for (input, expected) in tests {
@@ -353,8 +599,6 @@ mod tests {
             ("last([1, 2, 3])", Some(3)),
             ("last([1])", Some(1)),
             ("last([])", None),
-            ("last(1)", None),
-            ("last([1, 2, 3], [4, 5, 6])", None),
         ];
 
         for (input, expected) in tests {
@@ -372,8 +616,6 @@ mod tests {
             ("rest([1, 2, 3])", Some(vec![2, 3])),
             ("rest([1])", Some(Vec::new())),
             ("rest([])", None),
-            ("rest(1)", None),
-            ("rest([1, 2, 3], [4, 5, 6])", None),
         ];
 
         for (input, expected) in tests {
@@ -386,25 +628,24 @@ mod tests {
                 None => test_null_object(test_eval(input)),
             }
         }
+        test_eval_error("rest(1)");
+        test_eval_error("rest([1, 2, 3], [4, 5, 6])");
     }
 
     #[test]
     fn test_push_function() {
         let tests = vec![
-            ("push([], 1)", Some(vec![1])),
-            ("push([1], 2)", Some(vec![1, 2])),
-            ("push([1,2], 3)", Some(vec![1, 2, 3])),
-            ("push(1, 1)", None),
-            ("push([1,2], 3, 4)", None),
+            ("push([], 1)", vec![1]),
+            ("push([1], 2)", vec![1, 2]),
+            ("push([1,2], 3)", vec![1, 2, 3]),
         ];
 
         for (input, expected) in tests {
             println!("{input}");
-            match expected {
-                Some(x) => test_array_object(test_eval(input), x),
-                None => test_null_object(test_eval(input)),
-            }
+            test_array_object(test_eval(input), expected);
         }
+        test_eval_error("push(1, 1)");
+        test_eval_error("push([1,2], 3, 4)");
     }
 
     #[test]
@@ -509,7 +750,129 @@ mod tests {
         }
     }
 
-    fn test_eval(input: &str) -> Object {
+    #[test]
+    fn test_break_and_continue() {
+        let tests = vec![
+            (
+                "let a = 0; while (true) { let a = a + 1; if (a == 5) { break; } }; a",
+                5,
+            ),
+            (
+                "let a = 0; let c = 0; while (a < 10) { let a = a + 1; if (a > 5) { continue; } let c = c + a; }; c",
+                15,
+            ),
+            (
+                "let a = 0; let c = 0; while (a < 3) { let a = a + 1; let b = 0; while (true) { let b = b + 1; if (b > 4) { break; } let c = c + 1; } }; c",
+                12,
+            ),
+            (
+                "let f = fn(x) { while (true) { if (x > 3) { return x; } let x = x + 1; } }; f(0) + 1",
+                5,
+            ),
+            (
+                "let a = 0; while (a < 5) { let f = fn() { while (true) { break; } 10 }; let a = a + f(); }; a",
+                10,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            println!("{input}");
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_return_from_function_calls() {
+        let tests = vec![
+            ("let f = fn() { return 1; }; f() + 1", 2),
+            (
+                "let f = fn() { return 1; 2 }; let g = fn() { f() + 10 }; g()",
+                11,
+            ),
+            (
+                "let f = fn(x) { if (x) { return 1; } 2 }; f(true) * 10 + f(false)",
+                12,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            println!("{input}");
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_loop_statements_outside_of_loops() {
+        let tests = vec![
+            ("break; 1", "break outside of a loop"),
+            ("if (true) { continue; }", "continue outside of a loop"),
+            (
+                "while (true) { let f = fn() { break; }; f(); }",
+                "break outside of a loop",
+            ),
+            (
+                "let f = fn() { continue; }; while (true) { f() }",
+                "continue outside of a loop",
+            ),
+            ("while (1 + true) { 1 }", "type mismatch: INTEGER + BOOLEAN"),
+            // Found before the program runs, even in a function that is
+            // never called
+            ("let f = fn() { break; }; 1", "break outside of a loop"),
+            ("if (false) { continue; } 1", "continue outside of a loop"),
+            (
+                "let f = fn() { while (true) { fn() { continue; }; break; } }; 1",
+                "continue outside of a loop",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            println!("{input}");
+            test_error(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let tests = vec![
+            ("try { throw 5; } catch (e) { let x = e * 2; } x", 10),
+            ("let x = 1; try { let x = 2; } catch (e) { let x = 3; } x", 2),
+            (
+                r#"try { len(1); } catch (e) { let x = e; } {"TypeError": 1}[x["kind"]]"#,
+                1,
+            ),
+            (
+                "let f = fn(n) { if (n == 0) { throw 0; } f(n - 1) }; try { f(5); } catch (e) { let x = e + 7; } x",
+                7,
+            ),
+            (
+                "let a = 0; while (a < 10) { let a = a + 1; try { if (a == 3) { break; } } catch (e) {} }; a",
+                3,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            println!("{input}");
+            test_integer_object(test_eval(input), expected);
+        }
+
+        test_null_object(test_eval("try { 1 } catch (e) { 2 }"));
+        test_error("throw 1 + 1;", "uncaught exception: 2");
+        test_error(
+            "try { throw 1; } catch (e) { first(e); }",
+            "argument to `first` not supported, must be ARRAY, got INTEGER",
+        );
+        // Only errors of the program itself can be caught
+        test_error(
+            "try { foobar; } catch (e) { 1 }",
+            "identifier not found: foobar",
+        );
+        test_error("try { break; } catch (e) { 1 }", "break outside of a loop");
+    }
+
+    fn run(input: &str) -> Result<Object, MonkeyError> {
+        if let Err(mismatch) = differential::check(input) {
+            panic!("{mismatch}");
+        }
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         let program = parser.parse_program();
@@ -517,6 +880,24 @@ mod tests {
         evaluator.eval(program)
     }
 
+    fn test_eval(input: &str) -> Object {
+        match run(input) {
+            Ok(x) => x,
+            Err(err) => panic!("{input} failed: {err}"),
+        }
+    }
+
+    fn test_eval_error(input: &str) -> MonkeyError {
+        match run(input) {
+            Ok(x) => panic!("{input} did not fail, it returned {x}"),
+            Err(err) => err,
+        }
+    }
+
+    fn test_error(input: &str, expected: &str) {
+        assert_eq!(test_eval_error(input).to_string(), expected, "{input}");
+    }
+
     fn test_integer_object(object: Object, expected: i64) {
         match object {
             Object::INTEGER(x) => assert_eq!(x, expected),
@@ -533,19 +914,12 @@ mod tests {
 
     fn test_null_object(object: Object) {
         match object {
-            Object::NULL | Object::ERROR(_) => (),
+            Object::NULL => (),
 
             _ => panic!("The object is not null"),
         }
     }
 
-    fn test_error_object(object: Object, expected: String) {
-        match object {
-            Object::ERROR(x) => assert_eq!(x, expected),
-            _ => panic!("The object is not an  error"),
-        }
-    }
-
     fn test_string_object(object: Object, expected: String) {
         match object {
             Object::STRING(s) => assert_eq!(format!("{s}"), expected),
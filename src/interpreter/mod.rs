@@ -1,2 +1,3 @@
+mod budget_tests;
 pub mod evaluator;
 mod evaluator_tests;
@@ -0,0 +1,55 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::lexer::token::Span;
+
+/// An error found while splitting the input into tokens.
+///
+/// The lexer does not stop on them: it records the error, produces the most
+/// sensible token it can and carries on, the parser then reports them with its
+/// own diagnostics.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    /// A string literal still open at the end of the input.
+    UnterminatedString { span: Span },
+    /// A `/*` comment never closed by `*/`.
+    UnterminatedComment { span: Span },
+    /// A `\` in a string followed by something that is not a known escape.
+    InvalidEscape { escape: String, span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnterminatedString { span }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidEscape { span, .. } => *span,
+        }
+    }
+
+    /// The piece of source the error points at.
+    pub fn fragment(&self) -> String {
+        match self {
+            LexError::UnterminatedString { .. } => String::from("\""),
+            LexError::UnterminatedComment { .. } => String::from("/*"),
+            LexError::InvalidEscape { escape, .. } => escape.clone(),
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string"),
+            LexError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            LexError::InvalidEscape { escape, .. } => {
+                write!(f, "invalid escape sequence `{escape}`")
+            }
+        }
+    }
+}
+
+impl Error for LexError {}
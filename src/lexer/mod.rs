@@ -1,11 +1,23 @@
+pub mod lexer_errors;
 pub mod token;
-use crate::lexer::token::Token;
+use crate::lexer::{
+    lexer_errors::LexError,
+    token::{Comment, Span, SpannedToken, Token},
+};
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,      // current position in input (points to current char)
     read_position: usize, // current reading position in input (after current char)
     ch: char,             // current char under examination
+
+    offset: usize, // byte offset of the current char
+    line: usize,   // line of the current char
+    column: usize, // column of the current char
+
+    comments: Vec<Comment>, // comments skipped so far
+    last_token_line: usize, // line where the last token ended, 0 before the first one
+    errors: Vec<LexError>,  // errors found so far
 }
 
 impl Lexer {
@@ -15,6 +27,12 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: '\0',
+            offset: 0,
+            line: 1,
+            column: 1,
+            comments: Vec::new(),
+            last_token_line: 0,
+            errors: Vec::new(),
         };
 
         lexer.read_char();
@@ -22,6 +40,16 @@ impl Lexer {
     }
 
     fn read_char(&mut self) {
+        if self.read_position > 0 && self.position < self.input.len() {
+            self.offset += self.ch.len_utf8();
+            if self.ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = '\0';
         } else {
@@ -32,7 +60,28 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        self.next_spanned_token().token
+    }
+
+    pub fn next_spanned_token(&mut self) -> SpannedToken {
+        self.skip_whitespace_and_comments();
+        let span = self.current_span();
+        let token = self.read_token();
+        self.last_token_line = self.line;
+        SpannedToken { token, span }
+    }
+
+    /// Returns the comments skipped so far, in source order.
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Returns the errors found since the last call, in source order.
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn read_token(&mut self) -> Token {
         let token = match self.ch {
             '=' => {
                 if self.peek_char() == '=' {
@@ -42,8 +91,22 @@ impl Lexer {
                     Token::Assign
                 }
             }
-            '+' => Token::Plus,
-            '-' => Token::Minus,
+            '+' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::PlusAssign
+                } else {
+                    Token::Plus
+                }
+            }
+            '-' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::MinusAssign
+                } else {
+                    Token::Minus
+                }
+            }
             '!' => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -52,8 +115,22 @@ impl Lexer {
                     Token::Bang
                 }
             }
-            '/' => Token::Slash,
-            '*' => Token::Asterisk,
+            '/' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::SlashAssign
+                } else {
+                    Token::Slash
+                }
+            }
+            '*' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::AsteriskAssign
+                } else {
+                    Token::Asterisk
+                }
+            }
             '<' => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -86,7 +163,14 @@ impl Lexer {
                     Token::Illegal("|".to_string())
                 }
             }
-            '%' => Token::Modulo,
+            '%' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::ModuloAssign
+                } else {
+                    Token::Modulo
+                }
+            }
             ';' => Token::Semicolon,
             '(' => Token::LParen,
             ')' => Token::RParen,
@@ -114,19 +198,72 @@ impl Lexer {
                     "while" => Token::While,
                     "break" => Token::Break,
                     "continue" => Token::Continue,
+                    "try" => Token::Try,
+                    "catch" => Token::Catch,
+                    "throw" => Token::Throw,
                     _ => Token::Ident(ident_string),
                 };
             }
-            '0'..='9' => return Token::Int(self.read_number()),
+            '0'..='9' => return self.read_number(),
             s => Token::Illegal(s.to_string()),
         };
         self.read_char();
         token
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch.is_whitespace() {
+    fn current_span(&self) -> Span {
+        Span::new(self.offset, self.line, self.column)
+    }
+
+    /// Skips whitespace and comments up to the next token.
+    ///
+    /// A block comment that is never closed consumes the rest of the input.
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            while self.ch.is_whitespace() {
+                self.read_char();
+            }
+            if self.ch != '/' || !matches!(self.peek_char(), '/' | '*') {
+                return;
+            }
+
+            let span = self.current_span();
+            let position = self.position;
+            let terminated = if self.peek_char() == '/' {
+                while self.ch != '\n' && self.ch != '\0' {
+                    self.read_char();
+                }
+                true
+            } else {
+                self.read_block_comment()
+            };
+            if !terminated {
+                self.errors.push(LexError::UnterminatedComment { span });
+                return;
+            }
+
+            self.comments.push(Comment {
+                text: self.input[position..self.position].iter().collect(),
+                span,
+                trailing: span.line == self.last_token_line,
+            });
+        }
+    }
+
+    /// Reads a `/* */` comment, returns false if the input ends before `*/`.
+    fn read_block_comment(&mut self) -> bool {
+        self.read_char();
+        loop {
             self.read_char();
+            match self.ch {
+                '*' if self.peek_char() == '/' => {
+                    self.read_char();
+                    self.read_char();
+                    return true;
+                }
+                '\0' => return false,
+                _ => {}
+            }
         }
     }
 
@@ -146,24 +283,141 @@ impl Lexer {
         self.input[position..self.position].iter().collect()
     }
 
-    fn read_number(&mut self) -> String {
+    /// Reads an integer, or a float if it has a fractional part or an
+    /// exponent, like `3.14` or `1e-9`.
+    fn read_number(&mut self) -> Token {
         let position = self.position;
+        self.read_digits();
+
+        let mut float = false;
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            float = true;
+            self.read_char();
+            self.read_digits();
+        }
+        let exponent = match self.peek_char() {
+            '+' | '-' => self
+                .input
+                .get(self.read_position + 1)
+                .is_some_and(char::is_ascii_digit),
+            c => c.is_ascii_digit(),
+        };
+        if matches!(self.ch, 'e' | 'E') && exponent {
+            float = true;
+            self.read_char();
+            if matches!(self.ch, '+' | '-') {
+                self.read_char();
+            }
+            self.read_digits();
+        }
+
+        let number = self.input[position..self.position].iter().collect();
+        if float {
+            Token::Float(number)
+        } else {
+            Token::Int(number)
+        }
+    }
+
+    fn read_digits(&mut self) {
         while self.ch.is_numeric() {
             self.read_char();
         }
-        self.input[position..self.position].iter().collect()
     }
 
     fn read_string(&mut self) -> String {
-        let position = self.position + 1;
+        let span = self.current_span();
+        let mut string = String::new();
         loop {
             self.read_char();
-            if self.ch == '"' || self.ch == '\0' {
-                break; // TODO: handle unterminated string
+            match self.ch {
+                '"' => break,
+                '\0' => {
+                    self.errors.push(LexError::UnterminatedString { span });
+                    break;
+                }
+                '\\' => string.push_str(&self.read_escape()),
+                c => string.push(c),
             }
         }
-        self.input[position..self.position].iter().collect()
+        string
+    }
+
+    /// Reads the escape sequence starting at the current `\`.
+    ///
+    /// An invalid sequence is reported and kept as written.
+    fn read_escape(&mut self) -> String {
+        let span = self.current_span();
+        let escaped = match self.peek_char() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'u' => None,
+            // Let `read_string` report the unterminated string
+            '\0' => return String::new(),
+            _ => {
+                self.read_char();
+                let escape = format!("\\{}", self.ch);
+                self.errors.push(LexError::InvalidEscape {
+                    escape: escape.clone(),
+                    span,
+                });
+                return escape;
+            }
+        };
+        self.read_char();
+        if let Some(escaped) = escaped {
+            return escaped.to_string();
+        }
+
+        let mut escape = String::from("\\u");
+        if self.peek_char() == '{' {
+            self.read_char();
+            escape.push('{');
+            while self.peek_char().is_ascii_hexdigit() {
+                self.read_char();
+                escape.push(self.ch);
+            }
+            if self.peek_char() == '}' {
+                self.read_char();
+                escape.push('}');
+
+                let digits = &escape[3..escape.len() - 1];
+                let unicode = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() <= 6)
+                    .and_then(char::from_u32);
+                if let Some(unicode) = unicode {
+                    return unicode.to_string();
+                }
+            }
+        }
+        self.errors.push(LexError::InvalidEscape {
+            escape: escape.clone(),
+            span,
+        });
+        escape
+    }
+}
+
+/// Writes `string` back as the body of a string literal, escaping what the
+/// lexer would otherwise misread.
+pub fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", u32::from(c))),
+            c => escaped.push(c),
+        }
     }
+    escaped
 }
 
 #[cfg(test)]
@@ -206,7 +460,7 @@ mod tests {
 
             let result = add(five, ten);
 
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
 
             if (5 < 10) {
@@ -232,6 +486,8 @@ mod tests {
             break;
             continue;
             42%13==20;
+
+            try { throw 1; } catch (e) {}
         "#;
 
         let mut lexer = Lexer::new(input);
@@ -370,6 +626,19 @@ mod tests {
             Token::Int(String::from("20")),
             Token::Semicolon,
             //
+            Token::Try,
+            Token::LSquirly,
+            Token::Throw,
+            Token::Int(String::from("1")),
+            Token::Semicolon,
+            Token::RSquirly,
+            Token::Catch,
+            Token::LParen,
+            Token::Ident(String::from("e")),
+            Token::RParen,
+            Token::LSquirly,
+            Token::RSquirly,
+            //
             Token::Eof,
         ];
 
@@ -379,4 +648,251 @@ mod tests {
             assert_eq!(token, expected_token);
         }
     }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let x = 5;\nlet héllo = \"ü\" + x;";
+
+        let expected = vec![
+            (Token::Let, 0, 1, 1),
+            (Token::Ident(String::from("x")), 4, 1, 5),
+            (Token::Assign, 6, 1, 7),
+            (Token::Int(String::from("5")), 8, 1, 9),
+            (Token::Semicolon, 9, 1, 10),
+            (Token::Let, 11, 2, 1),
+            (Token::Ident(String::from("héllo")), 15, 2, 5),
+            (Token::Assign, 22, 2, 11),
+            (Token::String(String::from("ü")), 24, 2, 13),
+            (Token::Plus, 29, 2, 17),
+            (Token::Ident(String::from("x")), 31, 2, 19),
+            (Token::Semicolon, 32, 2, 20),
+            (Token::Eof, 33, 2, 21),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (expected_token, offset, line, column) in expected {
+            let SpannedToken { token, span } = lexer.next_spanned_token();
+
+            assert_eq!(token, expected_token);
+            assert_eq!(
+                (span.offset, span.line, span.column),
+                (offset, line, column),
+                "wrong span for {token}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let input = r"// header
+let x = 10 / 2; // half
+/* a block
+   comment */ let y = x /* inline */ * 2;
+x // last";
+
+        let expected = vec![
+            Token::Let,
+            Token::Ident(String::from("x")),
+            Token::Assign,
+            Token::Int(String::from("10")),
+            Token::Slash,
+            Token::Int(String::from("2")),
+            Token::Semicolon,
+            Token::Let,
+            Token::Ident(String::from("y")),
+            Token::Assign,
+            Token::Ident(String::from("x")),
+            Token::Asterisk,
+            Token::Int(String::from("2")),
+            Token::Semicolon,
+            Token::Ident(String::from("x")),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+
+        let comments = lexer
+            .take_comments()
+            .into_iter()
+            .map(|c| (c.text, c.span.line, c.span.column, c.trailing))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            vec![
+                (String::from("// header"), 1, 1, false),
+                (String::from("// half"), 2, 17, true),
+                (String::from("/* a block\n   comment */"), 3, 1, false),
+                (String::from("/* inline */"), 4, 25, true),
+                (String::from("// last"), 5, 3, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut lexer = Lexer::new("let x = 1; /* never closed\nlet y = 2;");
+        for _ in 0..5 {
+            lexer.next_token();
+        }
+        assert!(lexer.take_errors().is_empty());
+
+        assert_eq!(lexer.next_token(), Token::Eof);
+        let errors = lexer.take_errors();
+        assert_eq!(
+            errors,
+            vec![LexError::UnterminatedComment {
+                span: Span::new(11, 1, 12)
+            }]
+        );
+        assert_eq!((errors[0].span().line, errors[0].span().column), (1, 12));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let input = r#""a\nb" "\t\"quoted\"\\" "\u{48}\u{e9}\u{1F600}" "" "\r""#;
+
+        let expected = vec![
+            Token::String(String::from("a\nb")),
+            Token::String(String::from("\t\"quoted\"\\")),
+            Token::String(String::from("Hé😀")),
+            Token::String(String::new()),
+            Token::String(String::from("\r")),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+        assert!(lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let input = "3 3.14 1e-9 2.5E+3 7e 1.x";
+
+        let expected = vec![
+            Token::Int(String::from("3")),
+            Token::Float(String::from("3.14")),
+            Token::Float(String::from("1e-9")),
+            Token::Float(String::from("2.5E+3")),
+            Token::Int(String::from("7")),
+            Token::Ident(String::from("e")),
+            Token::Int(String::from("1")),
+            Token::Illegal(String::from(".")),
+            Token::Ident(String::from("x")),
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn test_assignment_operators() {
+        let input = "a = b += c -= d *= e /= f %= g + = / == -";
+
+        let expected = vec![
+            Token::Ident(String::from("a")),
+            Token::Assign,
+            Token::Ident(String::from("b")),
+            Token::PlusAssign,
+            Token::Ident(String::from("c")),
+            Token::MinusAssign,
+            Token::Ident(String::from("d")),
+            Token::AsteriskAssign,
+            Token::Ident(String::from("e")),
+            Token::SlashAssign,
+            Token::Ident(String::from("f")),
+            Token::ModuloAssign,
+            Token::Ident(String::from("g")),
+            Token::Plus,
+            Token::Assign,
+            Token::Slash,
+            Token::Equal,
+            Token::Minus,
+            Token::Eof,
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+        }
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let input = r#""\q" "\u{110000}" "\u41" "\u{zz}" "\u{1234567}""#;
+
+        let expected = vec![
+            (Token::String(String::from("\\q")), "\\q", 2),
+            (Token::String(String::from("\\u{110000}")), "\\u{110000}", 7),
+            (Token::String(String::from("\\u41")), "\\u", 20),
+            (Token::String(String::from("\\u{zz}")), "\\u{", 27),
+            (
+                Token::String(String::from("\\u{1234567}")),
+                "\\u{1234567}",
+                36,
+            ),
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for (expected_token, escape, column) in expected {
+            assert_eq!(lexer.next_token(), expected_token);
+
+            let errors = lexer.take_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].fragment(), escape);
+            assert_eq!(errors[0].span().column, column);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut lexer = Lexer::new("let s = \"abc\\\"");
+        for _ in 0..3 {
+            lexer.next_token();
+        }
+
+        assert_eq!(lexer.next_token(), Token::String(String::from("abc\"")));
+        let errors = lexer.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "unterminated string");
+        assert_eq!(errors[0].span().column, 9);
+        assert_eq!(lexer.next_token(), Token::Eof);
+
+        // A backslash right before the end of the input
+        let mut lexer = Lexer::new("\"abc\\");
+        assert_eq!(lexer.next_token(), Token::String(String::from("abc")));
+        assert_eq!(
+            lexer.take_errors(),
+            vec![LexError::UnterminatedString {
+                span: Span::new(0, 1, 1)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        let tests = vec![
+            ("plain", "plain"),
+            ("a\nb\tc\r", "a\\nb\\tc\\r"),
+            ("say \"hi\"", "say \\\"hi\\\""),
+            ("back\\slash", "back\\\\slash"),
+            ("\u{1}é", "\\u{1}é"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(escape(input), expected);
+
+            let mut lexer = Lexer::new(&format!("\"{expected}\""));
+            assert_eq!(lexer.next_token(), Token::String(input.to_string()));
+        }
+    }
 }
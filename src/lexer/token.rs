@@ -1,5 +1,52 @@
 use std::fmt::Display;
 
+/// A location in the source code.
+///
+/// Lines and columns start at 1, the column is counted in characters and the
+/// offset in bytes from the start of the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Span {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A token together with the location where it starts.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// A `//` or `/* */` comment, as written in the source.
+///
+/// Comments are not tokens, the lexer skips them and keeps them aside so that
+/// tools like the formatter can put them back.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    /// Full text of the comment, delimiters included.
+    pub text: String,
+    pub span: Span,
+    /// True if the comment follows some code on the same line.
+    pub trailing: bool,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, PartialEq, Clone)] // I should find a way of avoiding this thanks to lifetimes, but
                                    // not for now (the issue is with the parser...)
@@ -10,6 +57,7 @@ pub enum Token {
     // Identifiers + literals
     Ident(String), // add, foobar, x, y, ...
     Int(String),
+    Float(String),
     String(String),
 
     // Operators
@@ -28,6 +76,11 @@ pub enum Token {
     And,
     Or,
     Modulo,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    ModuloAssign,
 
     // Delimiters
     Comma,
@@ -52,12 +105,17 @@ pub enum Token {
     While,
     Break,
     Continue,
+    Try,
+    Catch,
+    Throw,
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::Ident(x) | Token::Int(x) | Token::String(x) => write!(f, "{x}"),
+            Token::Ident(x) | Token::Int(x) | Token::Float(x) | Token::String(x) => {
+                write!(f, "{x}")
+            }
             Token::Illegal(s) => write!(f, "Illegal: {s}"),
             Token::Eof => write!(f, "Eof"),
             Token::Assign => write!(f, "="),
@@ -93,7 +151,15 @@ impl Display for Token {
             Token::While => write!(f, "while"),
             Token::Break => write!(f, "break"),
             Token::Continue => write!(f, "continue"),
+            Token::Try => write!(f, "try"),
+            Token::Catch => write!(f, "catch"),
+            Token::Throw => write!(f, "throw"),
             Token::Modulo => write!(f, "%"),
+            Token::PlusAssign => write!(f, "+="),
+            Token::MinusAssign => write!(f, "-="),
+            Token::AsteriskAssign => write!(f, "*="),
+            Token::SlashAssign => write!(f, "/="),
+            Token::ModuloAssign => write!(f, "%="),
         }
     }
 }
@@ -1,10 +1,15 @@
+pub mod budget;
 pub mod compiler;
+pub mod differential;
+pub mod errors;
 pub mod formatter;
 pub mod interpreter;
 pub mod lexer;
 pub mod object;
 pub mod parser;
 pub mod repl;
+pub mod runtime;
+pub mod session;
 pub mod vm;
 
 pub mod utils;
@@ -1,11 +1,19 @@
 use enum_stringify::EnumStringify;
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Debug, Display, Formatter},
+    rc::Rc,
+};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-use crate::object::{Object, NULL};
+use crate::{
+    errors::MonkeyError,
+    object::{Object, NULL},
+};
 
 #[derive(Debug, PartialEq, Clone, FromPrimitive, ToPrimitive, EnumIter, EnumStringify)]
 #[enum_stringify(case = "lower")]
@@ -16,6 +24,57 @@ pub enum BuiltinFunction {
     REST,
     PUSH,
     PUTS,
+    KEYS,
+    VALUES,
+    ADD,
+    DELETE,
+    CONTAINS,
+    INT,
+    FLOAT,
+}
+
+/// Signature of the functions a host program registers with a
+/// [`Runtime`](crate::runtime::Runtime).
+pub type NativeFn = dyn Fn(&[Object]) -> Result<Object, MonkeyError>;
+
+/// A function of the host program, called from Monkey code like a builtin.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, function: Rc<NativeFn>) -> Self {
+        Self {
+            name: name.to_string(),
+            function,
+        }
+    }
+
+    pub fn call(&self, args: &[Object]) -> Result<Object, MonkeyError> {
+        (self.function)(args)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
 }
 
 #[allow(clippy::needless_pass_by_value)] // false positive
@@ -32,91 +91,79 @@ impl BuiltinFunction {
         BuiltinFunction::iter().map(|f| f.to_string()).collect()
     }
 
-    pub fn call(&self, args: Vec<Object>) -> Object {
+    pub fn call(&self, args: Vec<Object>) -> Result<Object, MonkeyError> {
         match self {
             BuiltinFunction::LEN => Self::call_len(args),
             BuiltinFunction::FIRST => Self::call_first(args),
             BuiltinFunction::LAST => Self::call_last(args),
             BuiltinFunction::REST => Self::call_rest(args),
             BuiltinFunction::PUSH => Self::call_push(args),
-            BuiltinFunction::PUTS => Self::call_puts(args),
+            BuiltinFunction::PUTS => Ok(Self::call_puts(args)),
+            BuiltinFunction::KEYS => Self::call_keys(args),
+            BuiltinFunction::VALUES => Self::call_values(args),
+            BuiltinFunction::ADD => Self::call_add(args),
+            BuiltinFunction::DELETE => Self::call_delete(args),
+            BuiltinFunction::CONTAINS => Self::call_contains(args),
+            BuiltinFunction::INT => Self::call_int(args),
+            BuiltinFunction::FLOAT => Self::call_float(args),
         }
     }
 
-    fn call_len(args: Vec<Object>) -> Object {
-        Self::handle_number_of_arguments(args.len(), 1).unwrap_or_else(|| match &args[0] {
-            Object::STRING(s) => Object::INTEGER(s.len() as i64),
-            Object::ARRAY(a) => Object::INTEGER(a.len() as i64),
-            _ => Object::ERROR(format!(
+    fn call_len(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::STRING(s) => Ok(Object::INTEGER(s.len() as i64)),
+            Object::ARRAY(a) => Ok(Object::INTEGER(a.len() as i64)),
+            Object::HASHMAP(h) => Ok(Object::INTEGER(h.len() as i64)),
+            _ => Err(MonkeyError::TypeError(format!(
                 "argument to `len` not supported, got {}",
                 args[0].get_type()
-            )),
-        })
+            ))),
+        }
     }
 
-    fn call_first(args: Vec<Object>) -> Object {
-        Self::handle_number_of_arguments(args.len(), 1).unwrap_or_else(|| match &args[0] {
-            Object::ARRAY(a) => {
-                if a.is_empty() {
-                    NULL
-                } else {
-                    a[0].clone()
-                }
-            }
-            _ => Object::ERROR(format!(
-                "argument to `first` not supported, must be ARRAY, got {}",
-                args[0].get_type()
-            )),
-        })
+    fn call_first(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::ARRAY(a) => Ok(a.first().cloned().unwrap_or(NULL)),
+            _ => Err(Self::unsupported_argument("first", "ARRAY", &args[0])),
+        }
     }
 
-    fn call_last(args: Vec<Object>) -> Object {
-        Self::handle_number_of_arguments(args.len(), 1).unwrap_or_else(|| match &args[0] {
-            Object::ARRAY(a) => {
-                let length = a.len();
-                if length > 0 {
-                    a[length - 1].clone()
-                } else {
-                    NULL
-                }
-            }
-            _ => Object::ERROR(format!(
-                "argument to `last` not supported, must be ARRAY, got {}",
-                args[0].get_type()
-            )),
-        })
+    fn call_last(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::ARRAY(a) => Ok(a.last().cloned().unwrap_or(NULL)),
+            _ => Err(Self::unsupported_argument("last", "ARRAY", &args[0])),
+        }
     }
 
-    fn call_rest(args: Vec<Object>) -> Object {
-        Self::handle_number_of_arguments(args.len(), 1).unwrap_or_else(|| match &args[0] {
+    fn call_rest(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
             Object::ARRAY(a) => {
                 let length = a.len();
 
-                match length.cmp(&1) {
+                Ok(match length.cmp(&1) {
                     Ordering::Greater => Object::ARRAY(a[1..length].to_vec()),
                     Ordering::Equal => Object::ARRAY(vec![]),
                     Ordering::Less => NULL,
-                }
+                })
             }
-            _ => Object::ERROR(format!(
-                "argument to `rest` not supported, must be ARRAY, got {}",
-                args[0].get_type()
-            )),
-        })
+            _ => Err(Self::unsupported_argument("rest", "ARRAY", &args[0])),
+        }
     }
 
-    fn call_push(args: Vec<Object>) -> Object {
-        Self::handle_number_of_arguments(args.len(), 2).unwrap_or_else(|| match &args[0] {
+    fn call_push(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 2)?;
+        match &args[0] {
             Object::ARRAY(a) => {
                 let mut new_array = a.clone();
                 new_array.push(args[1].clone());
-                Object::ARRAY(new_array)
+                Ok(Object::ARRAY(new_array))
             }
-            _ => Object::ERROR(format!(
-                "argument to `push` not supported, must be ARRAY, got {}",
-                args[0].get_type()
-            )),
-        })
+            _ => Err(Self::unsupported_argument("push", "ARRAY", &args[0])),
+        }
     }
 
     fn call_puts(args: Vec<Object>) -> Object {
@@ -126,12 +173,147 @@ impl BuiltinFunction {
         NULL
     }
 
-    fn handle_number_of_arguments(got: usize, expected: usize) -> Option<Object> {
+    fn call_keys(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::HASHMAP(h) => Ok(Object::ARRAY(
+                Self::sorted_entries(h)
+                    .into_iter()
+                    .map(|(k, _)| k.clone())
+                    .collect(),
+            )),
+            _ => Err(Self::unsupported_argument("keys", "HASHMAP", &args[0])),
+        }
+    }
+
+    fn call_values(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::HASHMAP(h) => Ok(Object::ARRAY(
+                Self::sorted_entries(h)
+                    .into_iter()
+                    .map(|(_, v)| v.clone())
+                    .collect(),
+            )),
+            _ => Err(Self::unsupported_argument("values", "HASHMAP", &args[0])),
+        }
+    }
+
+    fn call_add(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 3)?;
+        match &args[0] {
+            Object::HASHMAP(_) if !args[1].is_hashable() => Err(Self::unusable_key(&args[1])),
+            Object::HASHMAP(h) => {
+                let mut new_hashmap = h.clone();
+                new_hashmap.insert(args[1].clone(), args[2].clone());
+                Ok(Object::HASHMAP(new_hashmap))
+            }
+            _ => Err(Self::unsupported_argument("add", "HASHMAP", &args[0])),
+        }
+    }
+
+    fn call_delete(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 2)?;
+        match &args[0] {
+            Object::HASHMAP(_) if !args[1].is_hashable() => Err(Self::unusable_key(&args[1])),
+            Object::HASHMAP(h) => {
+                let mut new_hashmap = h.clone();
+                new_hashmap.remove(&args[1]);
+                Ok(Object::HASHMAP(new_hashmap))
+            }
+            _ => Err(Self::unsupported_argument("delete", "HASHMAP", &args[0])),
+        }
+    }
+
+    fn call_contains(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 2)?;
+        match &args[0] {
+            Object::HASHMAP(_) if !args[1].is_hashable() => Err(Self::unusable_key(&args[1])),
+            Object::HASHMAP(h) => Ok(Object::BOOLEAN(h.contains_key(&args[1]))),
+            _ => Err(Self::unsupported_argument("contains", "HASHMAP", &args[0])),
+        }
+    }
+
+    /// Floats are truncated towards zero, strings are parsed.
+    fn call_int(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::INTEGER(x) => Ok(Object::INTEGER(*x)),
+            // i64::MAX is not a float, the bound is 2^63 itself
+            Object::FLOAT(x) if x.is_finite() && *x >= i64::MIN as f64 && *x < i64::MAX as f64 => {
+                Ok(Object::INTEGER(x.trunc() as i64))
+            }
+            Object::STRING(s) => s
+                .parse::<i64>()
+                .map(Object::INTEGER)
+                .map_err(|_| Self::unconvertible(&args[0], "INTEGER")),
+            Object::FLOAT(_) => Err(Self::unconvertible(&args[0], "INTEGER")),
+            _ => Err(Self::unsupported_argument(
+                "int",
+                "INTEGER, FLOAT or STRING",
+                &args[0],
+            )),
+        }
+    }
+
+    /// Strings are parsed.
+    fn call_float(args: Vec<Object>) -> Result<Object, MonkeyError> {
+        Self::handle_number_of_arguments(args.len(), 1)?;
+        match &args[0] {
+            Object::INTEGER(x) => Ok(Object::FLOAT(*x as f64)),
+            Object::FLOAT(x) => Ok(Object::FLOAT(*x)),
+            Object::STRING(s) => s
+                .parse::<f64>()
+                .map(Object::FLOAT)
+                .map_err(|_| Self::unconvertible(&args[0], "FLOAT")),
+            _ => Err(Self::unsupported_argument(
+                "float",
+                "INTEGER, FLOAT or STRING",
+                &args[0],
+            )),
+        }
+    }
+
+    fn unconvertible(value: &Object, to: &str) -> MonkeyError {
+        MonkeyError::TypeError(format!("cannot convert {value} to {to}"))
+    }
+
+    fn unsupported_argument(name: &str, expected: &str, got: &Object) -> MonkeyError {
+        MonkeyError::TypeError(format!(
+            "argument to `{name}` not supported, must be {expected}, got {}",
+            got.get_type()
+        ))
+    }
+
+    fn unusable_key(key: &Object) -> MonkeyError {
+        MonkeyError::TypeError(format!("unusable as hash key: {}", key.get_type()))
+    }
+
+    /// Entries of a hash in a deterministic order: integers first, then
+    /// booleans, then strings, each of them sorted by value.
+    fn sorted_entries(hashmap: &HashMap<Object, Object>) -> Vec<(&Object, &Object)> {
+        fn rank(key: &Object) -> u8 {
+            match key {
+                Object::INTEGER(_) => 0,
+                Object::BOOLEAN(_) => 1,
+                _ => 2,
+            }
+        }
+
+        let mut entries = hashmap.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Object::INTEGER(x), Object::INTEGER(y)) => x.cmp(y),
+            (Object::BOOLEAN(x), Object::BOOLEAN(y)) => x.cmp(y),
+            (Object::STRING(x), Object::STRING(y)) => x.cmp(y),
+            _ => rank(a).cmp(&rank(b)),
+        });
+        entries
+    }
+
+    fn handle_number_of_arguments(got: usize, expected: usize) -> Result<(), MonkeyError> {
         if got != expected {
-            return Some(Object::ERROR(format!(
-                "wrong number of arguments. got={got}, want={expected}"
-            )));
+            return Err(MonkeyError::ArityError { expected, got });
         }
-        None
+        Ok(())
     }
 }
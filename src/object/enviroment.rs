@@ -42,4 +42,19 @@ impl Environment {
     pub fn set(&mut self, name: String, value: Object) {
         self.store.insert(name, value);
     }
+
+    /// Changes the value of `name` in the environment that defines it,
+    /// returns false if none does.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        match self.store.get_mut(name) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow_mut().assign(name, value),
+                None => false,
+            },
+        }
+    }
 }
@@ -4,48 +4,62 @@ pub mod test_utils;
 
 use std::{
     cell::RefCell,
+    cmp::Ordering,
     collections::HashMap,
     fmt::{self, Display, Formatter},
     hash::Hash,
     rc::Rc,
 };
 
-use crate::parser::ast::{BlockStatement, Identifier};
+use crate::{
+    errors::MonkeyError,
+    lexer::token::Span,
+    parser::ast::{BlockStatement, Identifier},
+};
 
-use crate::object::{builtins::BuiltinFunction, enviroment::Environment};
+use crate::object::{
+    builtins::{BuiltinFunction, NativeFunction},
+    enviroment::Environment,
+};
 
 pub const TRUE: Object = Object::BOOLEAN(true);
 pub const FALSE: Object = Object::BOOLEAN(false);
 pub const NULL: Object = Object::NULL;
 
+/// A value of a Monkey program, in either engine.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Object {
     INTEGER(i64),
+    FLOAT(f64),
     BOOLEAN(bool),
     STRING(String),
-    RETURN(Box<Object>),
-    ERROR(String),
     FUNCTION(Function),
     COMPILEDFUNCTION(CompiledFunction),
     CLOSURE(Closure),
     BUILTIN(BuiltinFunction),
+    NATIVE(NativeFunction),
     ARRAY(Vec<Object>),
     HASHMAP(HashMap<Object, Object>),
     NULL,
 }
 
+/// The name hosts use for [`Object`]: failures are not values, they are
+/// [`MonkeyError`](crate::errors::MonkeyError)s.
+pub type Value = Object;
+
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Object::INTEGER(i) => write!(f, "{i}"),
+            // `1.0` and not `1`, a float never looks like an integer
+            Object::FLOAT(x) => write!(f, "{x:?}"),
             Object::BOOLEAN(b) => write!(f, "{b}"),
             Object::STRING(s) => write!(f, "\"{s}\""),
-            Object::RETURN(o) => write!(f, "{o}",),
             Object::FUNCTION(o) => write!(f, "{o}"),
             Object::COMPILEDFUNCTION(o) => write!(f, "{o}"),
             Object::CLOSURE(o) => write!(f, "{o}"),
             Object::BUILTIN(o) => write!(f, "{o}"),
-            Object::ERROR(s) => write!(f, "ERROR: {s}"),
+            Object::NATIVE(o) => write!(f, "{o}"),
             Object::ARRAY(a) => Self::format_array(f, a),
             Object::HASHMAP(h) => {
                 let mut values: Vec<String> = h.iter().map(|(k, v)| format!("{k}: {v}")).collect();
@@ -63,6 +77,9 @@ impl Hash for Object {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         match self {
             Object::INTEGER(i) => i.hash(state),
+            // 0.0 and -0.0 are equal, they must hash the same
+            Object::FLOAT(x) if *x == 0.0 => 0.0f64.to_bits().hash(state),
+            Object::FLOAT(x) => x.to_bits().hash(state),
             Object::BOOLEAN(b) => b.hash(state),
             Object::STRING(s) => s.hash(state),
             _ => "".hash(state),
@@ -74,14 +91,13 @@ impl Object {
     pub fn get_type(&self) -> String {
         match self {
             Object::INTEGER(_) => String::from("INTEGER"),
+            Object::FLOAT(_) => String::from("FLOAT"),
             Object::BOOLEAN(_) => String::from("BOOLEAN"),
             Object::STRING(_) => String::from("STRING"),
-            Object::RETURN(_) => String::from("RETURN"),
-            Object::ERROR(_) => String::from("ERROR"),
-            Object::FUNCTION(_) => String::from("FUNCTION"),
+            // A function is a closure in the VM, the engines must not differ
+            Object::FUNCTION(_) | Object::CLOSURE(_) => String::from("FUNCTION"),
             Object::COMPILEDFUNCTION(_) => String::from("COMPILEDFUNCTION"),
-            Object::CLOSURE(_) => String::from("CLOSURE"),
-            Object::BUILTIN(_) => String::from("BUILTIN"),
+            Object::BUILTIN(_) | Object::NATIVE(_) => String::from("BUILTIN"),
             Object::ARRAY(_) => String::from("ARRAY"),
             Object::HASHMAP(_) => String::from("HASHMAP"),
             Object::NULL => String::from("NULL"),
@@ -93,12 +109,98 @@ impl Object {
         write!(f, "[{}]", values.join(", "))
     }
 
+    /// The value of an integer or a float as a float, which is what an
+    /// operation between an integer and a float works on.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Object::INTEGER(x) => Some(*x as f64),
+            Object::FLOAT(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    /// Compares two numbers exactly, without converting the integer to a
+    /// float, which could round it: `9007199254740993 == 9007199254740992.0`
+    /// would be true and `==` would not be transitive. `None` if either of them
+    /// is not a number or is NaN.
+    pub fn compare_numbers(&self, other: &Object) -> Option<Ordering> {
+        match (self, other) {
+            (Object::INTEGER(x), Object::INTEGER(y)) => Some(x.cmp(y)),
+            (Object::FLOAT(x), Object::FLOAT(y)) => x.partial_cmp(y),
+            (Object::INTEGER(x), Object::FLOAT(y)) => compare_integer_to_float(*x, *y),
+            (Object::FLOAT(x), Object::INTEGER(y)) => {
+                compare_integer_to_float(*y, *x).map(Ordering::reverse)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the object can be a key of a hash. Floats cannot: `1 == 1.0`
+    /// is true, but they would be two different keys.
     pub fn is_hashable(&self) -> bool {
         matches!(
             self,
             Object::INTEGER(_) | Object::BOOLEAN(_) | Object::STRING(_)
         )
     }
+
+    /// A copy of the array or hash with the element at the end of `path`
+    /// replaced by `value`, the containers on the way being copied too.
+    ///
+    /// Arrays and hashes are values: assigning to an index builds a new one
+    /// that the engines then store back in the variable.
+    pub fn with_element(self, path: &[Object], value: Object) -> Result<Object, MonkeyError> {
+        let Some((index, rest)) = path.split_first() else {
+            return Ok(value);
+        };
+        match (self, index) {
+            (Object::ARRAY(mut elements), Object::INTEGER(i)) => {
+                let position = usize::try_from(*i)
+                    .ok()
+                    .filter(|position| *position < elements.len())
+                    .ok_or_else(|| {
+                        MonkeyError::IndexError(format!(
+                            "index {i} out of bounds for an array of length {}",
+                            elements.len()
+                        ))
+                    })?;
+                let element = std::mem::replace(&mut elements[position], NULL);
+                elements[position] = element.with_element(rest, value)?;
+                Ok(Object::ARRAY(elements))
+            }
+            (Object::HASHMAP(mut pairs), key) => {
+                if !key.is_hashable() {
+                    return Err(MonkeyError::unusable_key(key));
+                }
+                let element = pairs.remove(key).unwrap_or(NULL);
+                pairs.insert(key.clone(), element.with_element(rest, value)?);
+                Ok(Object::HASHMAP(pairs))
+            }
+            (container, index) => Err(MonkeyError::IndexError(format!(
+                "index assignment not supported: {}[{}]",
+                container.get_type(),
+                index.get_type()
+            ))),
+        }
+    }
+}
+
+/// 2^63 is exact as a float, every float from there on is larger than any
+/// integer, and every float below -2^63 is smaller.
+fn compare_integer_to_float(x: i64, y: f64) -> Option<Ordering> {
+    const BOUND: f64 = 9_223_372_036_854_775_808.0;
+    if y.is_nan() {
+        None
+    } else if y >= BOUND {
+        Some(Ordering::Less)
+    } else if y < -BOUND {
+        Some(Ordering::Greater)
+    } else {
+        // The integral part of `y` fits in an i64, the fractional part only
+        // matters when it is equal to `x`
+        let integral = y.trunc();
+        Some(x.cmp(&(integral as i64)).then(integral.partial_cmp(&y)?))
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -119,11 +221,56 @@ impl Display for Function {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct CompiledFunction {
     pub instructions: Vec<u8>,
     pub num_locals: usize,
     pub num_parameters: usize,
+    pub debug: DebugInfo,
+}
+
+/// Offsets of instructions paired with the position of the code that produced
+/// them, sorted by offset.
+pub type LineTable = Vec<(usize, Span)>;
+
+/// Where the instructions of a compiled function come from, to tell where a
+/// runtime error happened.
+///
+/// It never takes part in equality, so that programs compare by their code
+/// alone, use [`DebugInfo::same_origin`] to compare it.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Name the function was bound to with `let`, if any.
+    pub name: Option<String>,
+    /// Position of the code that produced the instructions. An entry covers
+    /// every instruction up to the offset of the next one.
+    pub lines: LineTable,
+}
+
+impl DebugInfo {
+    /// Position of the code that produced the instruction at `offset`.
+    pub fn position(&self, offset: usize) -> Option<Span> {
+        let index = self.lines.partition_point(|(start, _)| *start <= offset);
+        self.lines.get(index.checked_sub(1)?).map(|(_, span)| *span)
+    }
+
+    /// True if both come from a function with the same name, written at the
+    /// same place.
+    pub fn same_origin(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.lines.len() == other.lines.len()
+            && self
+                .lines
+                .iter()
+                .zip(&other.lines)
+                .all(|(a, b)| a.0 == b.0 && a.1.offset == b.1.offset)
+    }
+}
+
+impl PartialEq for DebugInfo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
 impl Display for CompiledFunction {
@@ -139,7 +286,9 @@ impl Display for CompiledFunction {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Closure {
     pub function: CompiledFunction,
-    pub free: Vec<Object>,
+    /// Variables of the enclosing functions, shared with them so that an
+    /// assignment on either side is seen by the other.
+    pub free: Vec<Rc<RefCell<Object>>>,
 }
 
 impl Display for Closure {
@@ -160,11 +309,11 @@ impl Closure {
         }
     }
 
-    pub fn add_free_variable(&mut self, variable: Object) {
+    pub fn add_free_variable(&mut self, variable: Rc<RefCell<Object>>) {
         self.free.push(variable);
     }
 
-    pub fn extend_free_varaibles(&mut self, variables: Vec<Object>) {
+    pub fn extend_free_varaibles(&mut self, variables: Vec<Rc<RefCell<Object>>>) {
         self.free.extend(variables);
     }
 }
@@ -217,7 +366,8 @@ mod tests {
         let string_2 = Object::STRING("two".to_string());
         let true_1 = Object::BOOLEAN(true);
         let false_1 = Object::BOOLEAN(false);
-        let return_object = Object::RETURN(Box::new(Object::INTEGER(1)));
+        let array = Object::ARRAY(vec![Object::INTEGER(1)]);
+        let float = Object::FLOAT(1.0);
 
         assert!(one.is_hashable());
         assert!(two.is_hashable());
@@ -225,6 +375,7 @@ mod tests {
         assert!(string_2.is_hashable());
         assert!(true_1.is_hashable());
         assert!(false_1.is_hashable());
-        assert!(!return_object.is_hashable());
+        assert!(!array.is_hashable());
+        assert!(!float.is_hashable());
     }
 }
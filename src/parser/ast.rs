@@ -1,11 +1,31 @@
 use enum_stringify::EnumStringify;
 
-use crate::{lexer::token::Token, parser::Parser};
+use crate::{
+    lexer::{
+        escape,
+        token::{Comment, Span, Token},
+    },
+    parser::{parser_errors::ParseError, Parser},
+};
 use std::fmt::Display;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Statement>,
+
+    /// Comments found in the source, in order. They are not part of the
+    /// syntax tree, the formatter uses their spans to put them back.
+    pub comments: Vec<Comment>,
+}
+
+impl Program {
+    /// The first `break` or `continue` that is not inside a loop, in any
+    /// function too, whether it is called or not.
+    pub fn stray_loop_statement(&self) -> Option<&LoopStatement> {
+        self.statements
+            .iter()
+            .find_map(|x| x.stray_loop_statement(false))
+    }
 }
 
 impl Display for Program {
@@ -21,7 +41,7 @@ impl Display for Program {
 #[derive(PartialEq, Debug, Clone)]
 pub enum Expression {
     Identifier(Identifier),
-    Primitive(Primitive),
+    Primitive(Primitive, Span),
     Prefix(PrefixOperator),
     Infix(InfixOperator),
     Conditional(Conditional),
@@ -30,13 +50,14 @@ pub enum Expression {
     ArrayLiteral(ArrayLiteral),
     HashMapLiteral(HashMapLiteral),
     IndexExpression(IndexExpression),
+    Assignment(Assignment),
 }
 
 impl Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expression::Identifier(x) => write!(f, "{x}"),
-            Expression::Primitive(x) => write!(f, "{x}"),
+            Expression::Primitive(x, _) => write!(f, "{x}"),
             Expression::Prefix(x) => write!(f, "{x}"),
             Expression::Infix(x) => write!(f, "{x}"),
             Expression::Conditional(x) => write!(f, "{x}"),
@@ -45,16 +66,99 @@ impl Display for Expression {
             Expression::ArrayLiteral(x) => write!(f, "{x}"),
             Expression::IndexExpression(x) => write!(f, "{x}"),
             Expression::HashMapLiteral(x) => write!(f, "{x}"),
+            Expression::Assignment(x) => write!(f, "{x}"),
         }
     }
 }
 
 impl Expression {
-    pub fn parse(parser: &mut Parser, precedence: Precedence) -> Result<Self, String> {
+    /// Whether the expression assigns to the variable `name`, in any nested
+    /// function too. A variable that shadows `name` counts as `name`.
+    pub fn assigns(&self, name: &str) -> bool {
+        let in_blocks = match self {
+            Expression::Conditional(x) => {
+                x.consequence.assigns(name)
+                    || x.alternative.as_ref().is_some_and(|x| x.assigns(name))
+            }
+            Expression::FunctionLiteral(x) => x.body.assigns(name),
+            Expression::Assignment(x) => x.variable().value == name,
+            _ => false,
+        };
+        in_blocks || self.operands().iter().any(|x| x.assigns(name))
+    }
+
+    /// The first `break` or `continue` that is not inside a loop, see
+    /// [`Program::stray_loop_statement`].
+    fn stray_loop_statement(&self, in_loop: bool) -> Option<&LoopStatement> {
+        let in_blocks = match self {
+            Expression::Conditional(x) => x
+                .consequence
+                .stray_loop_statement(in_loop)
+                .or_else(|| x.alternative.as_ref()?.stray_loop_statement(in_loop)),
+            // A loop does not reach into the functions written in it
+            Expression::FunctionLiteral(x) => x.body.stray_loop_statement(false),
+            _ => None,
+        };
+        in_blocks.or_else(|| {
+            self.operands()
+                .into_iter()
+                .find_map(|x| x.stray_loop_statement(in_loop))
+        })
+    }
+
+    /// The expressions directly inside this one, not the ones in its blocks.
+    fn operands(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Identifier(_)
+            | Expression::Primitive(..)
+            | Expression::FunctionLiteral(_) => vec![],
+            Expression::Prefix(x) => vec![&x.right],
+            Expression::Infix(x) => vec![&x.left, &x.right],
+            Expression::Conditional(x) => vec![&x.condition],
+            Expression::FunctionCall(x) => std::iter::once(x.function.as_ref())
+                .chain(&x.arguments)
+                .collect(),
+            Expression::ArrayLiteral(x) => x.elements.iter().collect(),
+            Expression::HashMapLiteral(x) => x
+                .pairs
+                .iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+            Expression::IndexExpression(x) => vec![&x.left, &x.index],
+            Expression::Assignment(x) => vec![&x.target, &x.value],
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(x) => x.span,
+            Expression::Primitive(_, span) => *span,
+            Expression::Prefix(x) => x.span,
+            Expression::Infix(x) => x.span,
+            Expression::Conditional(x) => x.span,
+            Expression::FunctionLiteral(x) => x.span,
+            Expression::FunctionCall(x) => x.span,
+            Expression::ArrayLiteral(x) => x.span,
+            Expression::IndexExpression(x) => x.span,
+            Expression::HashMapLiteral(x) => x.span,
+            Expression::Assignment(x) => x.span,
+        }
+    }
+
+    pub fn parse(parser: &mut Parser, precedence: Precedence) -> Result<Self, ParseError> {
         let mut left_exp = match parser.current_token.clone() {
             Token::Ident(_) => (Identifier::parse(parser)).map(Expression::Identifier),
-            Token::Int(_) | Token::False | Token::True | Token::String(_) => {
-                Primitive::parse(parser).map(Expression::Primitive)
+            Token::Int(_) | Token::Float(_) | Token::False | Token::True | Token::String(_) => {
+                let span = parser.current_span;
+                Primitive::parse(parser).map(|x| Expression::Primitive(x, span))
+            }
+            Token::Minus if Primitive::is_negated_minimum(&parser.peek_token) => {
+                let span = parser.current_span;
+                parser.next_token();
+                Ok(Expression::Primitive(
+                    Primitive::IntegerLiteral(i64::MIN),
+                    span,
+                ))
             }
             Token::Bang | Token::Minus => PrefixOperator::parse(parser).map(Expression::Prefix),
             Token::LParen => Self::parse_grouped_expression(parser),
@@ -62,11 +166,14 @@ impl Expression {
             Token::Function => FunctionLiteral::parse(parser).map(Expression::FunctionLiteral),
             Token::LSquare => ArrayLiteral::parse(parser).map(Expression::ArrayLiteral),
             Token::LSquirly => HashMapLiteral::parse(parser).map(Expression::HashMapLiteral),
-
-            _ => Err(format!(
-                "There is no prefix parser for the token {}",
-                parser.current_token
-            )),
+            Token::Illegal(_) => Err(ParseError::IllegalToken {
+                found: parser.current_token.clone(),
+                span: parser.current_span,
+            }),
+            _ => Err(ParseError::NoPrefixParser {
+                found: parser.current_token.clone(),
+                span: parser.current_span,
+            }),
         }?;
 
         while !parser.peek_token_is(&Token::Semicolon) && precedence < parser.peek_precedence() {
@@ -99,6 +206,15 @@ impl Expression {
                     left_exp =
                         Expression::IndexExpression(IndexExpression::parse(parser, left_exp)?);
                 }
+                Token::Assign
+                | Token::PlusAssign
+                | Token::MinusAssign
+                | Token::AsteriskAssign
+                | Token::SlashAssign
+                | Token::ModuloAssign => {
+                    parser.next_token();
+                    left_exp = Expression::Assignment(Assignment::parse(parser, left_exp)?);
+                }
                 _ => return Ok(left_exp),
             }
         }
@@ -106,17 +222,17 @@ impl Expression {
         Ok(left_exp)
     }
 
-    fn parse_grouped_expression(parser: &mut Parser) -> Result<Expression, String> {
+    fn parse_grouped_expression(parser: &mut Parser) -> Result<Expression, ParseError> {
         parser.next_token();
-        let exp = Expression::parse(parser, Precedence::Lowest);
-        if parser.expect_peek(&Token::RParen) {
-            exp
-        } else {
-            Err(String::new())
-        }
+        let exp = Expression::parse(parser, Precedence::Lowest)?;
+        parser.expect_peek(&Token::RParen)?;
+        Ok(exp)
     }
 
-    fn parse_expression_list(parser: &mut Parser, end: &Token) -> Result<Vec<Expression>, String> {
+    fn parse_expression_list(
+        parser: &mut Parser,
+        end: &Token,
+    ) -> Result<Vec<Expression>, ParseError> {
         let mut list = Vec::new();
         if parser.peek_token_is(end) {
             parser.next_token();
@@ -130,9 +246,10 @@ impl Expression {
             parser.next_token();
             list.push(Expression::parse(parser, Precedence::Lowest)?);
         }
-        if !parser.expect_peek(end) {
-            return Err(String::new());
+        if !parser.peek_token_is(end) {
+            return Err(parser.peek_error(vec![Token::Comma, end.clone()]));
         }
+        parser.next_token();
         Ok(list)
     }
 }
@@ -140,24 +257,44 @@ impl Expression {
 #[derive(PartialEq, Debug, Clone)]
 pub enum Primitive {
     IntegerLiteral(i64),
+    /// The value and the literal as written, `1.5e3` stays `1.5e3` when the
+    /// program is printed back.
+    FloatLiteral(f64, String),
     BooleanLiteral(bool),
     StringLiteral(String),
 }
 
 impl Primitive {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    /// Whether `-` followed by `token` is the smallest integer, whose literal
+    /// only fits with its sign: `9223372036854775808` alone is too large.
+    fn is_negated_minimum(token: &Token) -> bool {
+        matches!(token, Token::Int(x) if format!("-{x}").parse() == Ok(i64::MIN))
+    }
+
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
         match parser.current_token.clone() {
             Token::Int(x) => match x.parse::<i64>() {
                 Ok(x) => Ok(Primitive::IntegerLiteral(x)),
-                Err(_) => Err("Error: expected a number, found an incopatible string".to_string()),
+                Err(_) => Err(ParseError::InvalidInteger {
+                    found: parser.current_token.clone(),
+                    span: parser.current_span,
+                }),
+            },
+            // The lexer only reads valid floats, but they can be too large
+            Token::Float(x) => match x.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(Primitive::FloatLiteral(value, x)),
+                _ => Err(ParseError::InvalidFloat {
+                    found: parser.current_token.clone(),
+                    span: parser.current_span,
+                }),
             },
             Token::True => Ok(Primitive::BooleanLiteral(true)),
             Token::False => Ok(Primitive::BooleanLiteral(false)),
             Token::String(x) => Ok(Primitive::StringLiteral(x)),
-            _ => Err(format!(
-                "There is no primitive parser for the token {}",
-                parser.current_token
-            )),
+            _ => Err(ParseError::NoPrefixParser {
+                found: parser.current_token.clone(),
+                span: parser.current_span,
+            }),
         }
     }
 }
@@ -166,8 +303,9 @@ impl Display for Primitive {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Primitive::IntegerLiteral(x) => write!(f, "{x}"),
+            Primitive::FloatLiteral(_, literal) => write!(f, "{literal}"),
             Primitive::BooleanLiteral(x) => write!(f, "{x}"),
-            Primitive::StringLiteral(x) => write!(f, "\"{x}\""),
+            Primitive::StringLiteral(x) => write!(f, "\"{}\"", escape(x)),
         }
     }
 }
@@ -176,20 +314,23 @@ impl Display for Primitive {
 pub struct PrefixOperator {
     pub token: Token,
     pub right: Box<Expression>,
+    pub span: Span,
 }
 
 impl PrefixOperator {
-    pub fn new(token: Token, rigth: Expression) -> Self {
+    pub fn new(token: Token, rigth: Expression, span: Span) -> Self {
         PrefixOperator {
             token,
             right: Box::new(rigth),
+            span,
         }
     }
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
         let token = parser.current_token.clone();
+        let span = parser.current_span;
         parser.next_token();
         let right = Expression::parse(parser, Precedence::Prefix)?;
-        Ok(PrefixOperator::new(token, right))
+        Ok(PrefixOperator::new(token, right, span))
     }
 }
 impl Display for PrefixOperator {
@@ -203,23 +344,26 @@ pub struct InfixOperator {
     pub token: Token,
     pub left: Box<Expression>,
     pub right: Box<Expression>,
+    pub span: Span,
 }
 
 impl InfixOperator {
-    pub fn new(token: Token, left: Expression, right: Expression) -> Self {
+    pub fn new(token: Token, left: Expression, right: Expression, span: Span) -> Self {
         InfixOperator {
             token,
             left: Box::new(left),
             right: Box::new(right),
+            span,
         }
     }
 
-    fn parse(parser: &mut Parser, left: Expression) -> Result<Self, String> {
+    fn parse(parser: &mut Parser, left: Expression) -> Result<Self, ParseError> {
         let token = parser.current_token.clone();
+        let span = parser.current_span;
         let precedence = parser.current_precedence();
         parser.next_token();
         let right = Expression::parse(parser, precedence)?;
-        Ok(InfixOperator::new(token, left, right))
+        Ok(InfixOperator::new(token, left, right, span))
     }
 }
 
@@ -229,11 +373,102 @@ impl Display for InfixOperator {
     }
 }
 
+/// `target = value`, or a compound assignment like `target += value`.
+///
+/// The target is a variable or an index expression on a variable, and the
+/// assignment evaluates to the new value of the target.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Assignment {
+    pub target: Box<Expression>,
+    pub token: Token,
+    pub value: Box<Expression>,
+    pub span: Span,
+}
+
+impl Assignment {
+    fn parse(parser: &mut Parser, target: Expression) -> Result<Self, ParseError> {
+        let token = parser.current_token.clone();
+        let span = parser.current_span;
+        if !Self::is_target(&target) {
+            return Err(ParseError::InvalidAssignmentTarget { found: token, span });
+        }
+        parser.next_token();
+        // Right associative: `a = b = 1` assigns 1 to both
+        let value = Expression::parse(parser, Precedence::Lowest)?;
+        Ok(Assignment {
+            target: Box::new(target),
+            token,
+            value: Box::new(value),
+            span,
+        })
+    }
+
+    /// A variable, or an index expression on a variable like `a[0]["k"]`.
+    fn is_target(expression: &Expression) -> bool {
+        match expression {
+            Expression::Identifier(_) => true,
+            Expression::IndexExpression(index) => Self::is_target(&index.left),
+            _ => false,
+        }
+    }
+
+    /// The variable an assignment target belongs to and the indices from it to
+    /// the assigned element, outermost first.
+    pub fn split_target(mut target: Expression) -> (Identifier, Vec<Expression>) {
+        let mut indices = Vec::new();
+        loop {
+            match target {
+                Expression::Identifier(name) => {
+                    indices.reverse();
+                    return (name, indices);
+                }
+                Expression::IndexExpression(index) => {
+                    indices.push(*index.index);
+                    target = *index.left;
+                }
+                _ => unreachable!("The parser only accepts variables and index expressions"),
+            }
+        }
+    }
+
+    /// The variable an assignment target belongs to.
+    pub fn variable(&self) -> &Identifier {
+        let mut target = self.target.as_ref();
+        loop {
+            match target {
+                Expression::Identifier(name) => return name,
+                Expression::IndexExpression(index) => target = &index.left,
+                _ => unreachable!("The parser only accepts variables and index expressions"),
+            }
+        }
+    }
+
+    /// The operator applied to the old value and the right side, `None` for a
+    /// plain `=`.
+    pub fn operator(&self) -> Option<Token> {
+        match self.token {
+            Token::PlusAssign => Some(Token::Plus),
+            Token::MinusAssign => Some(Token::Minus),
+            Token::AsteriskAssign => Some(Token::Asterisk),
+            Token::SlashAssign => Some(Token::Slash),
+            Token::ModuloAssign => Some(Token::Modulo),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Assignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {} {})", self.target, self.token, self.value)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Conditional {
     pub condition: Box<Expression>,
     pub consequence: BlockStatement,
     pub alternative: Option<BlockStatement>,
+    pub span: Span,
 }
 
 impl Display for Conditional {
@@ -252,34 +487,28 @@ impl Display for Conditional {
 }
 
 impl Conditional {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
-        if !parser.expect_peek(&Token::LParen) {
-            return Err(String::new());
-        }
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
+        let span = parser.current_span;
+        parser.expect_peek(&Token::LParen)?;
         parser.next_token();
         let condition = Expression::parse(parser, Precedence::Lowest)?;
-        if !parser.expect_peek(&Token::RParen) {
-            return Err(String::new());
-        }
-        if !parser.expect_peek(&Token::LSquirly) {
-            return Err(String::new());
-        }
-        let consequence = BlockStatement::parse(parser);
+        parser.expect_peek(&Token::RParen)?;
+        parser.expect_peek(&Token::LSquirly)?;
+        let consequence = BlockStatement::parse(parser)?;
         let mut alternative = None;
 
         if parser.peek_token_is(&Token::Else) {
             parser.next_token();
-            if !parser.expect_peek(&Token::LSquirly) {
-                return Err(String::new());
-            }
+            parser.expect_peek(&Token::LSquirly)?;
 
-            alternative = Some(BlockStatement::parse(parser));
+            alternative = Some(BlockStatement::parse(parser)?);
         }
 
         Ok(Conditional {
             condition: Box::new(condition),
             consequence,
             alternative,
+            span,
         })
     }
 }
@@ -287,6 +516,9 @@ impl Conditional {
 #[derive(PartialEq, Debug, Clone)]
 pub struct BlockStatement {
     pub statements: Vec<Statement>,
+    pub span: Span,
+    /// Location of the closing `}`.
+    pub end: Span,
 }
 
 impl Display for BlockStatement {
@@ -300,7 +532,20 @@ impl Display for BlockStatement {
 }
 
 impl BlockStatement {
-    pub(crate) fn parse(parser: &mut Parser) -> Self {
+    /// Whether a statement of the block assigns to the variable `name`, see
+    /// [`Expression::assigns`].
+    pub fn assigns(&self, name: &str) -> bool {
+        self.statements.iter().any(|x| x.assigns(name))
+    }
+
+    fn stray_loop_statement(&self, in_loop: bool) -> Option<&LoopStatement> {
+        self.statements
+            .iter()
+            .find_map(|x| x.stray_loop_statement(in_loop))
+    }
+
+    pub(crate) fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
+        let span = parser.current_span;
         parser.next_token();
         let mut statements: Vec<Statement> = Vec::new();
         while !parser.current_token_is(&Token::RSquirly) && !parser.current_token_is(&Token::Eof) {
@@ -309,7 +554,18 @@ impl BlockStatement {
             }
             parser.next_token();
         }
-        BlockStatement { statements }
+        if parser.current_token_is(&Token::Eof) {
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![Token::RSquirly],
+                found: Token::Eof,
+                span: parser.current_span,
+            });
+        }
+        Ok(BlockStatement {
+            statements,
+            span,
+            end: parser.current_span,
+        })
     }
 }
 
@@ -318,6 +574,7 @@ pub struct FunctionLiteral {
     pub name: Option<String>,
     pub parameters: Vec<Identifier>,
     pub body: BlockStatement,
+    pub span: Span,
 }
 
 impl Display for FunctionLiteral {
@@ -332,23 +589,21 @@ impl Display for FunctionLiteral {
 }
 
 impl FunctionLiteral {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
-        if !parser.expect_peek(&Token::LParen) {
-            return Err(String::new());
-        }
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
+        let span = parser.current_span;
+        parser.expect_peek(&Token::LParen)?;
         let parameters = Self::parse_function_parameters(parser)?;
-        if !parser.expect_peek(&Token::LSquirly) {
-            return Err(String::new());
-        }
-        let body = BlockStatement::parse(parser);
+        parser.expect_peek(&Token::LSquirly)?;
+        let body = BlockStatement::parse(parser)?;
         Ok(FunctionLiteral {
             name: None,
             parameters,
             body,
+            span,
         })
     }
 
-    fn parse_function_parameters(parser: &mut Parser) -> Result<Vec<Identifier>, String> {
+    fn parse_function_parameters(parser: &mut Parser) -> Result<Vec<Identifier>, ParseError> {
         let mut identifiers: Vec<Identifier> = Vec::new();
 
         if parser.peek_token_is(&Token::RParen) {
@@ -356,21 +611,22 @@ impl FunctionLiteral {
             return Ok(identifiers);
         }
 
-        parser.next_token();
+        parser.expect_peek(&Token::Ident(String::new()))?;
 
-        let mut identifier = Identifier::new(parser.current_token.clone());
+        let mut identifier = Identifier::new(parser.current_token.clone(), parser.current_span);
         identifiers.push(identifier);
 
         while parser.peek_token_is(&Token::Comma) {
             parser.next_token();
-            parser.next_token();
-            identifier = Identifier::new(parser.current_token.clone());
+            parser.expect_peek(&Token::Ident(String::new()))?;
+            identifier = Identifier::new(parser.current_token.clone(), parser.current_span);
             identifiers.push(identifier);
         }
 
-        if !parser.expect_peek(&Token::RParen) {
-            return Err(String::new());
+        if !parser.peek_token_is(&Token::RParen) {
+            return Err(parser.peek_error(vec![Token::Comma, Token::RParen]));
         }
+        parser.next_token();
 
         Ok(identifiers)
     }
@@ -380,6 +636,7 @@ impl FunctionLiteral {
 pub struct FunctionCall {
     pub function: Box<Expression>,
     pub arguments: Vec<Expression>,
+    pub span: Span,
 }
 
 impl Display for FunctionCall {
@@ -394,12 +651,14 @@ impl Display for FunctionCall {
 }
 
 impl FunctionCall {
-    fn parse(parser: &mut Parser, function: Expression) -> Result<Self, String> {
+    fn parse(parser: &mut Parser, function: Expression) -> Result<Self, ParseError> {
+        let span = parser.current_span;
         let arguments = Expression::parse_expression_list(parser, &Token::RParen)?;
 
         Ok(FunctionCall {
             function: Box::new(function),
             arguments,
+            span,
         })
     }
 }
@@ -410,7 +669,9 @@ pub enum Statement {
     Return(ReturnStatement),
     Expression(Expression),
     While(WhileStatement),
-    LoopStatements(LoopStatement),
+    LoopStatements(LoopStatement, Span),
+    Try(TryStatement),
+    Throw(ThrowStatement),
 }
 
 impl Display for Statement {
@@ -420,7 +681,56 @@ impl Display for Statement {
             Statement::Return(statement) => write!(f, "{statement}"),
             Statement::Expression(expression) => write!(f, "{expression}"),
             Statement::While(statement) => write!(f, "{statement}"),
-            Statement::LoopStatements(statement) => write!(f, "{statement}"),
+            Statement::LoopStatements(statement, _) => write!(f, "{statement}"),
+            Statement::Try(statement) => write!(f, "{statement}"),
+            Statement::Throw(statement) => write!(f, "{statement}"),
+        }
+    }
+}
+
+impl Statement {
+    /// Whether the statement assigns to the variable `name`, see
+    /// [`Expression::assigns`].
+    pub fn assigns(&self, name: &str) -> bool {
+        match self {
+            Statement::Let(x) => x.value.assigns(name),
+            Statement::Return(x) => x.return_value.assigns(name),
+            Statement::Expression(x) => x.assigns(name),
+            Statement::While(x) => x.condition.assigns(name) || x.body.assigns(name),
+            Statement::LoopStatements(..) => false,
+            Statement::Try(x) => x.body.assigns(name) || x.handler.assigns(name),
+            Statement::Throw(x) => x.value.assigns(name),
+        }
+    }
+
+    fn stray_loop_statement(&self, in_loop: bool) -> Option<&LoopStatement> {
+        match self {
+            Statement::Let(x) => x.value.stray_loop_statement(in_loop),
+            Statement::Return(x) => x.return_value.stray_loop_statement(in_loop),
+            Statement::Expression(x) => x.stray_loop_statement(in_loop),
+            // Like the compiler, the condition counts as part of the loop
+            Statement::While(x) => x
+                .condition
+                .stray_loop_statement(true)
+                .or_else(|| x.body.stray_loop_statement(true)),
+            Statement::LoopStatements(x, _) => (!in_loop).then_some(x),
+            Statement::Try(x) => x
+                .body
+                .stray_loop_statement(in_loop)
+                .or_else(|| x.handler.stray_loop_statement(in_loop)),
+            Statement::Throw(x) => x.value.stray_loop_statement(in_loop),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(statement) => statement.span,
+            Statement::Return(statement) => statement.span,
+            Statement::Expression(expression) => expression.span(),
+            Statement::While(statement) => statement.span,
+            Statement::LoopStatements(_, span) => *span,
+            Statement::Try(statement) => statement.span,
+            Statement::Throw(statement) => statement.span,
         }
     }
 }
@@ -429,6 +739,7 @@ impl Display for Statement {
 pub struct LetStatement {
     pub name: Identifier,
     pub value: Expression,
+    pub span: Span,
 }
 
 impl Display for LetStatement {
@@ -441,6 +752,7 @@ impl Display for LetStatement {
 pub struct Identifier {
     pub token: Token,
     pub value: String,
+    pub span: Span,
 }
 
 impl Display for Identifier {
@@ -450,25 +762,31 @@ impl Display for Identifier {
 }
 
 impl Identifier {
-    fn new(token: Token) -> Self {
+    fn new(token: Token, span: Span) -> Self {
         match token.clone() {
-            Token::Ident(s) => Identifier { token, value: s },
+            Token::Ident(s) => Identifier {
+                token,
+                value: s,
+                span,
+            },
             _ => panic!(
                 "This should be a Token::Ident; if not, the function has not been properly called."
             ),
         }
     }
 
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
         match parser.current_token.clone() {
             Token::Ident(s) => Ok(Identifier {
                 token: parser.current_token.clone(),
                 value: s,
+                span: parser.current_span,
+            }),
+            _ => Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Ident(String::new())],
+                found: parser.current_token.clone(),
+                span: parser.current_span,
             }),
-            _ => Err(format!(
-                "Expected an identifier, got {}",
-                parser.current_token
-            )),
         }
     }
 }
@@ -476,6 +794,7 @@ impl Identifier {
 #[derive(PartialEq, Debug, Clone)]
 pub struct ReturnStatement {
     pub return_value: Expression,
+    pub span: Span,
 }
 
 impl Display for ReturnStatement {
@@ -488,6 +807,7 @@ impl Display for ReturnStatement {
 pub struct WhileStatement {
     pub condition: Expression,
     pub body: BlockStatement,
+    pub span: Span,
 }
 
 impl Display for WhileStatement {
@@ -496,9 +816,42 @@ impl Display for WhileStatement {
     }
 }
 
+/// `try { body } catch (parameter) { handler }`, the handler runs with the
+/// exception bound to `parameter` if the body throws.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TryStatement {
+    pub body: BlockStatement,
+    pub parameter: Identifier,
+    pub handler: BlockStatement,
+    pub span: Span,
+}
+
+impl Display for TryStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "try {{\n{}}} catch ({}) {{\n{}}}",
+            self.body, self.parameter, self.handler
+        )
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ThrowStatement {
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl Display for ThrowStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "throw {};", self.value)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct ArrayLiteral {
     pub elements: Vec<Expression>,
+    pub span: Span,
 }
 
 impl Display for ArrayLiteral {
@@ -513,10 +866,12 @@ impl Display for ArrayLiteral {
 }
 
 impl ArrayLiteral {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
+        let span = parser.current_span;
         let expresssions = Expression::parse_expression_list(parser, &Token::RSquare)?;
         Ok(ArrayLiteral {
             elements: expresssions,
+            span,
         })
     }
 }
@@ -525,6 +880,7 @@ impl ArrayLiteral {
 pub struct IndexExpression {
     pub left: Box<Expression>,
     pub index: Box<Expression>,
+    pub span: Span,
 }
 
 impl Display for IndexExpression {
@@ -534,15 +890,15 @@ impl Display for IndexExpression {
 }
 
 impl IndexExpression {
-    fn parse(parser: &mut Parser, left: Expression) -> Result<Self, String> {
+    fn parse(parser: &mut Parser, left: Expression) -> Result<Self, ParseError> {
+        let span = parser.current_span;
         parser.next_token();
         let index = Expression::parse(parser, Precedence::Lowest)?;
-        if !parser.expect_peek(&Token::RSquare) {
-            return Err(String::new());
-        }
+        parser.expect_peek(&Token::RSquare)?;
         Ok(IndexExpression {
             left: Box::new(left),
             index: Box::new(index),
+            span,
         })
     }
 }
@@ -550,6 +906,7 @@ impl IndexExpression {
 #[derive(Debug, Clone, PartialEq)]
 pub struct HashMapLiteral {
     pub pairs: Vec<(Expression, Expression)>,
+    pub span: Span,
 }
 
 impl Display for HashMapLiteral {
@@ -564,30 +921,30 @@ impl Display for HashMapLiteral {
 }
 
 impl HashMapLiteral {
-    fn parse(parser: &mut Parser) -> Result<Self, String> {
+    fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
+        let span = parser.current_span;
         let mut pairs = Vec::new();
         while !parser.peek_token_is(&Token::RSquirly) {
             parser.next_token();
             let key = Expression::parse(parser, Precedence::Lowest)?;
-            if !parser.expect_peek(&Token::Colon) {
-                return Err(String::new());
-            }
+            parser.expect_peek(&Token::Colon)?;
 
             parser.next_token();
             let value = Expression::parse(parser, Precedence::Lowest)?;
 
             pairs.push((key, value));
 
-            if !parser.peek_token_is(&Token::RSquirly) && !parser.expect_peek(&Token::Comma) {
-                return Err(String::new());
+            if !parser.peek_token_is(&Token::RSquirly) {
+                if !parser.peek_token_is(&Token::Comma) {
+                    return Err(parser.peek_error(vec![Token::Comma, Token::RSquirly]));
+                }
+                parser.next_token();
             }
         }
 
-        if !parser.expect_peek(&Token::RSquirly) {
-            return Err(String::new());
-        }
+        parser.expect_peek(&Token::RSquirly)?;
 
-        Ok(HashMapLiteral { pairs })
+        Ok(HashMapLiteral { pairs, span })
     }
 }
 
@@ -599,14 +956,15 @@ pub enum LoopStatement {
 }
 
 impl LoopStatement {
-    pub fn parse(parser: &mut Parser) -> Result<Self, String> {
+    pub fn parse(parser: &mut Parser) -> Result<Self, ParseError> {
         match parser.current_token {
             Token::Break => Ok(Self::Break),
             Token::Continue => Ok(Self::Continue),
-            _ => Err(format!(
-                "Expected a loop statement keyword (break, continue), got {}",
-                parser.current_token
-            )),
+            _ => Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Break, Token::Continue],
+                found: parser.current_token.clone(),
+                span: parser.current_span,
+            }),
         }
     }
 }
@@ -614,18 +972,25 @@ impl LoopStatement {
 #[derive(PartialEq, PartialOrd, Clone, Copy)]
 pub enum Precedence {
     Lowest = 0,
-    Equals = 1,      // ==
-    LessGreater = 2, // > or <
-    Sum = 3,         // +
-    Product = 4,     // *
-    Prefix = 5,      // -X or !X
-    Call = 6,        // myFunction(X)
-    Index = 7,       // array[index]
+    Assign = 1,      // = or +=
+    Equals = 2,      // ==
+    LessGreater = 3, // > or <
+    Sum = 4,         // +
+    Product = 5,     // *
+    Prefix = 6,      // -X or !X
+    Call = 7,        // myFunction(X)
+    Index = 8,       // array[index]
 }
 
 impl From<&Token> for Precedence {
     fn from(value: &Token) -> Self {
         match value {
+            Token::Assign
+            | Token::PlusAssign
+            | Token::MinusAssign
+            | Token::AsteriskAssign
+            | Token::SlashAssign
+            | Token::ModuloAssign => Precedence::Assign,
             Token::Equal | Token::NotEqual => Precedence::Equals,
             Token::LT | Token::GT | Token::LTE | Token::GTE => Precedence::LessGreater,
             Token::Plus | Token::Minus | Token::Or => Precedence::Sum,
@@ -649,19 +1014,25 @@ mod tests {
                     name: Identifier {
                         token: Token::Ident("myVar".to_string()),
                         value: "myVar".to_string(),
+                        span: Span::default(),
                     },
                     value: Expression::Identifier(Identifier {
                         token: Token::Ident("anotherVar".to_string()),
                         value: "anotherVar".to_string(),
+                        span: Span::default(),
                     }),
+                    span: Span::default(),
                 }),
                 Statement::Return(ReturnStatement {
                     return_value: Expression::Identifier(Identifier {
                         token: Token::Ident("myVar".to_string()),
                         value: "myVar".to_string(),
+                        span: Span::default(),
                     }),
+                    span: Span::default(),
                 }),
             ],
+            comments: vec![],
         };
 
         assert_eq!(
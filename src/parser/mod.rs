@@ -1,17 +1,22 @@
 pub mod ast;
 pub mod parser_errors;
 mod parser_tests;
+mod recovery_tests;
 
 use crate::{
-    lexer::{token::Token, Lexer},
+    lexer::{
+        lexer_errors::LexError,
+        token::{Span, Token},
+        Lexer,
+    },
     parser::ast::{
         Expression, Identifier, LetStatement, Precedence, Program, ReturnStatement, Statement,
     },
 };
 
 use self::{
-    ast::{BlockStatement, LoopStatement, WhileStatement},
-    parser_errors::ParserErrors,
+    ast::{BlockStatement, LoopStatement, ThrowStatement, TryStatement, WhileStatement},
+    parser_errors::{ParseError, ParserErrors},
 };
 
 pub struct Parser {
@@ -20,6 +25,12 @@ pub struct Parser {
     pub errors: ParserErrors,
     pub current_token: Token,
     pub peek_token: Token,
+
+    pub current_span: Span,
+    pub peek_span: Span,
+
+    /// Number of `{` opened and not yet closed, up to the current token.
+    brace_depth: usize,
 }
 
 impl Parser {
@@ -29,6 +40,9 @@ impl Parser {
             errors: ParserErrors::new(),
             current_token: Token::Illegal(String::new()),
             peek_token: Token::Illegal(String::new()),
+            current_span: Span::default(),
+            peek_span: Span::default(),
+            brace_depth: 0,
         };
 
         parser.next_token();
@@ -39,12 +53,26 @@ impl Parser {
 
     pub fn next_token(&mut self) {
         self.current_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        self.current_span = self.peek_span;
+
+        let next = self.lexer.next_spanned_token();
+        self.peek_token = next.token;
+        self.peek_span = next.span;
+        for err in self.lexer.take_errors() {
+            self.errors.add_error(ParseError::Lexer(err));
+        }
+
+        match self.current_token {
+            Token::LSquirly => self.brace_depth += 1,
+            Token::RSquirly => self.brace_depth = self.brace_depth.saturating_sub(1),
+            _ => {}
+        }
     }
 
     pub fn parse_program(&mut self) -> Program {
         let mut program = Program {
             statements: Vec::new(),
+            comments: Vec::new(),
         };
 
         while self.current_token != Token::Eof {
@@ -54,47 +82,107 @@ impl Parser {
             self.next_token();
         }
 
+        // Lexer errors are found one token ahead of the parser
+        self.errors.errors.sort_by_key(|err| err.span().offset);
+        program.comments = self.lexer.take_comments();
         program
     }
 
     pub fn parse_statement(&mut self) -> Option<Statement> {
-        match self.current_token {
+        let depth = self.brace_depth - usize::from(self.current_token_is(&Token::LSquirly));
+        let start = self.current_span;
+        let statement = match self.current_token {
             Token::Let => self.parse_let_statement().map(Statement::Let),
             Token::Return => self.parse_return_statement().map(Statement::Return),
             Token::While => self.parse_while_statement().map(Statement::While),
+            Token::Try => self.parse_try_statement().map(Statement::Try),
+            Token::Throw => self.parse_throw_statement().map(Statement::Throw),
             Token::Break | Token::Continue => {
-                self.parse_loop_statement().map(Statement::LoopStatements)
+                let span = self.current_span;
+                self.parse_loop_statement()
+                    .map(|x| Statement::LoopStatements(x, span))
             }
+            Token::Semicolon => Err(ParseError::UnnecessarySemicolon {
+                span: self.current_span,
+            }),
             _ => self.parse_expression_statement().map(Statement::Expression),
+        };
+
+        match statement {
+            Ok(statement) => Some(statement),
+            Err(err) => {
+                // A token that cannot start a statement is dropped on its own,
+                // the rest of the line may still be valid.
+                if err.span().offset != start.offset {
+                    self.synchronize(depth);
+                }
+                if !(err.found() == Token::Eof && self.input_truncated()) {
+                    self.errors.add_error(err);
+                }
+                None
+            }
         }
     }
 
-    fn parse_let_statement(&mut self) -> Option<LetStatement> {
-        if !self.expect_peek(&Token::Ident(String::new())) {
-            return None;
+    /// True if the lexer reached the end of the input inside a string or a
+    /// comment. Running out of tokens is then already reported.
+    fn input_truncated(&self) -> bool {
+        self.errors.errors.iter().any(|err| {
+            matches!(
+                err,
+                ParseError::Lexer(
+                    LexError::UnterminatedString { .. } | LexError::UnterminatedComment { .. }
+                )
+            )
+        })
+    }
+
+    /// Skips the tokens of a statement that failed to parse.
+    ///
+    /// It stops on the `;` ending the statement, or right before a `}` closing
+    /// the enclosing block or a keyword starting a new statement. Braces opened
+    /// by the broken statement are skipped as a whole, `depth` being the
+    /// nesting level at which the statement started.
+    fn synchronize(&mut self, depth: usize) {
+        while !self.current_token_is(&Token::Eof) {
+            if self.brace_depth <= depth {
+                if self.current_token_is(&Token::Semicolon) {
+                    return;
+                }
+                if matches!(
+                    self.peek_token,
+                    Token::Let
+                        | Token::Return
+                        | Token::While
+                        | Token::Try
+                        | Token::Throw
+                        | Token::RSquirly
+                ) {
+                    return;
+                }
+            }
+            self.next_token();
         }
+    }
+
+    fn parse_let_statement(&mut self) -> Result<LetStatement, ParseError> {
+        let span = self.current_span;
+        self.expect_peek(&Token::Ident(String::new()))?;
 
         let name = match self.current_token.clone() {
             Token::Ident(value) => Identifier {
                 token: self.current_token.clone(),
                 value,
+                span: self.current_span,
             },
             _ => unreachable!("This should never happen, we already checked for Ident"),
         };
 
-        if !self.expect_peek(&Token::Assign) {
-            return None;
-        }
+        self.expect_peek(&Token::Assign)?;
 
         self.next_token();
 
-        let mut value = match Expression::parse(self, Precedence::Lowest) {
-            Ok(x) => x,
-            Err(s) => {
-                self.push_error(s);
-                return None;
-            }
-        };
+        let mut value = Expression::parse(self, Precedence::Lowest)?;
 
         if let Expression::FunctionLiteral(literal) = &mut value {
             literal.name = Some(name.token.to_string());
@@ -104,66 +192,98 @@ impl Parser {
             self.next_token();
         }
 
-        Some(LetStatement { name, value })
+        Ok(LetStatement { name, value, span })
     }
 
-    fn parse_return_statement(&mut self) -> Option<ReturnStatement> {
+    fn parse_return_statement(&mut self) -> Result<ReturnStatement, ParseError> {
+        let span = self.current_span;
         self.next_token();
 
-        let return_value = match Expression::parse(self, Precedence::Lowest) {
-            Ok(x) => x,
-            Err(s) => {
-                self.push_error(s);
-                return None;
-            }
-        };
+        let return_value = Expression::parse(self, Precedence::Lowest)?;
 
         if self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
 
-        Some(ReturnStatement { return_value })
+        Ok(ReturnStatement { return_value, span })
     }
 
-    fn parse_while_statement(&mut self) -> Option<WhileStatement> {
+    fn parse_while_statement(&mut self) -> Result<WhileStatement, ParseError> {
+        let span = self.current_span;
         self.next_token();
 
-        let condition = match Expression::parse(self, Precedence::Lowest) {
-            Ok(x) => x,
-            Err(s) => {
-                self.push_error(s);
-                return None;
-            }
+        let condition = Expression::parse(self, Precedence::Lowest)?;
+
+        self.expect_peek(&Token::LSquirly)?;
+
+        let body = BlockStatement::parse(self)?;
+
+        Ok(WhileStatement {
+            condition,
+            body,
+            span,
+        })
+    }
+
+    fn parse_try_statement(&mut self) -> Result<TryStatement, ParseError> {
+        let span = self.current_span;
+        self.expect_peek(&Token::LSquirly)?;
+
+        let body = BlockStatement::parse(self)?;
+
+        self.expect_peek(&Token::Catch)?;
+        self.expect_peek(&Token::LParen)?;
+        self.expect_peek(&Token::Ident(String::new()))?;
+
+        let parameter = match self.current_token.clone() {
+            Token::Ident(value) => Identifier {
+                token: self.current_token.clone(),
+                value,
+                span: self.current_span,
+            },
+            _ => unreachable!("This should never happen, we already checked for Ident"),
         };
 
-        if !self.expect_peek(&Token::LSquirly) {
-            return None;
-        }
+        self.expect_peek(&Token::RParen)?;
+        self.expect_peek(&Token::LSquirly)?;
 
-        let body = BlockStatement::parse(self);
+        let handler = BlockStatement::parse(self)?;
 
-        Some(WhileStatement { condition, body })
+        Ok(TryStatement {
+            body,
+            parameter,
+            handler,
+            span,
+        })
     }
 
-    fn parse_loop_statement(&mut self) -> Option<LoopStatement> {
-        let smt = LoopStatement::parse(self).ok();
+    fn parse_throw_statement(&mut self) -> Result<ThrowStatement, ParseError> {
+        let span = self.current_span;
         self.next_token();
-        smt
+
+        let value = Expression::parse(self, Precedence::Lowest)?;
+
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(ThrowStatement { value, span })
     }
 
-    fn parse_expression_statement(&mut self) -> Option<Expression> {
-        let expression = Expression::parse(self, Precedence::Lowest);
+    fn parse_loop_statement(&mut self) -> Result<LoopStatement, ParseError> {
+        let smt = LoopStatement::parse(self);
         if self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
+        smt
+    }
 
-        match expression {
-            Ok(expression) => Some(expression),
-            Err(s) => {
-                self.push_error(s);
-                None
-            }
+    fn parse_expression_statement(&mut self) -> Result<Expression, ParseError> {
+        let expression = Expression::parse(self, Precedence::Lowest);
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
         }
+        expression
     }
 
     pub fn current_token_is(&self, token: &Token) -> bool {
@@ -182,21 +302,28 @@ impl Parser {
         }
     }
 
-    pub fn expect_peek(&mut self, token: &Token) -> bool {
+    pub fn expect_peek(&mut self, token: &Token) -> Result<(), ParseError> {
         if self.peek_token_is(token) {
             self.next_token();
-            true
+            Ok(())
         } else {
-            self.peek_error(token);
-            false
+            Err(self.peek_error(vec![token.clone()]))
         }
     }
 
-    fn peek_error(&mut self, token: &Token) {
-        self.errors.add_error(format!(
-            "Expected next token to be {}, got {} instead",
-            token, self.peek_token
-        ));
+    /// Builds the error for a peek token that is none of the `expected` ones.
+    pub fn peek_error(&self, expected: Vec<Token>) -> ParseError {
+        match self.peek_token {
+            Token::Illegal(_) => ParseError::IllegalToken {
+                found: self.peek_token.clone(),
+                span: self.peek_span,
+            },
+            _ => ParseError::UnexpectedToken {
+                expected,
+                found: self.peek_token.clone(),
+                span: self.peek_span,
+            },
+        }
     }
 
     pub fn peek_precedence(&mut self) -> Precedence {
@@ -206,12 +333,6 @@ impl Parser {
     pub fn current_precedence(&mut self) -> Precedence {
         Precedence::from(&self.current_token)
     }
-
-    fn push_error(&mut self, message: String) {
-        if !message.is_empty() {
-            self.errors.add_error(message);
-        }
-    }
 }
 
 pub fn parse(input: &str) -> Program {
@@ -3,10 +3,204 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+use crate::lexer::{
+    escape,
+    lexer_errors::LexError,
+    token::{Span, Token},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A diagnostic produced while parsing.
+///
+/// Every diagnostic knows the token that triggered it, where that token starts
+/// and how severe it is. Warnings do not prevent the program from running.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The parser needed one of `expected` but found something else.
+    UnexpectedToken {
+        expected: Vec<Token>,
+        found: Token,
+        span: Span,
+    },
+    /// No expression can start with `found`.
+    NoPrefixParser { found: Token, span: Span },
+    /// An integer literal that does not fit in an `i64`.
+    InvalidInteger { found: Token, span: Span },
+    /// A float literal too large to be finite.
+    InvalidFloat { found: Token, span: Span },
+    /// An assignment whose left side is neither a variable nor an index
+    /// expression, `found` being its operator.
+    InvalidAssignmentTarget { found: Token, span: Span },
+    /// The lexer could not make sense of the input.
+    IllegalToken { found: Token, span: Span },
+    /// A `;` that does not end any statement.
+    UnnecessarySemicolon { span: Span },
+    /// The lexer could read a token, but not a valid one.
+    Lexer(LexError),
+}
+
+impl ParseError {
+    pub fn expected(&self) -> &[Token] {
+        match self {
+            ParseError::UnexpectedToken { expected, .. } => expected,
+            _ => &[],
+        }
+    }
+
+    pub fn found(&self) -> Token {
+        match self {
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::NoPrefixParser { found, .. }
+            | ParseError::InvalidInteger { found, .. }
+            | ParseError::InvalidFloat { found, .. }
+            | ParseError::InvalidAssignmentTarget { found, .. }
+            | ParseError::IllegalToken { found, .. } => found.clone(),
+            ParseError::UnnecessarySemicolon { .. } => Token::Semicolon,
+            ParseError::Lexer(err) => Token::Illegal(err.fragment()),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::NoPrefixParser { span, .. }
+            | ParseError::InvalidInteger { span, .. }
+            | ParseError::InvalidFloat { span, .. }
+            | ParseError::InvalidAssignmentTarget { span, .. }
+            | ParseError::IllegalToken { span, .. }
+            | ParseError::UnnecessarySemicolon { span } => *span,
+            ParseError::Lexer(err) => err.span(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ParseError::UnnecessarySemicolon { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedToken {
+                expected, found, ..
+            } => {
+                let expected = expected.iter().map(describe).collect::<Vec<String>>();
+                let expected = match expected.split_last() {
+                    Some((last, [])) => last.clone(),
+                    Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+                    None => String::from("something else"),
+                };
+                format!("expected {expected}, found {}", describe(found))
+            }
+            ParseError::NoPrefixParser { found, .. } => {
+                format!("expected an expression, found {}", describe(found))
+            }
+            ParseError::InvalidInteger { found, .. } => {
+                format!("integer literal {} is too large", describe(found))
+            }
+            ParseError::InvalidFloat { found, .. } => {
+                format!("float literal {} is too large", describe(found))
+            }
+            ParseError::InvalidAssignmentTarget { found, .. } => format!(
+                "cannot assign with {}, the left side must be a variable or an index expression",
+                describe(found)
+            ),
+            ParseError::IllegalToken { found, .. } => describe(found),
+            ParseError::UnnecessarySemicolon { .. } => String::from("unnecessary `;`"),
+            ParseError::Lexer(err) => err.to_string(),
+        }
+    }
+
+    /// Renders the diagnostic with the offending line of `source` and a caret
+    /// under the token that triggered it.
+    pub fn render(&self, source: &str) -> String {
+        let span = self.span();
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let line = source
+            .lines()
+            .nth(span.line.saturating_sub(1))
+            .unwrap_or("");
+
+        // Tabs are expanded like rustc does, a terminal could show them with
+        // any width and the caret would no longer be under the token
+        let before: String = line.chars().take(span.column.saturating_sub(1)).collect();
+        let padding = " ".repeat(before.replace('\t', TAB).chars().count());
+        let line = line.replace('\t', TAB);
+        let available = line.chars().count().saturating_sub(padding.len());
+        let carets = "^".repeat(width(&self.found()).min(available).max(1));
+
+        format!(
+            "{}: {}\n{gutter}--> {span}\n{gutter} |\n{line_number} | {line}\n{gutter} | {padding}{carets}\n",
+            self.severity(),
+            self.message(),
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.span(),
+            self.severity(),
+            self.message()
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// What a tab of the source is shown as in a snippet.
+const TAB: &str = "    ";
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(x) if x.is_empty() => String::from("an identifier"),
+        Token::Ident(x) => format!("identifier `{x}`"),
+        Token::Int(x) if x.is_empty() => String::from("an integer"),
+        Token::Int(x) => format!("`{x}`"),
+        Token::String(x) => format!("string \"{}\"", escape(x)),
+        Token::Illegal(x) => format!("unknown token `{x}`"),
+        Token::Eof => String::from("end of file"),
+        _ => format!("`{token}`"),
+    }
+}
+
+/// Number of characters the token takes in the source.
+fn width(token: &Token) -> usize {
+    match token {
+        Token::String(x) => escape(x).chars().count() + 2,
+        Token::Illegal(x) => x.chars().count(),
+        Token::Eof => 1,
+        _ => token.to_string().chars().count(),
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct ParserErrors {
-    pub errors: Vec<String>,
+    pub errors: Vec<ParseError>,
+
+    /// Source the errors come from, used to render them with snippets.
+    source: Option<String>,
 }
 
 impl Error for ParserErrors {}
@@ -19,6 +213,10 @@ impl Default for ParserErrors {
 
 impl Display for ParserErrors {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        if let Some(source) = &self.source {
+            return write!(f, "{}", self.render(source));
+        }
+
         writeln!(f, "Parser errors:")?;
         for err in &self.errors {
             writeln!(f, "\t{err}")?;
@@ -29,14 +227,24 @@ impl Display for ParserErrors {
 
 impl ParserErrors {
     pub fn new() -> ParserErrors {
-        ParserErrors { errors: vec![] }
+        ParserErrors {
+            errors: vec![],
+            source: None,
+        }
+    }
+
+    /// Attaches the source code, so that the errors are displayed as snippets.
+    #[must_use]
+    pub fn with_source(mut self, source: &str) -> ParserErrors {
+        self.source = Some(source.to_string());
+        self
     }
 
-    pub fn add_error(&mut self, err: String) {
+    pub fn add_error(&mut self, err: ParseError) {
         self.errors.push(err);
     }
 
-    pub fn add_errors(&mut self, mut errors: Vec<String>) {
+    pub fn add_errors(&mut self, mut errors: Vec<ParseError>) {
         self.errors.append(&mut errors);
     }
 
@@ -47,4 +255,102 @@ impl ParserErrors {
     pub fn len(&self) -> usize {
         self.errors.len()
     }
+
+    /// Returns true if any of the diagnostics is an error, warnings are ignored.
+    pub fn has_errors(&self) -> bool {
+        self.errors.iter().any(|e| e.severity() == Severity::Error)
+    }
+
+    pub fn render(&self, source: &str) -> String {
+        self.errors
+            .iter()
+            .map(|e| e.render(source))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let source = "let x = 1;\nif (x < 3 {\n    x\n}";
+        let error = ParseError::UnexpectedToken {
+            expected: vec![Token::RParen],
+            found: Token::LSquirly,
+            span: Span::new(21, 2, 11),
+        };
+
+        let expected = r"error: expected `)`, found `{`
+ --> 2:11
+  |
+2 | if (x < 3 {
+  |           ^
+";
+        assert_eq!(error.render(source), expected);
+    }
+
+    #[test]
+    fn test_render_multiple_characters() {
+        let source = "let = \"hello\";";
+        let error = ParseError::UnexpectedToken {
+            expected: vec![Token::Ident(String::new())],
+            found: Token::Assign,
+            span: Span::new(4, 1, 5),
+        };
+        assert_eq!(
+            error.render(source),
+            "error: expected an identifier, found `=`\n --> 1:5\n  |\n1 | let = \"hello\";\n  |     ^\n"
+        );
+
+        let error = ParseError::NoPrefixParser {
+            found: Token::String(String::from("hello")),
+            span: Span::new(6, 1, 7),
+        };
+        assert_eq!(
+            error.render(source),
+            "error: expected an expression, found string \"hello\"\n --> 1:7\n  |\n1 | let = \"hello\";\n  |       ^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_end_of_file() {
+        let source = "let f = fn(x) {\n    x";
+        let error = ParseError::UnexpectedToken {
+            expected: vec![Token::RSquirly],
+            found: Token::Eof,
+            span: Span::new(21, 2, 6),
+        };
+        assert_eq!(
+            error.render(source),
+            "error: expected `}`, found end of file\n --> 2:6\n  |\n2 |     x\n  |      ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_with_tabs() {
+        let source = "let f = fn(x) {\n\tif (x\t< 3 {\n\t\tx\n\t}\n}";
+        let error = ParseError::UnexpectedToken {
+            expected: vec![Token::RParen],
+            found: Token::LSquirly,
+            span: Span::new(27, 2, 12),
+        };
+        assert_eq!(
+            error.render(source),
+            "error: expected `)`, found `{`\n --> 2:12\n  |\n2 |     if (x    < 3 {\n  |                  ^\n"
+        );
+    }
+
+    #[test]
+    fn test_message_with_several_expected_tokens() {
+        let error = ParseError::UnexpectedToken {
+            expected: vec![Token::Comma, Token::RSquare],
+            found: Token::Int(String::from("3")),
+            span: Span::default(),
+        };
+        assert_eq!(error.message(), "expected `,` or `]`, found `3`");
+        assert_eq!(error.severity(), Severity::Error);
+    }
 }
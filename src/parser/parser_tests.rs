@@ -2,13 +2,17 @@
 mod tests {
 
     use crate::{
-        lexer::{token::Token, Lexer},
+        lexer::{
+            token::{Span, Token},
+            Lexer,
+        },
         parser::{
             ast::{
                 BlockStatement, Conditional, Expression, FunctionCall, Identifier, InfixOperator,
                 LetStatement, LoopStatement, Primitive, Program, ReturnStatement, Statement,
                 WhileStatement,
             },
+            parser_errors::{ParseError, Severity},
             Parser,
         },
     };
@@ -21,30 +25,37 @@ mod tests {
         ";
 
         let program = generate_program(input);
-        let expected_statemets = vec![
+        let expected_statemets = [
             Statement::Let(LetStatement {
                 name: Identifier {
                     token: Token::Ident("x".to_string()),
                     value: "x".to_string(),
+                    span: Span::default(),
                 },
-                value: Expression::Primitive(Primitive::IntegerLiteral(5)),
+                value: Expression::Primitive(Primitive::IntegerLiteral(5), Span::default()),
+                span: Span::default(),
             }),
             Statement::Let(LetStatement {
                 name: Identifier {
                     token: Token::Ident("y".to_string()),
                     value: "y".to_string(),
+                    span: Span::default(),
                 },
-                value: Expression::Primitive(Primitive::BooleanLiteral(true)),
+                value: Expression::Primitive(Primitive::BooleanLiteral(true), Span::default()),
+                span: Span::default(),
             }),
             Statement::Let(LetStatement {
                 name: Identifier {
                     token: Token::Ident("foobar".to_string()),
                     value: "foobar".to_string(),
+                    span: Span::default(),
                 },
                 value: Expression::Identifier(Identifier {
                     token: Token::Ident("y".to_string()),
                     value: "y".to_string(),
+                    span: Span::default(),
                 }),
+                span: Span::default(),
             }),
         ];
 
@@ -52,7 +63,7 @@ mod tests {
 
         for (i, expected) in expected_statemets.iter().enumerate() {
             println!("{} | {} | {} ", i, expected, program.statements[i]);
-            assert_eq!(program.statements[i], *expected);
+            assert_eq!(without_spans(program.statements[i].clone()), *expected);
         }
     }
 
@@ -65,25 +76,32 @@ mod tests {
         ";
 
         let program = generate_program(input);
-        let expected = vec![
+        let expected = [
             Statement::Return(ReturnStatement {
-                return_value: Expression::Primitive(Primitive::IntegerLiteral(5)),
+                return_value: Expression::Primitive(Primitive::IntegerLiteral(5), Span::default()),
+                span: Span::default(),
             }),
             Statement::Return(ReturnStatement {
-                return_value: Expression::Primitive(Primitive::BooleanLiteral(true)),
+                return_value: Expression::Primitive(
+                    Primitive::BooleanLiteral(true),
+                    Span::default(),
+                ),
+                span: Span::default(),
             }),
             Statement::Return(ReturnStatement {
                 return_value: Expression::Identifier(Identifier {
                     token: Token::Ident("y".to_string()),
                     value: "y".to_string(),
+                    span: Span::default(),
                 }),
+                span: Span::default(),
             }),
         ];
 
         assert_eq!(program.statements.len(), 3);
 
         for (i, expected) in expected.iter().enumerate() {
-            assert_eq!(program.statements[i], *expected);
+            assert_eq!(without_spans(program.statements[i].clone()), *expected);
         }
     }
 
@@ -114,6 +132,124 @@ mod tests {
         assert_ne!(parser.errors.len(), 0);
     }
 
+    #[test]
+    fn test_typed_errors() {
+        let tests = vec![
+            (
+                "let = 10;",
+                ParseError::UnexpectedToken {
+                    expected: vec![Token::Ident(String::new())],
+                    found: Token::Assign,
+                    span: Span::new(4, 1, 5),
+                },
+            ),
+            (
+                "if (true { 1 }",
+                ParseError::UnexpectedToken {
+                    expected: vec![Token::RParen],
+                    found: Token::LSquirly,
+                    span: Span::new(9, 1, 10),
+                },
+            ),
+            (
+                "fn(x) { x",
+                ParseError::UnexpectedToken {
+                    expected: vec![Token::RSquirly],
+                    found: Token::Eof,
+                    span: Span::new(9, 1, 10),
+                },
+            ),
+            (
+                "[1, 2 3]",
+                ParseError::UnexpectedToken {
+                    expected: vec![Token::Comma, Token::RSquare],
+                    found: Token::Int("3".to_string()),
+                    span: Span::new(6, 1, 7),
+                },
+            ),
+            (
+                "99999999999999999999",
+                ParseError::InvalidInteger {
+                    found: Token::Int("99999999999999999999".to_string()),
+                    span: Span::new(0, 1, 1),
+                },
+            ),
+            (
+                "-9223372036854775809",
+                ParseError::InvalidInteger {
+                    found: Token::Int("9223372036854775809".to_string()),
+                    span: Span::new(1, 1, 2),
+                },
+            ),
+            (
+                "1 - 9223372036854775808",
+                ParseError::InvalidInteger {
+                    found: Token::Int("9223372036854775808".to_string()),
+                    span: Span::new(4, 1, 5),
+                },
+            ),
+            (
+                "1e400",
+                ParseError::InvalidFloat {
+                    found: Token::Float("1e400".to_string()),
+                    span: Span::new(0, 1, 1),
+                },
+            ),
+            (
+                "try { 1 } catch { 2 }",
+                ParseError::UnexpectedToken {
+                    expected: vec![Token::LParen],
+                    found: Token::LSquirly,
+                    span: Span::new(16, 1, 17),
+                },
+            ),
+            (
+                "a + 1 = 5",
+                ParseError::InvalidAssignmentTarget {
+                    found: Token::Assign,
+                    span: Span::new(6, 1, 7),
+                },
+            ),
+            (
+                "f() += 1",
+                ParseError::InvalidAssignmentTarget {
+                    found: Token::PlusAssign,
+                    span: Span::new(4, 1, 5),
+                },
+            ),
+            (
+                "5 + #",
+                ParseError::IllegalToken {
+                    found: Token::Illegal("#".to_string()),
+                    span: Span::new(4, 1, 5),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            println!("{input} => {:?}", parser.errors);
+            let error = &parser.errors.errors[0];
+            assert_eq!(error, &expected);
+            assert_eq!(error.severity(), Severity::Error);
+        }
+    }
+
+    #[test]
+    fn test_unnecessary_semicolon_is_a_warning() {
+        let lexer = Lexer::new("let x = 5;;");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        assert_eq!(program.statements.len(), 1);
+        assert_eq!(parser.errors.len(), 1);
+        assert_eq!(parser.errors.errors[0].severity(), Severity::Warning);
+        assert!(!parser.errors.has_errors());
+    }
+
     #[test]
     fn test_identifier_expression() {
         let input = "foobar;";
@@ -121,12 +257,13 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        let statement = &program.statements[0];
+        let statement = without_spans(program.statements[0].clone());
         assert_eq!(
             statement,
-            &Statement::Expression(Expression::Identifier(Identifier {
+            Statement::Expression(Expression::Identifier(Identifier {
                 token: Token::Ident("foobar".to_string()),
                 value: "foobar".to_string(),
+                span: Span::default(),
             }))
         );
     }
@@ -138,11 +275,48 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        let statement = &program.statements[0];
+        let statement = without_spans(program.statements[0].clone());
         assert_eq!(
             statement,
-            &Statement::Expression(Expression::Primitive(Primitive::IntegerLiteral(5)))
+            Statement::Expression(Expression::Primitive(
+                Primitive::IntegerLiteral(5),
+                Span::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_smallest_integer_literal() {
+        // Its digits alone are too large, the sign is part of the literal
+        let program = generate_program("-9223372036854775808;");
+
+        assert_eq!(program.statements.len(), 1);
+        assert_eq!(
+            without_spans(program.statements[0].clone()),
+            Statement::Expression(Expression::Primitive(
+                Primitive::IntegerLiteral(i64::MIN),
+                Span::default()
+            ))
         );
+        assert_eq!(program.to_string(), "-9223372036854775808\n");
+    }
+
+    #[test]
+    fn test_float_literal_expression() {
+        let tests = vec![("2.75", 2.75), ("1e-9", 1e-9), ("2.5E3", 2500.0)];
+
+        for (input, expected) in tests {
+            let program = generate_program(&format!("{input};"));
+
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(
+                without_spans(program.statements[0].clone()),
+                Statement::Expression(Expression::Primitive(
+                    Primitive::FloatLiteral(expected, input.to_string()),
+                    Span::default()
+                ))
+            );
+        }
     }
 
     #[test]
@@ -241,6 +415,11 @@ mod tests {
                 "add(a * b[2], b[1], 2 * [1, 2][1])",
                 "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
             ),
+            ("a = b = c", "(a = (b = c))"),
+            ("a += b * c", "(a += (b * c))"),
+            ("a = b == c", "(a = (b == c))"),
+            ("a[b][c] %= 2", "(((a[b])[c]) %= 2)"),
+            ("f(a -= 1)", "f((a -= 1))"),
         ];
 
         for (input, expected) in test {
@@ -383,6 +562,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_literal_escapes() {
+        let input = r#""tab\there\n\"quoted\" \\ \u{263A}";"#;
+
+        let program = generate_program(input);
+
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::Expression(exp) => {
+                check_primitive_literal(exp, "tab\there\n\"quoted\" \\ \u{263A}");
+                assert_eq!(exp.to_string(), r#""tab\there\n\"quoted\" \\ ☺""#);
+            }
+            _ => panic!("It is not an expression statement"),
+        }
+    }
+
     #[test]
     fn test_array_literal() {
         let input = "[1,2*2,3+3]";
@@ -586,8 +781,13 @@ mod tests {
                 left: Box::new(Expression::Identifier(Identifier {
                     token: Token::Ident("x".to_string()),
                     value: "x".to_string(),
+                    span: Span::default(),
                 })),
-                right: Box::new(Expression::Primitive(Primitive::IntegerLiteral(3))),
+                right: Box::new(Expression::Primitive(
+                    Primitive::IntegerLiteral(3),
+                    Span::default(),
+                )),
+                span: Span::default(),
             }),
             body: BlockStatement {
                 statements: vec![
@@ -595,28 +795,41 @@ mod tests {
                         name: Identifier {
                             token: Token::Ident("x".to_string()),
                             value: "x".to_string(),
+                            span: Span::default(),
                         },
                         value: Expression::Infix(InfixOperator {
                             token: Token::Plus,
                             left: Box::new(Expression::Identifier(Identifier {
                                 token: Token::Ident("x".to_string()),
                                 value: "x".to_string(),
+                                span: Span::default(),
                             })),
-                            right: Box::new(Expression::Primitive(Primitive::IntegerLiteral(3))),
+                            right: Box::new(Expression::Primitive(
+                                Primitive::IntegerLiteral(3),
+                                Span::default(),
+                            )),
+                            span: Span::default(),
                         }),
+                        span: Span::default(),
                     }),
                     Statement::Expression(Expression::FunctionCall(FunctionCall {
                         function: Box::new(Expression::Identifier(Identifier {
                             token: Token::Ident("puts".to_string()),
                             value: "puts".to_string(),
+                            span: Span::default(),
                         })),
                         arguments: vec![Expression::Identifier(Identifier {
                             token: Token::Ident("x".to_string()),
                             value: "x".to_string(),
+                            span: Span::default(),
                         })],
+                        span: Span::default(),
                     })),
                 ],
+                span: Span::default(),
+                end: Span::default(),
             },
+            span: Span::default(),
         };
 
         println!("Input:\n{input}");
@@ -625,7 +838,7 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        match program.statements[0].clone() {
+        match without_spans(program.statements[0].clone()) {
             Statement::While(smt) => {
                 assert_eq!(smt, expected);
             }
@@ -649,8 +862,13 @@ mod tests {
                 left: Box::new(Expression::Identifier(Identifier {
                     token: Token::Ident("x".to_string()),
                     value: "x".to_string(),
+                    span: Span::default(),
                 })),
-                right: Box::new(Expression::Primitive(Primitive::IntegerLiteral(3))),
+                right: Box::new(Expression::Primitive(
+                    Primitive::IntegerLiteral(3),
+                    Span::default(),
+                )),
+                span: Span::default(),
             }),
             body: BlockStatement {
                 statements: vec![Statement::Expression(Expression::Conditional(
@@ -660,18 +878,37 @@ mod tests {
                             left: Box::new(Expression::Identifier(Identifier {
                                 token: Token::Ident("x".to_string()),
                                 value: "x".to_string(),
+                                span: Span::default(),
                             })),
-                            right: Box::new(Expression::Primitive(Primitive::IntegerLiteral(2))),
+                            right: Box::new(Expression::Primitive(
+                                Primitive::IntegerLiteral(2),
+                                Span::default(),
+                            )),
+                            span: Span::default(),
                         })),
                         consequence: BlockStatement {
-                            statements: vec![Statement::LoopStatements(LoopStatement::Break)],
+                            statements: vec![Statement::LoopStatements(
+                                LoopStatement::Break,
+                                Span::default(),
+                            )],
+                            span: Span::default(),
+                            end: Span::default(),
                         },
                         alternative: Some(BlockStatement {
-                            statements: vec![Statement::LoopStatements(LoopStatement::Continue)],
+                            statements: vec![Statement::LoopStatements(
+                                LoopStatement::Continue,
+                                Span::default(),
+                            )],
+                            span: Span::default(),
+                            end: Span::default(),
                         }),
+                        span: Span::default(),
                     },
                 ))],
+                span: Span::default(),
+                end: Span::default(),
             },
+            span: Span::default(),
         };
 
         println!("Input:\n{input}");
@@ -680,7 +917,7 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        match program.statements[0].clone() {
+        match without_spans(program.statements[0].clone()) {
             Statement::While(smt) => {
                 assert_eq!(smt, expected);
             }
@@ -688,6 +925,216 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parsing_try_statements() {
+        let input = r#"try {
+            throw "boom";
+        } catch (e) {
+            puts(e);
+        }"#;
+
+        let program = generate_program(input);
+        assert_eq!(program.statements.len(), 1);
+
+        let Statement::Try(try_statement) = &program.statements[0] else {
+            panic!("It is not a try statement");
+        };
+        check_block_statement(&try_statement.body, r#"throw "boom";"#);
+        check_identifier(&try_statement.parameter, "e");
+        check_block_statement(&try_statement.handler, "puts(e)");
+
+        let Statement::Throw(throw_statement) = &try_statement.body.statements[0] else {
+            panic!("It is not a throw statement");
+        };
+        check_primitive_literal(&throw_statement.value, "boom");
+    }
+
+    #[test]
+    fn test_node_spans() {
+        let input = r"let add = fn(a, b) {
+    return a + b;
+};
+add(1, [2][0]);
+while (true) { break; }";
+
+        let program = generate_program(input);
+        assert_eq!(program.statements.len(), 3);
+
+        let check = |span: Span, line: usize, column: usize| {
+            assert_eq!((span.line, span.column), (line, column));
+            let offset = input
+                .lines()
+                .take(line - 1)
+                .map(|l| l.len() + 1)
+                .sum::<usize>()
+                + column
+                - 1;
+            assert_eq!(span.offset, offset);
+        };
+
+        let Statement::Let(let_statement) = &program.statements[0] else {
+            panic!("It is not a let statement");
+        };
+        check(let_statement.span, 1, 1);
+        check(let_statement.name.span, 1, 5);
+        let Expression::FunctionLiteral(function) = &let_statement.value else {
+            panic!("It is not a function literal");
+        };
+        check(function.span, 1, 11);
+        check(function.parameters[1].span, 1, 17);
+        check(function.body.span, 1, 20);
+        check(function.body.end, 3, 1);
+        let Statement::Return(return_statement) = &function.body.statements[0] else {
+            panic!("It is not a return statement");
+        };
+        check(return_statement.span, 2, 5);
+        check(return_statement.return_value.span(), 2, 14);
+
+        let Statement::Expression(Expression::FunctionCall(call)) = &program.statements[1] else {
+            panic!("It is not a function call");
+        };
+        check(call.span, 4, 4);
+        check(call.function.span(), 4, 1);
+        check(call.arguments[0].span(), 4, 5);
+        let Expression::IndexExpression(index) = &call.arguments[1] else {
+            panic!("It is not an index expression");
+        };
+        check(index.span, 4, 11);
+        check(index.left.span(), 4, 8);
+
+        let Statement::While(while_statement) = &program.statements[2] else {
+            panic!("It is not a while statement");
+        };
+        check(while_statement.span, 5, 1);
+        check(while_statement.condition.span(), 5, 8);
+        check(while_statement.body.statements[0].span(), 5, 16);
+    }
+
+    #[test]
+    fn test_comments_are_kept_aside() {
+        let input = r"// adds one
+let f = fn(x) { x + 1 }; /* trailing */
+f(1) // call";
+
+        let program = generate_program(input);
+        assert_eq!(program.to_string(), "let f = fn(x){\n(x + 1)\n};\nf(1)\n");
+
+        let comments = program
+            .comments
+            .iter()
+            .map(|c| (c.text.as_str(), c.span.line, c.trailing))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            [
+                ("// adds one", 1, false),
+                ("/* trailing */", 2, true),
+                ("// call", 3, true)
+            ]
+        );
+    }
+
+    /// The statement with every span reset, to compare the structure of the
+    /// tree alone.
+    fn without_spans(mut statement: Statement) -> Statement {
+        clear_statement(&mut statement);
+        statement
+    }
+
+    fn clear_statement(statement: &mut Statement) {
+        match statement {
+            Statement::Let(x) => {
+                x.name.span = Span::default();
+                clear_expression(&mut x.value);
+                x.span = Span::default();
+            }
+            Statement::Return(x) => {
+                clear_expression(&mut x.return_value);
+                x.span = Span::default();
+            }
+            Statement::Expression(x) => clear_expression(x),
+            Statement::While(x) => {
+                clear_expression(&mut x.condition);
+                clear_block(&mut x.body);
+                x.span = Span::default();
+            }
+            Statement::LoopStatements(_, span) => *span = Span::default(),
+            Statement::Try(x) => {
+                clear_block(&mut x.body);
+                x.parameter.span = Span::default();
+                clear_block(&mut x.handler);
+                x.span = Span::default();
+            }
+            Statement::Throw(x) => {
+                clear_expression(&mut x.value);
+                x.span = Span::default();
+            }
+        }
+    }
+
+    fn clear_block(block: &mut BlockStatement) {
+        block.statements.iter_mut().for_each(clear_statement);
+        block.span = Span::default();
+        block.end = Span::default();
+    }
+
+    fn clear_expression(expression: &mut Expression) {
+        match expression {
+            Expression::Identifier(x) => x.span = Span::default(),
+            Expression::Primitive(_, span) => *span = Span::default(),
+            Expression::Prefix(x) => {
+                clear_expression(&mut x.right);
+                x.span = Span::default();
+            }
+            Expression::Infix(x) => {
+                clear_expression(&mut x.left);
+                clear_expression(&mut x.right);
+                x.span = Span::default();
+            }
+            Expression::Conditional(x) => {
+                clear_expression(&mut x.condition);
+                clear_block(&mut x.consequence);
+                if let Some(alternative) = &mut x.alternative {
+                    clear_block(alternative);
+                }
+                x.span = Span::default();
+            }
+            Expression::FunctionLiteral(x) => {
+                for parameter in &mut x.parameters {
+                    parameter.span = Span::default();
+                }
+                clear_block(&mut x.body);
+                x.span = Span::default();
+            }
+            Expression::FunctionCall(x) => {
+                clear_expression(&mut x.function);
+                x.arguments.iter_mut().for_each(clear_expression);
+                x.span = Span::default();
+            }
+            Expression::ArrayLiteral(x) => {
+                x.elements.iter_mut().for_each(clear_expression);
+                x.span = Span::default();
+            }
+            Expression::HashMapLiteral(x) => {
+                for (key, value) in &mut x.pairs {
+                    clear_expression(key);
+                    clear_expression(value);
+                }
+                x.span = Span::default();
+            }
+            Expression::IndexExpression(x) => {
+                clear_expression(&mut x.left);
+                clear_expression(&mut x.index);
+                x.span = Span::default();
+            }
+            Expression::Assignment(x) => {
+                clear_expression(&mut x.target);
+                clear_expression(&mut x.value);
+                x.span = Span::default();
+            }
+        }
+    }
+
     fn generate_program(input: &str) -> Program {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
@@ -713,8 +1160,9 @@ mod tests {
 
     fn check_primitive_literal(exp: &Expression, value: &str) {
         match exp {
-            Expression::Primitive(p) => match p {
+            Expression::Primitive(p, _) => match p {
                 Primitive::IntegerLiteral(i) => assert_eq!(i.to_string(), value),
+                Primitive::FloatLiteral(_, literal) => assert_eq!(literal, value),
                 Primitive::BooleanLiteral(b) => assert_eq!(b.to_string(), value),
                 Primitive::StringLiteral(s) => assert_eq!(s, value),
             },
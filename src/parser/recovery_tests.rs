@@ -0,0 +1,285 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        lexer::Lexer,
+        parser::{ast::Program, Parser},
+    };
+
+    struct RecoveryTestCase {
+        input: &'static str,
+        expected_errors: Vec<&'static str>,
+        expected_program: &'static str,
+    }
+
+    fn run_recovery_tests(tests: Vec<RecoveryTestCase>) {
+        for test in tests {
+            println!("Testing input:\n{}", test.input);
+            let (program, errors) = parse_with_errors(test.input);
+            println!("Got errors: {errors:#?}");
+
+            assert_eq!(errors, test.expected_errors);
+            assert_eq!(program.to_string(), test.expected_program);
+        }
+    }
+
+    fn parse_with_errors(input: &str) -> (Program, Vec<String>) {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        let errors = parser
+            .errors
+            .errors
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        (program, errors)
+    }
+
+    #[test]
+    fn test_broken_let_statements() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r"let x 5;
+let = 10;
+let 838383;
+let y = 838383;",
+                expected_errors: vec![
+                    "1:7: error: expected `=`, found `5`",
+                    "2:5: error: expected an identifier, found `=`",
+                    "3:5: error: expected an identifier, found `838383`",
+                ],
+                expected_program: "let y = 838383;\n",
+            },
+            RecoveryTestCase {
+                input: r"let x = ;
+let y = 1 +;
+let z = 2;",
+                expected_errors: vec![
+                    "1:9: error: expected an expression, found `;`",
+                    "2:12: error: expected an expression, found `;`",
+                ],
+                expected_program: "let z = 2;\n",
+            },
+            RecoveryTestCase {
+                // Without a semicolon the next keyword is the synchronization point
+                input: r"let x = 5 let y = 6;
+let = 7
+return y;",
+                expected_errors: vec!["2:5: error: expected an identifier, found `=`"],
+                expected_program: "let x = 5;\nlet y = 6;\nreturn y;\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_broken_delimiters() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r"let x = 1;
+if (x < 3 {
+    x
+}
+let y = 2;",
+                expected_errors: vec!["2:11: error: expected `)`, found `{`"],
+                expected_program: "let x = 1;\nlet y = 2;\n",
+            },
+            RecoveryTestCase {
+                input: r"let a = [1, 2 3];
+let b = a[0;
+let c = (1 + 2;
+let d = {1: 2 3: 4};
+a",
+                expected_errors: vec![
+                    "1:15: error: expected `,` or `]`, found `3`",
+                    "2:12: error: expected `]`, found `;`",
+                    "3:15: error: expected `)`, found `;`",
+                    "4:15: error: expected `,` or `}`, found `3`",
+                ],
+                expected_program: "a\n",
+            },
+            RecoveryTestCase {
+                input: r"let add = fn(a, b {
+    return a + b;
+};
+let result = add(1, 2);
+result",
+                expected_errors: vec!["1:19: error: expected `,` or `)`, found `{`"],
+                expected_program: "let result = add(1, 2);\nresult\n",
+            },
+            RecoveryTestCase {
+                input: r"let f = fn(x, 2) { x };
+f(1)",
+                expected_errors: vec!["1:15: error: expected an identifier, found `2`"],
+                expected_program: "f(1)\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_errors_inside_blocks() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r"let f = fn(x) {
+    let y = x * ;
+    let z = (x + 1;
+    return y + z;
+};
+f(1)",
+                expected_errors: vec![
+                    "2:17: error: expected an expression, found `;`",
+                    "3:19: error: expected `)`, found `;`",
+                ],
+                expected_program: "let f = fn(x){\nreturn (y + z);\n};\nf(1)\n",
+            },
+            RecoveryTestCase {
+                input: r"while (true) {
+    if (x > ) { break; }
+    let a = 1;
+}
+puts(1);",
+                expected_errors: vec!["2:13: error: expected an expression, found `)`"],
+                expected_program: "while true {\nlet a = 1;\n}\nputs(1)\n",
+            },
+            RecoveryTestCase {
+                // The broken hash is skipped as a whole, its closing brace does
+                // not close the function body
+                input: r"let f = fn() {
+    let h = {1 2};
+    h
+};",
+                expected_errors: vec!["2:16: error: expected `:`, found `2`"],
+                expected_program: "let f = fn(){\nh\n};\n",
+            },
+            RecoveryTestCase {
+                input: r"let f = fn() {
+    break
+}",
+                expected_errors: vec![],
+                expected_program: "let f = fn(){\nbreak\n};\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_unterminated_blocks() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r"let f = fn(x) {
+    x + 1;",
+                expected_errors: vec!["2:11: error: expected `}`, found end of file"],
+                expected_program: "",
+            },
+            RecoveryTestCase {
+                input: r"let x = 1;
+while (x < 10) {
+    let x = x + 1;",
+                expected_errors: vec!["3:19: error: expected `}`, found end of file"],
+                expected_program: "let x = 1;\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_illegal_tokens() {
+        run_recovery_tests(vec![RecoveryTestCase {
+            input: r"let c = 1 & 2;
+let d = #;
+let e = 3 | 4;
+let f = 5;",
+            expected_errors: vec![
+                "1:11: error: unknown token `&`",
+                "2:9: error: unknown token `#`",
+                "3:11: error: unknown token `|`",
+            ],
+            expected_program: "let c = 1;\n2\nlet e = 3;\n4\nlet f = 5;\n",
+        }]);
+    }
+
+    #[test]
+    fn test_stray_tokens() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r"let x = 1;
+}
+let y = 2;;
+)",
+                expected_errors: vec![
+                    "2:1: error: expected an expression, found `}`",
+                    "3:11: warning: unnecessary `;`",
+                    "4:1: error: expected an expression, found `)`",
+                ],
+                expected_program: "let x = 1;\nlet y = 2;\n",
+            },
+            RecoveryTestCase {
+                input: r"let x = 1;
+= 5;
+let y = x;",
+                expected_errors: vec!["2:1: error: expected an expression, found `=`"],
+                expected_program: "let x = 1;\n5\nlet y = x;\n",
+            },
+            RecoveryTestCase {
+                input: r"let x = 1;
+x + 1 = 5;
+let y = x;",
+                expected_errors: vec![
+                    "2:7: error: cannot assign with `=`, the left side must be a variable or an index expression",
+                ],
+                expected_program: "let x = 1;\nlet y = x;\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        run_recovery_tests(vec![
+            RecoveryTestCase {
+                input: r#"let a = "tab\t ok";
+let b = "bad \q escape";
+let c = x + ;
+let d = "unknown \u{zzz} and \u{110000}";"#,
+                expected_errors: vec![
+                    "2:14: error: invalid escape sequence `\\q`",
+                    "3:13: error: expected an expression, found `;`",
+                    "4:18: error: invalid escape sequence `\\u{`",
+                    "4:30: error: invalid escape sequence `\\u{110000}`",
+                ],
+                expected_program: r#"let a = "tab\t ok";
+let b = "bad \\q escape";
+let d = "unknown \\u{zzz} and \\u{110000}";
+"#,
+            },
+            RecoveryTestCase {
+                input: r#"let a = 1;
+puts("never closed);
+let b = 2;"#,
+                expected_errors: vec!["2:6: error: unterminated string"],
+                expected_program: "let a = 1;\n",
+            },
+            RecoveryTestCase {
+                input: r"let a = 1; /* never
+closed",
+                expected_errors: vec!["1:12: error: unterminated block comment"],
+                expected_program: "let a = 1;\n",
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_render_lexer_errors() {
+        let input = "let s = \"a\\qb\";";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(
+            parser.errors.render(input),
+            r#"error: invalid escape sequence `\q`
+ --> 1:11
+  |
+1 | let s = "a\qb";
+  |           ^^
+"#
+        );
+    }
+}
@@ -2,24 +2,24 @@ mod errors;
 
 use crate::{
     compiler::{
-        symbol_table::SymbolTable,
+        disassembler::disassemble,
+        optimizer::optimize,
+        serialization::BytecodeError,
         {Bytecode, Compiler},
     },
+    errors::MonkeyError,
     interpreter::evaluator::Evaluator,
     lexer::{token::Token, Lexer},
-    object::{
-        builtins::BuiltinFunction,
-        {Object, NULL},
-    },
+    object::Object,
     parser::{parser_errors::ParserErrors, Parser},
     repl::errors::{CompilerError, LexerErrors, RuntimeError},
-    vm::{GLOBALS_SIZE, VM},
+    session::{Engine, Session},
+    vm::{vm_errors, VM},
 };
 
-use clap_derive::{Parser, ValueEnum};
+use clap_derive::{Parser, Subcommand, ValueEnum};
 use rustyline::{error::ReadlineError, DefaultEditor};
-use std::rc::Rc;
-use std::{error::Error, fs};
+use std::{error::Error, fs, path::Path};
 
 enum InputType {
     File(String),
@@ -34,9 +34,31 @@ enum Mode {
     Compiler,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Compile a Monkey file to bytecode, that can then be run with `monkey <file>.mkc`
+    Build {
+        /// File to compile
+        filename: String,
+
+        /// Output file, defaults to the input file with the `.mkc` extension
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Print the bytecode of a Monkey file (`.monkey` source or `.mkc` bytecode)
+    Disasm {
+        /// File to disassemble
+        filename: String,
+    },
+}
+
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct ReplCli {
-    /// Input file, if not specified, the REPL will be launched
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input file (`.monkey` source or `.mkc` bytecode), if not specified, the REPL will be launched
     filename: Option<String>,
 
     /// Set the mode to use, if not specified, compiler is used
@@ -46,6 +68,10 @@ pub struct ReplCli {
     /// Show the logo
     #[clap(long)]
     logo: bool,
+
+    /// Optimize the compiled bytecode
+    #[arg(short = 'O', long, global = true)]
+    optimize: bool,
 }
 
 impl ReplCli {
@@ -64,6 +90,15 @@ impl ReplCli {
 
     pub fn run(&self) -> Result<(), Box<dyn Error>> {
         //TODO: Implement our own editor for competition
+        if let Some(command) = &self.command {
+            return match command {
+                Command::Build { filename, output } => {
+                    build(filename, output.as_deref(), self.optimize)
+                }
+                Command::Disasm { filename } => disasm(filename, self.optimize),
+            };
+        }
+
         let mut rl = DefaultEditor::new()?;
         match &self.get_input_type() {
             InputType::Repl => {
@@ -73,8 +108,8 @@ impl ReplCli {
                     // we should have an abstraction for this
                     Mode::Lexer => Ok(self.rlpl(&mut rl)?),
                     Mode::Parser => Ok(self.rppl(&mut rl)?),
-                    Mode::Interpreter => self.interpreter(&mut rl),
-                    Mode::Compiler => self.compiler(&mut rl),
+                    Mode::Interpreter => self.session(&mut rl, Engine::Evaluator),
+                    Mode::Compiler => self.session(&mut rl, Engine::Vm),
                 }
             }
             InputType::File(filename) => self.run_file(filename),
@@ -135,85 +170,29 @@ impl ReplCli {
         }
     }
 
-    pub fn interpreter(&self, rl: &mut DefaultEditor) -> Result<(), Box<dyn Error>> {
-        let mut evaluator = Evaluator::new();
-        loop {
-            match rl.readline(self.get_prompt().as_str()) {
-                Ok(line) => match interpret(&mut evaluator, &line) {
-                    Ok(str) => {
-                        if str != Object::NULL.to_string() {
-                            println!("{str}");
-                        }
-                    }
-                    Err(err) => eprintln!("{err}",),
-                },
-                Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
-                    break;
-                }
-                Err(err) => {
-                    println!("Error: {err:?}");
-                    break;
-                }
-            }
-        }
-        Ok(())
-    }
-
-    pub fn compiler(&self, rl: &mut DefaultEditor) -> Result<(), Box<dyn Error>> {
-        let mut symbol_table = SymbolTable::new();
-        for (i, builtin) in BuiltinFunction::get_builtins_names().iter().enumerate() {
-            symbol_table.define_builtin(i, builtin.clone());
-        }
-        let mut constants = Vec::new();
-        let mut globals = {
-            let mut v = Vec::with_capacity(GLOBALS_SIZE);
-            (0..GLOBALS_SIZE).for_each(|_| v.push(Rc::new(NULL)));
-            v
-        };
-
+    /// Runs every line in the same session, so that the globals of a line
+    /// are defined in the next ones.
+    pub fn session(&self, rl: &mut DefaultEditor, engine: Engine) -> Result<(), Box<dyn Error>> {
+        let mut session = Session::new(engine);
+        session.set_optimize(self.optimize);
         loop {
             match rl.readline(self.get_prompt().as_str()) {
                 Ok(line) => {
-                    let lexer = Lexer::new(&line);
-                    let mut parser = Parser::new(lexer);
-                    let program = parser.parse_program();
-                    if !parser.errors.is_empty() {
-                        eprintln!("{}", parser.errors);
+                    let result = session.eval(&line);
+                    if !session.warnings().is_empty() {
+                        eprintln!("{}", session.warnings());
                     }
-                    let mut compiler =
-                        Compiler::new_with_state(symbol_table.clone(), constants.clone());
-                    if let Err(err) = compiler.compile(program) {
-                        let err = CompilerError::new(err);
-                        eprintln!("{err}",);
-                    }
-
-                    let mut vm = VM::new_with_global_store(compiler.bytecode(), globals.clone());
-                    if let Err(err) = vm.run() {
-                        eprintln!("{err}",);
-                    }
-                    constants = compiler.constants;
-                    symbol_table = compiler.symbol_table;
-
-                    let vm_result: Result<String, Box<dyn Error>> = match vm
-                        .last_popped_stack_element()
-                    {
-                        Ok(obj) => match obj.as_ref() {
-                            Object::ERROR(error) => Err(Box::new(RuntimeError::new(error.clone()))),
-                            x => Ok(x.to_string()),
-                        },
-                        Err(_) => Err(Box::new(RuntimeError::new(String::from(
-                            "No object returned from VM",
-                        )))),
-                    };
-
-                    globals = vm.globals;
-                    match vm_result {
-                        Ok(str) => {
-                            if str != Object::NULL.to_string() {
-                                println!("{str}");
+                    match result {
+                        Ok(value) => {
+                            if value != Object::NULL {
+                                println!("{value}");
                             }
                         }
-                        Err(err) => eprintln!("{err}",),
+                        Err(MonkeyError::Compile(err)) => eprintln!("{}", CompilerError::new(err)),
+                        Err(error) => {
+                            let trace = session.trace().to_vec();
+                            eprintln!("{}", vm_errors::RuntimeError { error, trace });
+                        }
                     }
                 }
                 Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
@@ -282,6 +261,17 @@ impl ReplCli {
     }
 
     fn run_file(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        if file_path.ends_with(".mkc") {
+            if self.get_mode() != Mode::Compiler {
+                return Err(
+                    String::from("Error: .mkc files can only be run in compiler mode").into(),
+                );
+            }
+            let bytecode = load_bytecode(file_path)?;
+            run_vm(bytecode)?;
+            return Ok(());
+        }
+
         let contents = ReplCli::read_file_contents(file_path)?;
 
         match self.get_mode() {
@@ -292,7 +282,7 @@ impl ReplCli {
                 interpret(&mut evaluator, &contents)?;
             }
             Mode::Compiler => {
-                let bytecode = compile(&contents)?;
+                let bytecode = compile(&contents, self.optimize)?;
                 run_vm(bytecode)?;
             }
         }
@@ -330,11 +320,20 @@ fn parse(line: &str) -> Result<(), ParserErrors> {
     let lexer = Lexer::new(line);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
-    if parser.errors.is_empty() {
-        println!("{program}");
-        Ok(())
+    check_parser_errors(parser, line)?;
+    println!("{program}");
+    Ok(())
+}
+
+/// Fails if the parser found any error, warnings are only printed.
+fn check_parser_errors(parser: Parser, line: &str) -> Result<(), ParserErrors> {
+    if parser.errors.has_errors() {
+        Err(parser.errors.with_source(line))
     } else {
-        Err(parser.errors)
+        if !parser.errors.is_empty() {
+            eprintln!("{}", parser.errors.render(line));
+        }
+        Ok(())
     }
 }
 
@@ -342,44 +341,67 @@ fn interpret(interpreter: &mut Evaluator, line: &str) -> Result<String, Box<dyn
     let lexer = Lexer::new(line);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
-    if !parser.errors.is_empty() {
-        return Err(Box::new(parser.errors));
-    }
-    let evaluated = interpreter.eval(program);
-
-    if let Object::ERROR(error) = evaluated {
-        Err(Box::new(RuntimeError::new(error)))
-    } else {
-        Ok(evaluated.to_string())
+    check_parser_errors(parser, line)?;
+    match interpreter.eval(program) {
+        Ok(evaluated) => Ok(evaluated.to_string()),
+        Err(err) => Err(Box::new(RuntimeError::new(err.to_string()))),
     }
 }
 
-fn compile(line: &str) -> Result<Bytecode, Box<dyn Error>> {
+fn compile(line: &str, optimized: bool) -> Result<Bytecode, Box<dyn Error>> {
     let lexer = Lexer::new(line);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
-    if !parser.errors.is_empty() {
-        return Err(Box::new(parser.errors));
-    }
+    check_parser_errors(parser, line)?;
     let mut compiler = Compiler::new();
-    match compiler.compile(program) {
-        Ok(()) => Ok(compiler.bytecode()),
-        Err(e) => Err(Box::new(CompilerError::new(e))),
+    if let Err(e) = compiler.compile(program) {
+        return Err(Box::new(CompilerError::new(e)));
     }
+    if optimized {
+        optimize(compiler.bytecode()).map_err(|e| Box::new(CompilerError::new(e)).into())
+    } else {
+        Ok(compiler.bytecode())
+    }
+}
+
+fn build(file_path: &str, output: Option<&str>, optimized: bool) -> Result<(), Box<dyn Error>> {
+    let contents = ReplCli::read_file_contents(file_path)?;
+    let bytecode = compile(&contents, optimized)?;
+    let output = match output {
+        Some(output) => output.to_string(),
+        None => Path::new(file_path)
+            .with_extension("mkc")
+            .to_string_lossy()
+            .to_string(),
+    };
+    fs::write(output, bytecode.serialize()?)?;
+    Ok(())
+}
+
+fn disasm(file_path: &str, optimized: bool) -> Result<(), Box<dyn Error>> {
+    let bytecode = if file_path.ends_with(".mkc") {
+        load_bytecode(file_path)?
+    } else {
+        compile(&ReplCli::read_file_contents(file_path)?, optimized)?
+    };
+    print!("{}", disassemble(&bytecode)?);
+    Ok(())
+}
+
+fn load_bytecode(file_path: &str) -> Result<Bytecode, Box<dyn Error>> {
+    let bytes = fs::read(file_path)?;
+    Bytecode::deserialize(&bytes).map_err(|err: BytecodeError| format!("{file_path}: {err}").into())
 }
 
 fn run_vm(bytecode: Bytecode) -> Result<String, Box<dyn Error>> {
     let mut vm = VM::new(bytecode);
     match vm.run() {
         Ok(()) => match vm.last_popped_stack_element() {
-            Ok(obj) => match obj.as_ref() {
-                Object::ERROR(error) => Err(Box::new(RuntimeError::new(error.clone()))),
-                x => Ok(x.to_string()),
-            },
+            Ok(obj) => Ok(obj.to_string()),
             Err(_) => Err(Box::new(RuntimeError::new(String::from(
                 "No object returned from VM",
             )))),
         },
-        Err(e) => Err(Box::new(RuntimeError::new(e))),
+        Err(e) => Err(Box::new(RuntimeError::new(e.to_string()))),
     }
 }
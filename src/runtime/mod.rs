@@ -0,0 +1,114 @@
+//! Functions of the host program made available to Monkey code.
+//!
+//! ```
+//! use chimpanzee::{errors::MonkeyError, object::Object, parser::parse, runtime::Runtime};
+//!
+//! let mut runtime = Runtime::new();
+//! runtime
+//!     .register("double", |args| match args {
+//!         [Object::INTEGER(x)] => Ok(Object::INTEGER(x * 2)),
+//!         _ => Err(MonkeyError::Native("double expects an integer".to_string())),
+//!     })
+//!     .unwrap();
+//!
+//! let mut evaluator = runtime.evaluator(Default::default());
+//! assert_eq!(evaluator.eval(parse("double(21)")).unwrap(), Object::INTEGER(42));
+//! ```
+
+mod runtime_tests;
+
+use std::rc::Rc;
+
+use crate::{
+    budget::Budget,
+    compiler::{Bytecode, Compiler},
+    errors::MonkeyError,
+    interpreter::evaluator::Evaluator,
+    object::{
+        builtins::{BuiltinFunction, NativeFunction},
+        Object,
+    },
+    vm::{config::VmConfig, VM},
+};
+
+/// The builtins a program can call: the ones of the language, then the
+/// functions registered by the host.
+///
+/// Engines made by a runtime see the functions registered so far, they do not
+/// see the ones registered later.
+#[derive(Debug, Clone, Default)]
+pub struct Runtime {
+    natives: Vec<NativeFunction>,
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `function` callable as `name`. Registering a name again replaces
+    /// the function, the builtins of the language cannot be replaced.
+    ///
+    /// The function fails like a builtin, with any [`MonkeyError`]: scripts
+    /// cannot tell them apart.
+    pub fn register<F>(&mut self, name: &str, function: F) -> Result<(), String>
+    where
+        F: Fn(&[Object]) -> Result<Object, MonkeyError> + 'static,
+    {
+        if BuiltinFunction::get_builtin(name).is_some() {
+            return Err(format!("{name} is already a builtin function"));
+        }
+        let native = NativeFunction::new(name, Rc::new(function));
+        match self.natives.iter_mut().find(|native| native.name == name) {
+            Some(existing) => *existing = native,
+            None => self.natives.push(native),
+        }
+        Ok(())
+    }
+
+    /// Names of all the builtins, the index of a name is the operand of
+    /// `OpGetBuiltin` that loads it.
+    pub fn builtin_names(&self) -> Vec<String> {
+        let mut names = BuiltinFunction::get_builtins_names();
+        names.extend(self.natives.iter().map(|native| native.name.clone()));
+        names
+    }
+
+    pub fn get_builtin(&self, name: &str) -> Option<Object> {
+        BuiltinFunction::get_builtin(name).or_else(|| {
+            self.natives
+                .iter()
+                .find(|native| native.name == name)
+                .map(|native| Object::NATIVE(native.clone()))
+        })
+    }
+
+    pub fn get_builtin_by_id(&self, id: usize) -> Option<Object> {
+        let count = BuiltinFunction::get_builtins_names().len();
+        if id < count {
+            BuiltinFunction::get_builtin_by_id(id)
+        } else {
+            self.natives
+                .get(id - count)
+                .map(|native| Object::NATIVE(native.clone()))
+        }
+    }
+
+    /// A compiler that resolves the names of the builtins of this runtime.
+    pub fn compiler(&self) -> Compiler {
+        Compiler::with_builtins(&self.builtin_names())
+    }
+
+    pub fn evaluator(&self, budget: Budget) -> Evaluator {
+        let mut evaluator = Evaluator::with_budget(budget);
+        evaluator.runtime = self.clone();
+        evaluator
+    }
+
+    /// A VM for bytecode compiled by [`Runtime::compiler`].
+    pub fn vm(&self, bytecode: Bytecode, config: VmConfig) -> VM {
+        let mut vm = VM::with_config(bytecode, config);
+        vm.runtime = self.clone();
+        vm
+    }
+}
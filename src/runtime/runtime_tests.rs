@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{errors::MonkeyError, object::Object, parser::parse, runtime::Runtime};
+
+    fn runtime() -> Runtime {
+        let mut runtime = Runtime::new();
+        runtime
+            .register("double", |args| match args {
+                [Object::INTEGER(x)] => Ok(Object::INTEGER(x * 2)),
+                _ => Err(MonkeyError::Native("double expects an integer".to_string())),
+            })
+            .unwrap();
+        runtime
+            .register("join", |args| {
+                let parts: Vec<String> = args.iter().map(ToString::to_string).collect();
+                Ok(Object::STRING(parts.join(" ")))
+            })
+            .unwrap();
+        runtime
+    }
+
+    /// Runs `input` with both engines, which must agree.
+    fn run(runtime: &Runtime, input: &str) -> Result<Object, String> {
+        let evaluated = runtime
+            .evaluator(Default::default())
+            .eval(parse(input))
+            .map_err(|err| err.to_string());
+
+        let mut compiler = runtime.compiler();
+        compiler.compile(parse(input)).unwrap();
+        let mut vm = runtime.vm(compiler.bytecode(), Default::default());
+        let got = match vm.run() {
+            Ok(()) => Ok(vm.last_popped_stack_element().unwrap().as_ref().clone()),
+            Err(err) => Err(err.error.to_string()),
+        };
+
+        assert_eq!(
+            evaluated, got,
+            "the evaluator and the VM disagree on {input}"
+        );
+        got
+    }
+
+    #[test]
+    fn test_native_functions() {
+        let runtime = runtime();
+        let tests = vec![
+            ("double(21)", Object::INTEGER(42)),
+            (
+                "let f = fn(x) { double(x) + 1 }; f(double(2))",
+                Object::INTEGER(9),
+            ),
+            (
+                "join(1, true, \"a\", len([1, 2]))",
+                Object::STRING("1 true \"a\" 2".to_string()),
+            ),
+            (
+                "let apply = fn(f, x) { f(x) }; apply(double, 5)",
+                Object::INTEGER(10),
+            ),
+            ("[double, len][0](3)", Object::INTEGER(6)),
+            // Natives can be shadowed like any builtin
+            ("let double = fn(x) { x }; double(3)", Object::INTEGER(3)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(&runtime, input),
+                Ok(expected),
+                "wrong result for {input}"
+            );
+        }
+        assert_eq!(
+            run(&runtime, "double(true)"),
+            Err("double expects an integer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_builtin_indices() {
+        let runtime = runtime();
+        let names = runtime.builtin_names();
+        let count = names.len();
+
+        assert_eq!(names[count - 2], "double");
+        assert_eq!(names[count - 1], "join");
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(runtime.get_builtin_by_id(i), runtime.get_builtin(name));
+        }
+        assert_eq!(runtime.get_builtin_by_id(count), None);
+    }
+
+    #[test]
+    fn test_register() {
+        let mut runtime = runtime();
+        assert_eq!(
+            runtime.register("len", |_| Ok(Object::NULL)),
+            Err("len is already a builtin function".to_string())
+        );
+
+        let count = runtime.builtin_names().len();
+        runtime
+            .register("double", |args| match args {
+                [Object::INTEGER(x)] => Ok(Object::INTEGER(x + x + 1)),
+                _ => Ok(Object::NULL),
+            })
+            .unwrap();
+        assert_eq!(runtime.builtin_names().len(), count);
+        assert_eq!(run(&runtime, "double(2)"), Ok(Object::INTEGER(5)));
+    }
+
+    #[test]
+    fn test_unknown_natives() {
+        // Engines that do not know a native cannot resolve it
+        let result = Runtime::new()
+            .evaluator(Default::default())
+            .eval(parse("double(1)"));
+        assert!(matches!(result, Err(MonkeyError::UndefinedVariable(name)) if name == "double"));
+
+        let mut compiler = Runtime::new().compiler();
+        assert_eq!(
+            compiler.compile(parse("double(1)")),
+            Err("Undefined variable: double".to_string())
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Runs Monkey code from a host program, one piece at a time.
+//!
+//! ```
+//! use chimpanzee::{object::Object, session::{Engine, Session}};
+//!
+//! let mut session = Session::new(Engine::Vm);
+//! session.set("limit", Object::INTEGER(3)).unwrap();
+//! session.eval("let double = fn(x) { x * 2 };").unwrap();
+//! assert_eq!(session.eval("double(limit)").unwrap(), Object::INTEGER(6));
+//! ```
+
+mod session_tests;
+
+use std::rc::Rc;
+
+use crate::{
+    compiler::{
+        optimizer::optimize,
+        symbol_table::{SymbolScope, SymbolTable},
+        Compiler,
+    },
+    errors::MonkeyError,
+    interpreter::evaluator::Evaluator,
+    lexer::Lexer,
+    object::{Object, NULL},
+    parser::{ast::Statement, parser_errors::ParserErrors, Parser},
+    runtime::Runtime,
+    vm::{config::VmConfig, vm_errors::TraceEntry, GLOBALS_SIZE},
+};
+
+pub use crate::object::Value;
+
+/// The engine that runs the code of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    Evaluator,
+    #[default]
+    Vm,
+}
+
+/// Global variables, functions and constants kept between two calls to
+/// [`Session::eval`].
+pub struct Session {
+    runtime: Runtime,
+    config: VmConfig,
+    state: State,
+    /// Whether the VM runs optimized bytecode.
+    optimize: bool,
+    /// Calls that were running when the last runtime error happened.
+    trace: Vec<TraceEntry>,
+    /// Warnings of the parser on the last code that ran.
+    warnings: ParserErrors,
+}
+
+enum State {
+    Evaluator(Evaluator),
+    Vm {
+        symbol_table: SymbolTable,
+        constants: Vec<Object>,
+        globals: Vec<Rc<Object>>,
+    },
+}
+
+impl Session {
+    pub fn new(engine: Engine) -> Self {
+        Self::with_runtime(engine, Runtime::default(), VmConfig::default())
+    }
+
+    /// Creates a session whose code can call the functions of `runtime` and
+    /// is stopped when it goes over `config`. The evaluator only has a
+    /// budget, the other limits only apply to the VM.
+    pub fn with_runtime(engine: Engine, runtime: Runtime, config: VmConfig) -> Self {
+        let state = match engine {
+            Engine::Evaluator => State::Evaluator(runtime.evaluator(config.budget.clone())),
+            Engine::Vm => State::Vm {
+                symbol_table: runtime.compiler().symbol_table,
+                constants: Vec::new(),
+                globals: Vec::new(),
+            },
+        };
+        Session {
+            runtime,
+            config,
+            state,
+            optimize: false,
+            trace: Vec::new(),
+            warnings: ParserErrors::new(),
+        }
+    }
+
+    /// Runs the [optimizer](crate::compiler::optimizer) over the code before
+    /// the VM runs it. The evaluator is not affected.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn engine(&self) -> Engine {
+        match self.state {
+            State::Evaluator(_) => Engine::Evaluator,
+            State::Vm { .. } => Engine::Vm,
+        }
+    }
+
+    /// Runs `input` and returns the value of its last statement if it is an
+    /// expression, null otherwise.
+    ///
+    /// The globals defined before a runtime error are kept, nothing is kept
+    /// from code that does not parse or compile.
+    pub fn eval(&mut self, input: &str) -> Result<Value, MonkeyError> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        let errors = parser.errors.with_source(input);
+        self.warnings = ParserErrors::new();
+        if errors.has_errors() {
+            return Err(MonkeyError::Syntax(errors));
+        }
+        self.warnings = errors;
+        let has_value = matches!(program.statements.last(), Some(Statement::Expression(_)));
+        self.trace.clear();
+
+        let value = match &mut self.state {
+            State::Evaluator(evaluator) => evaluator.eval(program)?,
+            State::Vm {
+                symbol_table,
+                constants,
+                globals,
+            } => {
+                let mut compiler =
+                    Compiler::new_with_state(symbol_table.clone(), constants.clone());
+                compiler.compile(program).map_err(MonkeyError::Compile)?;
+                let mut bytecode = compiler.bytecode();
+                if self.optimize {
+                    bytecode = optimize(bytecode).map_err(MonkeyError::Compile)?;
+                }
+                // Optimizing can add constants, the next calls must see them
+                constants.clone_from(&bytecode.constants);
+                *symbol_table = compiler.symbol_table;
+
+                let mut vm = self.runtime.vm(bytecode, self.config.clone());
+                vm.globals = std::mem::take(globals);
+                let result = vm.run();
+                *globals = std::mem::take(&mut vm.globals);
+                if let Err(err) = result {
+                    self.trace = err.trace;
+                    return Err(err.error);
+                }
+
+                vm.last_popped_stack_element()
+                    .map_or(NULL, |value| value.as_ref().clone())
+            }
+        };
+
+        Ok(if has_value { value } else { NULL })
+    }
+
+    /// The calls that were running when the last call to [`Session::eval`]
+    /// failed, the innermost one first. Only the VM keeps track of them.
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    /// The warnings of the parser on the code of the last call to
+    /// [`Session::eval`], displayed as snippets of that code. When the code
+    /// has errors they are in the [`MonkeyError::Syntax`] instead.
+    pub fn warnings(&self) -> &ParserErrors {
+        &self.warnings
+    }
+
+    /// Value of the global variable `name`, if it is defined.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match &self.state {
+            State::Evaluator(evaluator) => evaluator.get_global(name),
+            State::Vm {
+                symbol_table,
+                globals,
+                ..
+            } => {
+                // Resolving a name does not change a table without an outer one
+                let symbol = symbol_table.clone().resolve(name)?;
+                if symbol.scope != SymbolScope::Global {
+                    return None;
+                }
+                Some(globals.get(symbol.index)?.as_ref().clone())
+            }
+        }
+    }
+
+    /// Defines the global variable `name`, or changes its value, like a `let`
+    /// at the top of the program would.
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), MonkeyError> {
+        match &mut self.state {
+            State::Evaluator(evaluator) => evaluator.set_global(name, value),
+            State::Vm {
+                symbol_table,
+                globals,
+                ..
+            } => {
+                let symbol = match symbol_table.resolve(name) {
+                    Some(symbol) if symbol.scope == SymbolScope::Global => symbol,
+                    _ => symbol_table.define(name.to_string()),
+                };
+                if symbol.index >= GLOBALS_SIZE {
+                    return Err(MonkeyError::Compile(format!(
+                        "Too many global variables, a program can use at most {GLOBALS_SIZE}"
+                    )));
+                }
+                if symbol.index >= globals.len() {
+                    globals.resize(symbol.index + 1, Rc::new(NULL));
+                }
+                globals[symbol.index] = Rc::new(value);
+            }
+        }
+        Ok(())
+    }
+}
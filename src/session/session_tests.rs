@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        budget::Budget,
+        errors::{Limit, MonkeyError},
+        object::Object,
+        runtime::Runtime,
+        session::{Engine, Session},
+        vm::config::VmConfig,
+    };
+
+    const ENGINES: [Engine; 2] = [Engine::Evaluator, Engine::Vm];
+
+    #[test]
+    fn test_state_is_kept() {
+        for engine in ENGINES {
+            let mut session = Session::new(engine);
+            assert_eq!(session.engine(), engine);
+
+            assert_eq!(session.eval("let a = 5;").unwrap(), Object::NULL);
+            assert_eq!(
+                session.eval("let add = fn(x) { x + a };").unwrap(),
+                Object::NULL
+            );
+            assert_eq!(session.eval("add(2)").unwrap(), Object::INTEGER(7));
+            assert_eq!(
+                session.eval("let a = 10; add(2)").unwrap(),
+                Object::INTEGER(12)
+            );
+            assert_eq!(
+                session.eval("\"a\" + \"b\"").unwrap(),
+                Object::STRING("ab".to_string())
+            );
+            assert_eq!(session.eval("[1, 2][1]").unwrap(), Object::INTEGER(2));
+        }
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        for engine in ENGINES {
+            let mut session = Session::new(engine);
+            session.eval("let a = [1, 2];").unwrap();
+
+            assert_eq!(
+                session.get("a"),
+                Some(Object::ARRAY(vec![Object::INTEGER(1), Object::INTEGER(2)]))
+            );
+            assert_eq!(session.get("b"), None);
+            assert_eq!(session.get("len"), None);
+
+            session.set("b", Object::INTEGER(40)).unwrap();
+            session.set("a", Object::INTEGER(2)).unwrap();
+            assert_eq!(session.eval("a + b").unwrap(), Object::INTEGER(42));
+            assert_eq!(session.get("b"), Some(Object::INTEGER(40)));
+
+            // A global can take the name of a builtin, like with `let`
+            session.set("len", Object::INTEGER(1)).unwrap();
+            assert_eq!(session.eval("len").unwrap(), Object::INTEGER(1));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        for engine in ENGINES {
+            let mut session = Session::new(engine);
+
+            assert!(matches!(
+                session.eval("let = 1;"),
+                Err(MonkeyError::Syntax(_))
+            ));
+
+            let result = session.eval("let a = 1; a + true");
+            assert!(
+                matches!(result, Err(MonkeyError::TypeError(_))),
+                "{result:?}"
+            );
+            assert_eq!(session.trace().len(), usize::from(engine == Engine::Vm));
+            // The globals defined before the error are kept
+            assert_eq!(session.get("a"), Some(Object::INTEGER(1)));
+            assert_eq!(session.eval("a").unwrap(), Object::INTEGER(1));
+        }
+
+        let mut session = Session::new(Engine::Vm);
+        let result = session.eval("let f = fn() { 1 }; undefined");
+        assert!(
+            matches!(&result, Err(MonkeyError::Compile(message)) if message == "Undefined variable: undefined"),
+            "{result:?}"
+        );
+        // Nothing is kept from code that does not compile
+        assert_eq!(session.get("f"), None);
+        assert!(matches!(session.eval("f"), Err(MonkeyError::Compile(_))));
+    }
+
+    #[test]
+    fn test_optimized_code() {
+        let mut session = Session::new(Engine::Vm);
+        session.set_optimize(true);
+
+        // The folded constants are kept for the next calls
+        session.eval(r#"let s = "mon" + "key";"#).unwrap();
+        assert_eq!(
+            session.eval(r#"s + "s""#).unwrap(),
+            Object::STRING("monkeys".to_string())
+        );
+        assert_eq!(
+            session.eval("if (1 < 2) { 3 * 4 }").unwrap(),
+            Object::INTEGER(12)
+        );
+        assert!(matches!(
+            session.eval("1 / 0"),
+            Err(MonkeyError::DivisionByZero)
+        ));
+    }
+
+    #[test]
+    fn test_warnings() {
+        for engine in ENGINES {
+            let mut session = Session::new(engine);
+
+            assert_eq!(session.eval("1;;").unwrap(), Object::INTEGER(1));
+            assert_eq!(session.warnings().len(), 1);
+            assert!(session.warnings().to_string().contains("1 | 1;;"));
+
+            session.eval("2").unwrap();
+            assert!(session.warnings().is_empty());
+
+            session.eval("1;;").unwrap();
+            assert!(session.eval("let = 1;;").is_err());
+            assert!(session.warnings().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_runtime_and_budget() {
+        let mut runtime = Runtime::new();
+        runtime
+            .register("answer", |_| Ok(Object::INTEGER(42)))
+            .unwrap();
+        let config = VmConfig {
+            budget: Budget {
+                fuel: Some(1000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for engine in ENGINES {
+            let mut session = Session::with_runtime(engine, runtime.clone(), config.clone());
+            assert_eq!(session.eval("answer()").unwrap(), Object::INTEGER(42));
+
+            let err = session.eval("while (true) {}").unwrap_err();
+            assert_eq!(err.limit(), Some(Limit::OutOfFuel));
+
+            // Each call gets the whole budget
+            assert_eq!(session.eval("answer() + 1").unwrap(), Object::INTEGER(43));
+        }
+    }
+}
@@ -29,7 +29,7 @@ pub fn execute_vm(compiler: &Compiler) -> Object {
 pub fn execute_interpreter(program: &Program) {
     let mut interpreter = Evaluator::new();
 
-    interpreter.eval(program.clone());
+    interpreter.eval(program.clone()).unwrap();
 }
 
 pub fn run_input(input: &str) -> Object {
@@ -0,0 +1,256 @@
+#[allow(clippy::too_many_lines)]
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        object::Object,
+        vm::test_utils::{run_vm_error_tests, run_vm_tests, VmTestCase},
+    };
+
+    #[test]
+    fn test_assign_variables() {
+        let tests = vec![
+            VmTestCase {
+                input: "let a = 1; a = 2; a".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "let a = 1; a = a + 1".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "let a = 1; let b = 2; a = b = 3; a + b".to_string(),
+                expected: Object::INTEGER(6),
+            },
+            VmTestCase {
+                input: "let a = 10; a += 5; a -= 3; a *= 2; a /= 4; a %= 4; a".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: r#"let s = "a"; s += "b"; s"#.to_string(),
+                expected: Object::STRING("ab".to_string()),
+            },
+            VmTestCase {
+                input: "let f = fn() { let a = 1; a += 1; a }; f()".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "let a = 1; let f = fn() { a = 5 }; f(); a".to_string(),
+                expected: Object::INTEGER(5),
+            },
+            VmTestCase {
+                input: r"
+                let i = 0;
+                let sum = 0;
+                while (i < 5) {
+                    sum += i;
+                    i += 1;
+                }
+                sum"
+                .to_string(),
+                expected: Object::INTEGER(10),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_assign_captured_variables() {
+        let tests = vec![
+            VmTestCase {
+                input: r"
+                let counter = fn() {
+                    let n = 0;
+                    fn() { n += 1; n }
+                };
+                let c = counter();
+                c();
+                c();
+                c()"
+                .to_string(),
+                expected: Object::INTEGER(3),
+            },
+            // Each call creates a new counter
+            VmTestCase {
+                input: r"
+                let counter = fn() {
+                    let n = 0;
+                    fn() { n += 1; n }
+                };
+                let a = counter();
+                let b = counter();
+                a();
+                a();
+                b()"
+                .to_string(),
+                expected: Object::INTEGER(1),
+            },
+            // The function and the closure share the variable
+            VmTestCase {
+                input: r"
+                let f = fn() {
+                    let x = 1;
+                    let g = fn() { x = x * 10 };
+                    g();
+                    x += 1;
+                    g();
+                    x
+                };
+                f()"
+                .to_string(),
+                expected: Object::INTEGER(110),
+            },
+            // Closures created by the same call share the variable
+            VmTestCase {
+                input: r"
+                let pair = fn() {
+                    let n = 0;
+                    [fn() { n += 1 }, fn() { n }]
+                };
+                let p = pair();
+                p[0]();
+                p[0]();
+                p[1]()"
+                    .to_string(),
+                expected: Object::INTEGER(2),
+            },
+            // A variable captured through two levels of functions
+            VmTestCase {
+                input: r"
+                let f = fn(a) {
+                    let g = fn() { fn() { a += 1 } };
+                    g()();
+                    g()();
+                    a
+                };
+                f(1)"
+                    .to_string(),
+                expected: Object::INTEGER(3),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_assign_function_name() {
+        let tests = vec![
+            VmTestCase {
+                input: "let f = fn() { f = 2; f }; f()".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            // The variable that holds the function changes
+            VmTestCase {
+                input: "let f = fn() { f = 2; 1 }; f(); f".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "let g = fn() { let f = fn() { f = 2; 1 }; f(); f }; g()".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "let f = fn() { let h = fn() { f = 3 }; h(); f }; f()".to_string(),
+                expected: Object::INTEGER(3),
+            },
+            // Calls made before the assignment still recurse
+            VmTestCase {
+                input: r"
+                let f = fn(n) {
+                    if (n == 0) { f = 0; return 1; }
+                    n * f(n - 1)
+                };
+                [f(5), f]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![Object::INTEGER(120), Object::INTEGER(0)]),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_assign_indices() {
+        let tests = vec![
+            VmTestCase {
+                input: "let a = [1, 2, 3]; a[1] = 5; a".to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::INTEGER(1),
+                    Object::INTEGER(5),
+                    Object::INTEGER(3),
+                ]),
+            },
+            VmTestCase {
+                input: "let a = [1, 2, 3]; a[2] += 10; a[2]".to_string(),
+                expected: Object::INTEGER(13),
+            },
+            VmTestCase {
+                input: "let a = [[1, 2], [3]]; a[0][1] *= 4; a[0][1]".to_string(),
+                expected: Object::INTEGER(8),
+            },
+            VmTestCase {
+                input: r#"let h = {"k": 1}; h["k"] -= 3; h["z"] = 2; h["k"] + h["z"]"#.to_string(),
+                expected: Object::INTEGER(0),
+            },
+            VmTestCase {
+                input: r#"let h = {"a": [1]}; h["a"][0] = 7; h["a"][0]"#.to_string(),
+                expected: Object::INTEGER(7),
+            },
+            VmTestCase {
+                input: "let a = [1]; a[0] = 2".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            // Arrays and hashes are values, so a copy is not changed
+            VmTestCase {
+                input: "let a = [1]; let b = a; b[0] = 2; a[0]".to_string(),
+                expected: Object::INTEGER(1),
+            },
+            VmTestCase {
+                input: "let f = fn(a) { a[0] = 2; a }; let a = [1]; f(a)[0] + a[0]".to_string(),
+                expected: Object::INTEGER(3),
+            },
+            VmTestCase {
+                input: r"
+                let f = fn() {
+                    let a = [0, 0];
+                    let g = fn(i) { a[i] += 1 };
+                    g(0);
+                    g(1);
+                    g(1);
+                    a
+                };
+                f()"
+                .to_string(),
+                expected: Object::ARRAY(vec![Object::INTEGER(1), Object::INTEGER(2)]),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_assignment_errors() {
+        let tests = vec![
+            (
+                "let a = [1, 2]; a[2] = 3",
+                "index 2 out of bounds for an array of length 2",
+            ),
+            (
+                "let a = [1]; a[-1] = 3",
+                "index -1 out of bounds for an array of length 1",
+            ),
+            ("let a = {}; a[[1]] = 3", "unusable as hash key: ARRAY"),
+            (
+                r#"let a = "ab"; a[0] = "c""#,
+                "index assignment not supported: STRING[INTEGER]",
+            ),
+            (
+                r#"let h = {}; h["k"] += 1"#,
+                "type mismatch: NULL + INTEGER",
+            ),
+            ("let a = 1; a += true", "type mismatch: INTEGER + BOOLEAN"),
+        ];
+
+        run_vm_error_tests(tests);
+    }
+}
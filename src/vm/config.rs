@@ -0,0 +1,32 @@
+use crate::budget::Budget;
+
+/// Limits on the resources a program can use, to run code that is not trusted.
+///
+/// Going over one of them stops the VM with a [`MonkeyError::Limit`] that
+/// tells which one it was.
+///
+/// [`MonkeyError::Limit`]: crate::errors::MonkeyError::Limit
+#[derive(Debug, Clone)]
+pub struct VmConfig {
+    /// Values the stack can hold, arguments and locals included.
+    pub max_stack: usize,
+    /// Calls that can be running at the same time, the main program included.
+    pub max_frames: usize,
+    /// Strings, arrays, hashmaps and closures the program can create over its
+    /// whole run, `None` for no limit. Objects that are no longer used still
+    /// count: it bounds the work spent allocating, not the memory in use.
+    pub max_allocations: Option<usize>,
+    /// Fuel, one unit per instruction, deadline and cancellation.
+    pub budget: Budget,
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self {
+            max_stack: 2048,
+            max_frames: 1024,
+            max_allocations: None,
+            budget: Budget::default(),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        object::Object,
+        vm::test_utils::{run_vm_error_tests, run_vm_tests, VmTestCase},
+    };
+
+    #[test]
+    fn test_float_arithmetic() {
+        let tests = vec![
+            VmTestCase {
+                input: "1.5 + 2.25".to_string(),
+                expected: Object::FLOAT(3.75),
+            },
+            VmTestCase {
+                input: "-2.5 * 2".to_string(),
+                expected: Object::FLOAT(-5.0),
+            },
+            VmTestCase {
+                input: "1 / 4.0".to_string(),
+                expected: Object::FLOAT(0.25),
+            },
+            VmTestCase {
+                input: "7.5 % 2".to_string(),
+                expected: Object::FLOAT(1.5),
+            },
+            VmTestCase {
+                input: "let f = fn(x) { x * 1e-3 }; f(500)".to_string(),
+                expected: Object::FLOAT(0.5),
+            },
+            VmTestCase {
+                input: "[1 == 1.0, 1.5 > 1, 2 <= 1.99, -0.0 == 0.0]".to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::BOOLEAN(true),
+                    Object::BOOLEAN(true),
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(true),
+                ]),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_mixed_comparisons_are_exact() {
+        let tests = vec![
+            // 2^53 + 1 is not a float, it must not be rounded to 2^53
+            VmTestCase {
+                input: "[9007199254740993 == 9007199254740992.0, 9007199254740993 > 9007199254740992.0]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![Object::BOOLEAN(false), Object::BOOLEAN(true)]),
+            },
+            VmTestCase {
+                input: "[9007199254740992 == 9007199254740992.0, 1 <= 1.5, -1 >= -1.5, 2.5 < 3]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![Object::BOOLEAN(true); 4]),
+            },
+            // The largest integer is below 2^63, the smallest one is -2^63
+            VmTestCase {
+                input: "let max = 9223372036854775807; [max == 9223372036854775808.0, max < 9223372036854775808.0, -max - 1 == -9223372036854775808.0, -max - 1 > -1e19]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(true),
+                    Object::BOOLEAN(true),
+                    Object::BOOLEAN(true),
+                ]),
+            },
+            VmTestCase {
+                input: r#"let nan = float("NaN"); [1 == nan, 1 != nan, 1 < nan, nan >= 1]"#
+                    .to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(true),
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(false),
+                ]),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_conversion_builtins() {
+        let tests = vec![
+            VmTestCase {
+                input: r#"[int(3.99), int(-3.99), int("42"), int(7)]"#.to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::INTEGER(3),
+                    Object::INTEGER(-3),
+                    Object::INTEGER(42),
+                    Object::INTEGER(7),
+                ]),
+            },
+            VmTestCase {
+                input: r#"[float(3), float(2.5), float("1e3")]"#.to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::FLOAT(3.0),
+                    Object::FLOAT(2.5),
+                    Object::FLOAT(1000.0),
+                ]),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_float_errors() {
+        run_vm_error_tests(vec![
+            ("1.5 / 0", "division by zero"),
+            ("1 % 0.0", "division by zero"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            ("int(1e300)", "cannot convert 1e300 to INTEGER"),
+            (r#"float("pi")"#, "cannot convert \"pi\" to FLOAT"),
+            (
+                "int([])",
+                "argument to `int` not supported, must be INTEGER, FLOAT or STRING, got ARRAY",
+            ),
+            ("{1.5: 1}", "unusable as hash key: FLOAT"),
+            ("[1][1.0]", "index operator not supported: ARRAY[FLOAT]"),
+        ]);
+    }
+}
@@ -2,13 +2,8 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        compiler::Compiler,
         object::Object,
-        parser::parse,
-        vm::{
-            test_utils::{run_vm_tests, VmTestCase},
-            VM,
-        },
+        vm::test_utils::{run_vm_error_tests, run_vm_tests, VmTestCase},
     };
 
     #[test]
@@ -238,49 +233,20 @@ mod tests {
 
     #[test]
     fn test_calling_functions_with_wrong_arguments() {
-        let tests = vec![
-            VmTestCase {
-                input: r"
-                    fn() { 1; }(1);"
-                    .to_string(),
-                expected: Object::ERROR("Wrong number of arguments: want=0, got=1".to_string()),
-            },
-            VmTestCase {
-                input: r"
-                    fn(a) { a; }();"
-                    .to_string(),
-                expected: Object::ERROR("Wrong number of arguments: want=1, got=0".to_string()),
-            },
-            VmTestCase {
-                input: r"
-                    fn(a, b) { a + b; }(1);"
-                    .to_string(),
-                expected: Object::ERROR("Wrong number of arguments: want=2, got=1".to_string()),
-            },
-        ];
-
-        for test in tests {
-            println!("Running test: {}", test.input);
-            let program = parse(&test.input);
-            let mut compiler = Compiler::new();
-            compiler.compile(program).unwrap();
-            let bytecode = compiler.bytecode();
-
-            let mut vm = VM::new(bytecode);
-            match vm.run() {
-                Ok(()) => {
-                    panic!("Expected error, but got no error");
-                }
-                Err(e) => match test.expected {
-                    Object::ERROR(msg) => {
-                        assert_eq!(e, msg);
-                    }
-                    _ => {
-                        unreachable!("Poorly written test, the expected value should be an error");
-                    }
-                },
-            }
-        }
+        run_vm_error_tests(vec![
+            (
+                "fn() { 1; }(1);",
+                "wrong number of arguments: want=0, got=1",
+            ),
+            (
+                "fn(a) { a; }();",
+                "wrong number of arguments: want=1, got=0",
+            ),
+            (
+                "fn(a, b) { a + b; }(1);",
+                "wrong number of arguments: want=2, got=1",
+            ),
+        ]);
     }
 
     #[test]
@@ -298,14 +264,6 @@ mod tests {
                 input: r#"len("hello world")"#.to_string(),
                 expected: Object::INTEGER(11),
             },
-            VmTestCase {
-                input: r"len(1)".to_string(),
-                expected: Object::ERROR("argument to `len` not supported, got INTEGER".to_string()),
-            },
-            VmTestCase {
-                input: r#"len("one", "two")"#.to_string(),
-                expected: Object::ERROR("wrong number of arguments. got=2, want=1".to_string()),
-            },
             VmTestCase {
                 input: r"len([1, 2, 3])".to_string(),
                 expected: Object::INTEGER(3),
@@ -314,10 +272,6 @@ mod tests {
                 input: r"len([])".to_string(),
                 expected: Object::INTEGER(0),
             },
-            VmTestCase {
-                input: r"len([1, 2, 3], [4, 5, 6])".to_string(),
-                expected: Object::ERROR("wrong number of arguments. got=2, want=1".to_string()),
-            },
             VmTestCase {
                 input: r"first([1, 2, 3])".to_string(),
                 expected: Object::INTEGER(1),
@@ -326,12 +280,6 @@ mod tests {
                 input: r"first([])".to_string(),
                 expected: Object::NULL,
             },
-            VmTestCase {
-                input: r"first(1)".to_string(),
-                expected: Object::ERROR(
-                    "argument to `first` not supported, must be ARRAY, got INTEGER".to_string(),
-                ),
-            },
             VmTestCase {
                 input: r"last([1, 2, 3])".to_string(),
                 expected: Object::INTEGER(3),
@@ -340,12 +288,6 @@ mod tests {
                 input: r"last([])".to_string(),
                 expected: Object::NULL,
             },
-            VmTestCase {
-                input: r"last(1)".to_string(),
-                expected: Object::ERROR(
-                    "argument to `last` not supported, must be ARRAY, got INTEGER".to_string(),
-                ),
-            },
             VmTestCase {
                 input: r"rest([1, 2, 3])".to_string(),
                 expected: Object::ARRAY(vec![Object::INTEGER(2), Object::INTEGER(3)]),
@@ -358,15 +300,113 @@ mod tests {
                 input: r"push([], 1)".to_string(),
                 expected: Object::ARRAY(vec![Object::INTEGER(1)]),
             },
-            VmTestCase {
-                input: r"push(1, 1)".to_string(),
-                expected: Object::ERROR(
-                    "argument to `push` not supported, must be ARRAY, got INTEGER".to_string(),
-                ),
-            },
         ];
 
         run_vm_tests(tests);
+        run_vm_error_tests(vec![
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (
+                r#"len("one", "two")"#,
+                "wrong number of arguments: want=1, got=2",
+            ),
+            (
+                "len([1, 2, 3], [4, 5, 6])",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            (
+                "first(1)",
+                "argument to `first` not supported, must be ARRAY, got INTEGER",
+            ),
+            (
+                "last(1)",
+                "argument to `last` not supported, must be ARRAY, got INTEGER",
+            ),
+            (
+                "push(1, 1)",
+                "argument to `push` not supported, must be ARRAY, got INTEGER",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_hash_builtin_functions() {
+        let tests = vec![
+            (
+                r#"keys({"b": 1, "a": 2, 10: 3, 2: 4, true: 5, false: 6})"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(2),
+                    Object::INTEGER(10),
+                    Object::BOOLEAN(false),
+                    Object::BOOLEAN(true),
+                    Object::STRING("a".to_string()),
+                    Object::STRING("b".to_string()),
+                ]),
+            ),
+            (
+                r#"values({"b": 1, "a": 2, 10: 3, 2: 4, true: 5, false: 6})"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(4),
+                    Object::INTEGER(3),
+                    Object::INTEGER(6),
+                    Object::INTEGER(5),
+                    Object::INTEGER(2),
+                    Object::INTEGER(1),
+                ]),
+            ),
+            (r"keys({})", Object::ARRAY(vec![])),
+            (r"values({})", Object::ARRAY(vec![])),
+            (r#"len({"a": 1, "b": 2})"#, Object::INTEGER(2)),
+            (r"len({})", Object::INTEGER(0)),
+            (
+                r#"let h = {"a": 1}; let g = add(h, "b", 2); [len(h), len(g), g["b"]]"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(1),
+                    Object::INTEGER(2),
+                    Object::INTEGER(2),
+                ]),
+            ),
+            (r#"add({"a": 1}, "a", 5)["a"]"#, Object::INTEGER(5)),
+            (
+                r#"let h = {"a": 1, "b": 2}; let g = delete(h, "a"); [len(h), len(g), g["a"], g["b"]]"#,
+                Object::ARRAY(vec![
+                    Object::INTEGER(2),
+                    Object::INTEGER(1),
+                    Object::NULL,
+                    Object::INTEGER(2),
+                ]),
+            ),
+            (r#"len(delete({"a": 1}, "z"))"#, Object::INTEGER(1)),
+            (r#"contains({"a": 1}, "a")"#, Object::BOOLEAN(true)),
+            (r#"contains({"a": 1}, "b")"#, Object::BOOLEAN(false)),
+            (r"contains({1: 1}, true)", Object::BOOLEAN(false)),
+        ];
+
+        run_vm_tests(
+            tests
+                .into_iter()
+                .map(|(input, expected)| VmTestCase {
+                    input: input.to_string(),
+                    expected,
+                })
+                .collect(),
+        );
+        run_vm_error_tests(vec![
+            (
+                "keys([1])",
+                "argument to `keys` not supported, must be HASHMAP, got ARRAY",
+            ),
+            (
+                "values(1)",
+                "argument to `values` not supported, must be HASHMAP, got INTEGER",
+            ),
+            ("add({}, 1)", "wrong number of arguments: want=3, got=2"),
+            ("add({}, [1], 1)", "unusable as hash key: ARRAY"),
+            (
+                r#"delete("a", "a")"#,
+                "argument to `delete` not supported, must be HASHMAP, got STRING",
+            ),
+            ("contains({}, [])", "unusable as hash key: ARRAY"),
+        ]);
     }
 
     #[test]
@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        budget::{Budget, CancelHandle},
+        compiler::test_utils::compile,
+        errors::Limit,
+        object::Object,
+        vm::{config::VmConfig, VM},
+    };
+
+    fn run(input: &str, config: VmConfig) -> Result<(), (Option<Limit>, String)> {
+        let mut vm = VM::with_config(compile(input), config);
+        vm.run()
+            .map_err(|err| (err.error.limit(), err.error.to_string()))
+    }
+
+    #[test]
+    fn test_limits() {
+        let tests = vec![
+            (
+                "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(100)",
+                VmConfig {
+                    max_frames: 50,
+                    ..Default::default()
+                },
+                Limit::Frames,
+                "Stack overflow: at most 50 calls can be running",
+            ),
+            (
+                "let f = fn(a, b, c, d) { if (a == 0) { 0 } else { 1 + f(a - 1, b, c, d) } }; f(100, 1, 2, 3)",
+                VmConfig {
+                    max_stack: 64,
+                    ..Default::default()
+                },
+                Limit::Stack,
+                "Stack overflow: the stack holds at most 64 values",
+            ),
+            (
+                "[1, [2, 3], \"a\" + \"b\", fn() { 1 }, {}, len([])]",
+                VmConfig {
+                    max_allocations: Some(4),
+                    ..Default::default()
+                },
+                Limit::Allocations,
+                "Out of allocations: a program can create at most 4 objects",
+            ),
+            // Arrays that were dropped still count
+            (
+                "let i = 0; while (i < 10) { [i]; i += 1; }",
+                VmConfig {
+                    max_allocations: Some(5),
+                    ..Default::default()
+                },
+                Limit::Allocations,
+                "Out of allocations: a program can create at most 5 objects",
+            ),
+            (
+                "let loop = fn(x) { loop(push(x, 1)) }; loop([])",
+                VmConfig {
+                    max_allocations: Some(1000),
+                    ..Default::default()
+                },
+                Limit::Allocations,
+                "Out of allocations: a program can create at most 1000 objects",
+            ),
+            (
+                "while (true) {}",
+                VmConfig {
+                    budget: Budget {
+                        fuel: Some(100),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Limit::OutOfFuel,
+                "Out of fuel: a program can run at most 100 instructions",
+            ),
+            (
+                "while (true) {}",
+                VmConfig {
+                    budget: Budget {
+                        deadline: Some(Instant::now() + Duration::from_millis(20)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Limit::Interrupted,
+                "Interrupted",
+            ),
+        ];
+
+        for (input, config, limit, message) in tests {
+            assert_eq!(
+                run(input, config),
+                Err((Some(limit), message.to_string())),
+                "wrong error for {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_exactly_at_limits() {
+        let config = VmConfig {
+            max_stack: 4,
+            max_frames: 2,
+            max_allocations: Some(2),
+            budget: Budget {
+                fuel: Some(10),
+                ..Default::default()
+            },
+        };
+        assert_eq!(run("let f = fn(a) { [a, \"x\"] }; f(1)", config), Ok(()));
+    }
+
+    #[test]
+    fn test_other_errors_have_no_limit() {
+        let config = VmConfig {
+            budget: Budget {
+                fuel: Some(100),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            run("1 / 0", config),
+            Err((None, "division by zero".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cancel = CancelHandle::new();
+        let config = VmConfig {
+            budget: Budget {
+                cancel: Some(cancel.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        });
+
+        assert_eq!(
+            run("while (true) {}", config),
+            Err((Some(Limit::Interrupted), "Interrupted".to_string()))
+        );
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_state_after_running_out_of_fuel() {
+        let config = VmConfig {
+            budget: Budget {
+                fuel: Some(50),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut vm = VM::with_config(compile("let a = 1; let b = 2; while (true) {}"), config);
+
+        let err = vm.run().unwrap_err();
+        assert_eq!(err.error.limit(), Some(Limit::OutOfFuel));
+        assert_eq!(vm.fuel_used(), 51);
+        assert_eq!(*vm.globals[0], Object::INTEGER(1));
+        assert_eq!(*vm.globals[1], Object::INTEGER(2));
+    }
+
+    #[test]
+    fn test_globals_grow() {
+        let mut vm = VM::new(compile("let a = 1; let b = 2;"));
+        assert!(vm.globals.is_empty());
+        vm.run().unwrap();
+        assert_eq!(vm.globals.len(), 2);
+    }
+}
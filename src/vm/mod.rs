@@ -1,24 +1,35 @@
+mod assignment_tests;
+pub mod config;
+mod float_tests;
 mod function_tests;
+mod limit_tests;
 mod shadowing_tests;
+mod tail_call_tests;
 mod test_utils;
+mod trace_tests;
+mod try_tests;
+pub mod vm_errors;
 mod vm_tests;
 mod while_statemets_tests;
+mod wide_operand_tests;
 
 use crate::{
+    budget::Meter,
     compiler::{
-        code::{read_u16, Opcode},
+        code::{read_instruction, read_u16, Opcode},
         Bytecode,
     },
-    object::{
-        builtins::BuiltinFunction,
-        {Closure, CompiledFunction, Object, FALSE, NULL, TRUE},
+    errors::{Limit, MonkeyError},
+    object::{Closure, CompiledFunction, Object, FALSE, NULL, TRUE},
+    runtime::Runtime,
+    vm::{
+        config::VmConfig,
+        vm_errors::{RuntimeError, TraceEntry},
     },
 };
 use num_traits::FromPrimitive;
-use std::{collections::HashMap, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
 
-const STACK_SIZE: usize = 2048;
-const MAX_FRAMES: usize = 1024;
 pub const GLOBALS_SIZE: usize = 65536;
 
 #[derive(Debug)]
@@ -26,6 +37,22 @@ struct Frame {
     function: Closure,
     ip: i32,
     base_pointer: usize,
+    /// Handlers of the `try` statements the frame is in, the innermost one
+    /// last.
+    handlers: Vec<Handler>,
+    /// Locals captured by a closure: once captured, a local lives in its cell
+    /// instead of its stack slot.
+    cells: Vec<Option<Rc<RefCell<Object>>>>,
+}
+
+/// Where to go when an error happens inside a `try` body.
+#[derive(Debug, Clone, Copy)]
+struct Handler {
+    /// Offset of the first instruction of the `catch` block.
+    ip: usize,
+    /// Stack pointer when the `try` started, the values pushed since then are
+    /// dropped.
+    sp: usize,
 }
 
 impl Frame {
@@ -34,6 +61,8 @@ impl Frame {
             function,
             ip: -1,
             base_pointer,
+            handlers: Vec::new(),
+            cells: Vec::new(),
         }
     }
 
@@ -52,39 +81,47 @@ pub struct VM {
 
     frames: Vec<Frame>,
     frames_index: usize,
+
+    config: VmConfig,
+    /// Strings, arrays, hashmaps and closures created so far, including the
+    /// ones that were dropped.
+    allocations: usize,
+    meter: Meter,
+    pub(crate) runtime: Runtime,
 }
 
 impl VM {
     pub fn new(bytecode: Bytecode) -> Self {
+        Self::with_config(bytecode, VmConfig::default())
+    }
+
+    /// Creates a VM that stops the program when it goes over one of the limits
+    /// of `config`.
+    pub fn with_config(bytecode: Bytecode, config: VmConfig) -> Self {
         let main_function = CompiledFunction {
             instructions: bytecode.instructions.data,
             num_locals: 0,
             num_parameters: 0,
+            debug: bytecode.debug,
         };
         let main_closure = Closure::new(main_function);
         let main_frame = Frame::new(main_closure, 0);
-        let mut frames = Vec::with_capacity(MAX_FRAMES);
-        frames.push(main_frame);
         Self {
             constants: bytecode.constants.into_iter().map(Rc::new).collect(),
 
             sp: 0,
 
-            // TODO: Improve this
-            stack: {
-                let mut v = Vec::with_capacity(STACK_SIZE);
-                (0..STACK_SIZE).for_each(|_| v.push(Rc::new(NULL)));
-                v
-            },
+            // Both grow when the program needs more room
+            stack: Vec::new(),
+            globals: Vec::new(),
 
-            globals: {
-                let mut v = Vec::with_capacity(GLOBALS_SIZE);
-                (0..GLOBALS_SIZE).for_each(|_| v.push(Rc::new(NULL)));
-                v
-            },
-
-            frames,
+            frames: vec![main_frame],
             frames_index: 1,
+
+            meter: Meter::new(config.budget.clone()),
+            config,
+            allocations: 0,
+            runtime: Runtime::default(),
         }
     }
 
@@ -94,18 +131,88 @@ impl VM {
         vm
     }
 
+    /// Runs the program, a failure comes with the trace of the calls that led
+    /// to it.
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let result = match self.run_instructions() {
+                Ok(()) => return Ok(()),
+                Err(error) if error.is_catchable() && self.in_try() => self.catch(error),
+                Err(error) => Err(error),
+            };
+            result.map_err(|error| RuntimeError {
+                error,
+                trace: self.trace(),
+            })?;
+        }
+    }
+
+    /// Whether a running function is inside a `try` body.
+    fn in_try(&self) -> bool {
+        self.frames[..self.frames_index]
+            .iter()
+            .any(|frame| !frame.handlers.is_empty())
+    }
+
+    /// Unwinds to the innermost handler and jumps to its `catch` block with
+    /// the exception on the stack.
+    fn catch(&mut self, error: MonkeyError) -> Result<(), MonkeyError> {
+        while self.current_frame().handlers.is_empty() {
+            self.pop_frame();
+        }
+        let handler = self.current_frame().handlers.pop().unwrap();
+        self.current_frame().ip = handler.ip as i32 - 1;
+        self.sp = handler.sp;
+
+        let exception = error.into_exception();
+        if let Object::HASHMAP(_) = exception {
+            self.allocate()?;
+        }
+        self.push(Rc::new(exception))
+    }
+
+    /// The running functions, the innermost one first.
+    fn trace(&self) -> Vec<TraceEntry> {
+        self.frames[..self.frames_index]
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, frame)| {
+                let debug = &frame.function.function.debug;
+                let function = match (&debug.name, i) {
+                    (_, 0) => String::from("<main>"),
+                    (Some(name), _) => name.clone(),
+                    (None, _) => String::from("<anonymous>"),
+                };
+                TraceEntry {
+                    function,
+                    position: debug.position(frame.ip.max(0) as usize),
+                }
+            })
+            .collect()
+    }
+
     #[allow(clippy::too_many_lines)]
-    pub fn run(&mut self) -> Result<(), String> {
+    fn run_instructions(&mut self) -> Result<(), MonkeyError> {
         while self.current_frame().ip < self.current_frame().get_instructions().len() as i32 - 1 {
+            self.count_instruction()?;
             self.current_frame().ip += 1;
             let ip = self.current_frame().ip as usize;
             let ins = self.current_frame().get_instructions();
-            let op = Opcode::from_u8(ins[ip]).ok_or(format!("Unknown opcode {}", ins[ip]))?;
+            let op = Opcode::from_u8(ins[ip]).ok_or_else(|| {
+                MonkeyError::InvalidBytecode(format!("Unknown opcode {}", ins[ip]))
+            })?;
             match op {
                 Opcode::Constant => {
                     let const_index = read_u16(&ins[ip + 1..]);
                     self.current_frame().ip += 2;
-                    self.push(self.constants[const_index as usize].clone())?;
+                    self.push(self.constant(const_index as usize)?)?;
+                }
+                Opcode::Wide => {
+                    let (op, operands, size) =
+                        read_instruction(&ins[ip..]).map_err(MonkeyError::InvalidBytecode)?;
+                    self.current_frame().ip += size as i32 - 1;
+                    self.execute_wide(op, &operands)?;
                 }
                 Opcode::Add
                 | Opcode::Sub
@@ -119,7 +226,9 @@ impl VM {
                 Opcode::Equal
                 | Opcode::NotEqual
                 | Opcode::GreaterThan
-                | Opcode::GreaterEqualThan => {
+                | Opcode::GreaterEqualThan
+                | Opcode::LessThan
+                | Opcode::LessEqualThan => {
                     self.execute_comparison(op)?;
                 }
                 Opcode::Pop => {
@@ -144,10 +253,7 @@ impl VM {
                 Opcode::JumpNotTruthy => {
                     let pos = i32::from(read_u16(&ins[ip + 1..]));
                     self.current_frame().ip += 2;
-                    let condition = self.pop()?;
-                    if !self.is_truthy(&condition) {
-                        self.current_frame().ip = pos - 1;
-                    }
+                    self.jump_not_truthy(pos)?;
                 }
                 Opcode::Null => {
                     self.push(Rc::new(NULL))?;
@@ -155,80 +261,80 @@ impl VM {
                 Opcode::SetGlobal => {
                     let global_index = read_u16(&ins[ip + 1..]) as usize;
                     self.current_frame().ip += 2;
-                    let value = self.pop()?;
-                    self.globals[global_index] = value;
+                    self.set_global(global_index)?;
                 }
 
                 Opcode::GetGlobal => {
                     let global_index = read_u16(&ins[ip + 1..]) as usize;
                     self.current_frame().ip += 2;
-                    self.push(self.globals[global_index].clone())?;
+                    self.get_global(global_index)?;
                 }
                 Opcode::SetLocal => {
                     let local_index = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
-                    let value = self.pop()?;
-                    let base_pointer = self.current_frame().base_pointer;
-                    self.stack[base_pointer + local_index] = value;
+                    self.set_local(local_index)?;
                 }
                 Opcode::GetLocal => {
                     let local_index = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
-                    let base_pointer = self.current_frame().base_pointer;
-                    let value = Rc::clone(&self.stack[base_pointer + local_index]);
-                    self.push(value)?;
+                    self.get_local(local_index)?;
                 }
 
                 Opcode::GetBuiltin => {
                     let builtin_index = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
-
-                    let def = BuiltinFunction::get_builtin_by_id(builtin_index)
-                        .ok_or(format!("Unknown builtin function id {builtin_index}"))?;
-
-                    self.push(Rc::new(def))?;
+                    self.get_builtin(builtin_index)?;
                 }
                 Opcode::Array => {
                     let num_elements = read_u16(&ins[ip + 1..]) as usize;
                     self.current_frame().ip += 2;
-                    let array = self.build_array(self.sp - num_elements, self.sp)?;
-                    self.sp -= num_elements;
-                    self.push(array)?;
+                    self.push_array(num_elements)?;
                 }
                 Opcode::HashMap => {
                     let num_elements = read_u16(&ins[ip + 1..]) as usize;
                     self.current_frame().ip += 2;
-                    let hashmap = self.build_hashmap(self.sp - num_elements, self.sp)?;
-                    self.sp -= num_elements;
-                    self.push(hashmap)?;
+                    self.push_hashmap(num_elements)?;
                 }
                 Opcode::Index => {
                     let index = self.pop()?;
                     let left = self.pop()?;
                     self.execute_index_expression(&left, &index)?;
                 }
+                Opcode::SetIndex => {
+                    let depth = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+                    self.execute_set_index(depth)?;
+                }
+                Opcode::PeekIndex => {
+                    let depth = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+                    self.execute_peek_index(depth)?;
+                }
                 Opcode::Call => {
                     let num_args = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
 
                     self.execute_call(num_args)?;
                 }
+                Opcode::TailCall => {
+                    let num_args = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+
+                    self.execute_tail_call(num_args)?;
+                }
                 Opcode::ReturnValue => {
                     let return_value = self.pop()?;
-
-                    match self.pop_frame() {
-                        Some(frame) => self.sp = frame.base_pointer - 1,
-                        None => Err("There was no frame")?,
+                    if self.frames_index == 1 {
+                        // A `return` outside of any function ends the program, the
+                        // returned value is the last one popped
+                        return Ok(());
                     }
 
+                    self.return_from_frame()?;
                     self.push(return_value)?;
                 }
                 Opcode::Return => {
-                    match self.pop_frame() {
-                        Some(frame) => self.sp = frame.base_pointer - 1,
-                        None => Err("There was no frame")?,
-                    }
-
+                    self.return_from_frame()?;
                     self.push(Rc::new(NULL))?;
                 }
                 Opcode::Closure => {
@@ -242,20 +348,247 @@ impl VM {
                 Opcode::GetFree => {
                     let free_index = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
-
-                    let free = self.current_frame().function.free[free_index].clone();
-                    self.push(Rc::new(free))?;
+                    self.get_free(free_index)?;
+                }
+                Opcode::SetFree => {
+                    let free_index = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+                    self.set_free(free_index)?;
+                }
+                Opcode::CaptureLocal => {
+                    let local_index = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+                    let cell = self.local_cell(local_index)?;
+                    self.capture(cell)?;
+                }
+                Opcode::CaptureFree => {
+                    let free_index = ins[ip + 1] as usize;
+                    self.current_frame().ip += 1;
+                    let cell = self.free_cell(free_index)?;
+                    self.capture(cell)?;
+                }
+                Opcode::CaptureCurrentClosure => {
+                    let current_closure = self.current_frame().function.clone();
+                    self.capture(Rc::new(RefCell::new(Object::CLOSURE(current_closure))))?;
                 }
                 Opcode::CurrentClosure => {
                     let current_closure = self.current_frame().function.clone();
                     self.push(Rc::new(Object::CLOSURE(current_closure)))?;
                 }
+                Opcode::Try => {
+                    let pos = read_u16(&ins[ip + 1..]) as usize;
+                    self.current_frame().ip += 2;
+                    self.push_handler(pos);
+                }
+                Opcode::EndTry => {
+                    self.current_frame().handlers.pop();
+                }
+                Opcode::Throw => {
+                    let value = self.pop()?;
+                    return Err(MonkeyError::Thrown(Box::new(value.as_ref().clone())));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs an instruction that followed `OpWide`, the frame is already past
+    /// its operands.
+    fn execute_wide(&mut self, op: Opcode, operands: &[i32]) -> Result<(), MonkeyError> {
+        let operand = |i: usize| operands[i] as usize;
+        match op {
+            Opcode::Constant => {
+                let constant = self.constant(operand(0))?;
+                self.push(constant)
+            }
+            Opcode::Jump => {
+                self.current_frame().ip = operands[0] - 1;
+                Ok(())
+            }
+            Opcode::JumpNotTruthy => self.jump_not_truthy(operands[0]),
+            Opcode::SetGlobal => self.set_global(operand(0)),
+            Opcode::GetGlobal => self.get_global(operand(0)),
+            Opcode::SetLocal => self.set_local(operand(0)),
+            Opcode::GetLocal => self.get_local(operand(0)),
+            Opcode::GetBuiltin => self.get_builtin(operand(0)),
+            Opcode::Array => self.push_array(operand(0)),
+            Opcode::HashMap => self.push_hashmap(operand(0)),
+            Opcode::Call => self.execute_call(operand(0)),
+            Opcode::TailCall => self.execute_tail_call(operand(0)),
+            Opcode::Closure => self.push_closure(operand(0), operand(1)),
+            Opcode::GetFree => self.get_free(operand(0)),
+            Opcode::SetFree => self.set_free(operand(0)),
+            Opcode::SetIndex => self.execute_set_index(operand(0)),
+            Opcode::PeekIndex => self.execute_peek_index(operand(0)),
+            Opcode::CaptureLocal => {
+                let cell = self.local_cell(operand(0))?;
+                self.capture(cell)
+            }
+            Opcode::CaptureFree => {
+                let cell = self.free_cell(operand(0))?;
+                self.capture(cell)
+            }
+            Opcode::Try => {
+                self.push_handler(operand(0));
+                Ok(())
+            }
+            _ => Err(MonkeyError::InvalidBytecode(format!("{op} cannot be wide"))),
+        }
+    }
+
+    fn push_handler(&mut self, ip: usize) {
+        let sp = self.sp;
+        self.current_frame().handlers.push(Handler { ip, sp });
+    }
+
+    fn jump_not_truthy(&mut self, pos: i32) -> Result<(), MonkeyError> {
+        let condition = self.pop()?;
+        if !self.is_truthy(&condition) {
+            self.current_frame().ip = pos - 1;
+        }
+        Ok(())
+    }
+
+    fn set_global(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let value = self.pop()?;
+        if index >= self.globals.len() {
+            self.globals.resize(index + 1, Rc::new(NULL));
+        }
+        self.globals[index] = value;
+        Ok(())
+    }
+
+    fn get_global(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let value = self
+            .globals
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| Rc::new(NULL));
+        self.push(value)
+    }
+
+    fn set_local(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let value = self.pop()?;
+        let frame = &self.frames[self.frames_index - 1];
+        match frame.cells.get(index) {
+            Some(Some(cell)) => *cell.borrow_mut() = value.as_ref().clone(),
+            _ => {
+                let slot = self
+                    .stack
+                    .get_mut(frame.base_pointer + index)
+                    .ok_or_else(|| Self::unknown_local(index))?;
+                *slot = value;
             }
         }
         Ok(())
     }
 
-    fn execute_binary_operation(&mut self, op: Opcode) -> Result<(), String> {
+    fn get_local(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let frame = &self.frames[self.frames_index - 1];
+        let value = match frame.cells.get(index) {
+            Some(Some(cell)) => Rc::new(cell.borrow().clone()),
+            _ => self
+                .stack
+                .get(frame.base_pointer + index)
+                .cloned()
+                .ok_or_else(|| Self::unknown_local(index))?,
+        };
+        self.push(value)
+    }
+
+    /// The cell of a local of the running function, created from the value
+    /// in its stack slot the first time a closure captures it.
+    fn local_cell(&mut self, index: usize) -> Result<Rc<RefCell<Object>>, MonkeyError> {
+        let frame = &mut self.frames[self.frames_index - 1];
+        let value = self
+            .stack
+            .get(frame.base_pointer + index)
+            .ok_or_else(|| Self::unknown_local(index))?;
+        if frame.cells.len() <= index {
+            frame.cells.resize(index + 1, None);
+        }
+        Ok(Rc::clone(frame.cells[index].get_or_insert_with(|| {
+            Rc::new(RefCell::new(value.as_ref().clone()))
+        })))
+    }
+
+    /// Adds a free variable to the closure on top of the stack.
+    fn capture(&mut self, cell: Rc<RefCell<Object>>) -> Result<(), MonkeyError> {
+        let top = self.sp.checked_sub(1).ok_or_else(Self::stack_underflow)?;
+        // The closure was just created, nothing else refers to it yet
+        match Rc::make_mut(&mut self.stack[top]) {
+            Object::CLOSURE(closure) => {
+                closure.add_free_variable(cell);
+                Ok(())
+            }
+            x => Err(MonkeyError::InvalidBytecode(format!(
+                "Closure expected, got {}",
+                x.get_type()
+            ))),
+        }
+    }
+
+    fn get_builtin(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let def = self.runtime.get_builtin_by_id(index).ok_or_else(|| {
+            MonkeyError::InvalidBytecode(format!("Unknown builtin function id {index}"))
+        })?;
+        self.push(Rc::new(def))
+    }
+
+    fn get_free(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let free = self.free_cell(index)?.borrow().clone();
+        self.push(Rc::new(free))
+    }
+
+    fn set_free(&mut self, index: usize) -> Result<(), MonkeyError> {
+        let value = self.pop()?;
+        *self.free_cell(index)?.borrow_mut() = value.as_ref().clone();
+        Ok(())
+    }
+
+    /// The cell of a free variable of the running closure.
+    fn free_cell(&self, index: usize) -> Result<Rc<RefCell<Object>>, MonkeyError> {
+        let free = &self.frames[self.frames_index - 1].function.free;
+        free.get(index).cloned().ok_or_else(|| {
+            MonkeyError::InvalidBytecode(format!(
+                "Unknown free variable {index}, the closure has {}",
+                free.len()
+            ))
+        })
+    }
+
+    fn constant(&self, index: usize) -> Result<Rc<Object>, MonkeyError> {
+        self.constants.get(index).cloned().ok_or_else(|| {
+            MonkeyError::InvalidBytecode(format!(
+                "Unknown constant {index}, the program has {}",
+                self.constants.len()
+            ))
+        })
+    }
+
+    fn push_array(&mut self, num_elements: usize) -> Result<(), MonkeyError> {
+        self.allocate()?;
+        let start = self
+            .sp
+            .checked_sub(num_elements)
+            .ok_or_else(Self::stack_underflow)?;
+        let array = self.build_array(start, self.sp)?;
+        self.sp = start;
+        self.push(array)
+    }
+
+    fn push_hashmap(&mut self, num_elements: usize) -> Result<(), MonkeyError> {
+        self.allocate()?;
+        let start = self
+            .sp
+            .checked_sub(num_elements)
+            .ok_or_else(Self::stack_underflow)?;
+        let hashmap = self.build_hashmap(start, self.sp)?;
+        self.sp = start;
+        self.push(hashmap)
+    }
+
+    fn execute_binary_operation(&mut self, op: Opcode) -> Result<(), MonkeyError> {
         let right = self.pop()?;
         let left = self.pop()?;
 
@@ -268,7 +601,11 @@ impl VM {
                     Opcode::Or => *left || *right,
                     Opcode::And => *left && *right,
                     _ => {
-                        return Err("Unsupported types for binary operation".to_string());
+                        return Err(Self::unsupported_binary_operation(
+                            &Object::BOOLEAN(*left),
+                            op,
+                            &Object::BOOLEAN(*right),
+                        ));
                     }
                 };
 
@@ -282,13 +619,14 @@ impl VM {
                 let result = match op {
                     Opcode::Add => s1.to_string() + s2,
                     _ => {
-                        return Err("Unsupported types for binary operation".to_string());
+                        return Err(Self::unsupported_binary_operation(&left, op, &right));
                     }
                 };
+                self.allocate()?;
 
                 self.push(Rc::new(Object::STRING(result)))?;
             }
-            _ => return Err("Unsupported types for binary operation".to_string()),
+            (left, right) => self.execute_binary_float_operation(left, right, op)?,
         }
         Ok(())
     }
@@ -298,36 +636,55 @@ impl VM {
         left: &Rc<Object>,
         right: &Rc<Object>,
         op: Opcode,
-    ) -> Result<(), String> {
+    ) -> Result<(), MonkeyError> {
         let left = self.cast_to_integer(left)?;
         let right = self.cast_to_integer(right)?;
 
         let result = match op {
-            Opcode::Add => left + right,
-            Opcode::Sub => left - right,
-            Opcode::Mul => left * right,
-            Opcode::Div => {
-                if right == 0 {
-                    Err("Division by zero".to_string())?
-                } else {
-                    left / right
-                }
-            }
-            Opcode::Modulo => {
-                if right == 0 {
-                    Err("Division by zero".to_string())?
-                } else {
-                    left % right
-                }
-            }
-            _ => unreachable!(),
-        };
+            Opcode::Add => left.checked_add(right),
+            Opcode::Sub => left.checked_sub(right),
+            Opcode::Mul => left.checked_mul(right),
+            Opcode::Div | Opcode::Modulo if right == 0 => Err(MonkeyError::DivisionByZero)?,
+            Opcode::Div => left.checked_div(right),
+            // Only the quotient of `i64::MIN / -1` overflows, the remainder is 0
+            Opcode::Modulo => Some(left.wrapping_rem(right)),
+            _ => Err(Self::unsupported_binary_operation(
+                &Object::INTEGER(left),
+                op,
+                &Object::INTEGER(right),
+            ))?,
+        }
+        .ok_or(MonkeyError::IntegerOverflow)?;
 
         self.push(Rc::new(Object::INTEGER(result)))?;
         Ok(())
     }
 
-    fn execute_comparison(&mut self, op: Opcode) -> Result<(), String> {
+    /// An operation between two floats, or an integer and a float which is
+    /// then converted to a float.
+    fn execute_binary_float_operation(
+        &mut self,
+        left: &Object,
+        right: &Object,
+        op: Opcode,
+    ) -> Result<(), MonkeyError> {
+        let (Some(x), Some(y)) = (left.as_float(), right.as_float()) else {
+            return Err(Self::unsupported_binary_operation(left, op, right));
+        };
+        let result = match op {
+            Opcode::Add => x + y,
+            Opcode::Sub => x - y,
+            Opcode::Mul => x * y,
+            Opcode::Div | Opcode::Modulo if y == 0.0 => Err(MonkeyError::DivisionByZero)?,
+            Opcode::Div => x / y,
+            Opcode::Modulo => x % y,
+            _ => Err(Self::unsupported_binary_operation(left, op, right))?,
+        };
+
+        self.push(Rc::new(Object::FLOAT(result)))
+    }
+
+    fn execute_comparison(&mut self, op: Opcode) -> Result<(), MonkeyError> {
         let right = self.pop()?;
         let left = self.pop()?;
 
@@ -342,9 +699,26 @@ impl VM {
                 Opcode::NotEqual => {
                     self.push(self.native_boolean_to_boolean_object(left != right))?;
                 }
-                _ => Err("Unsupported types for comparison".to_string())?,
+                _ => Err(Self::unsupported_binary_operation(&left, op, &right))?,
             },
-            _ => Err("Unsupported types for comparison".to_string())?,
+            (left, right) if left.as_float().is_some() && right.as_float().is_some() => {
+                let ordering = left.compare_numbers(right);
+                let result = match op {
+                    Opcode::Equal => ordering == Some(Ordering::Equal),
+                    Opcode::NotEqual => ordering != Some(Ordering::Equal),
+                    Opcode::GreaterThan => ordering == Some(Ordering::Greater),
+                    Opcode::GreaterEqualThan => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                    Opcode::LessThan => ordering == Some(Ordering::Less),
+                    Opcode::LessEqualThan => {
+                        matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+                    }
+                    _ => unreachable!(),
+                };
+                self.push(self.native_boolean_to_boolean_object(result))?;
+            }
+            (left, right) => Err(Self::unsupported_binary_operation(left, op, right))?,
         }
         Ok(())
     }
@@ -354,7 +728,7 @@ impl VM {
         left: &Rc<Object>,
         right: &Rc<Object>,
         op: Opcode,
-    ) -> Result<(), String> {
+    ) -> Result<(), MonkeyError> {
         let left = self.cast_to_integer(left)?;
         let right = self.cast_to_integer(right)?;
 
@@ -363,6 +737,8 @@ impl VM {
             Opcode::NotEqual => left != right,
             Opcode::GreaterThan => left > right,
             Opcode::GreaterEqualThan => left >= right,
+            Opcode::LessThan => left < right,
+            Opcode::LessEqualThan => left <= right,
             _ => unreachable!(),
         };
 
@@ -374,49 +750,48 @@ impl VM {
         Ok(())
     }
 
-    fn execute_bang_operation(&mut self) -> Result<(), String> {
+    fn execute_bang_operation(&mut self) -> Result<(), MonkeyError> {
         let operand = self.pop()?;
         let value = self.native_boolean_to_boolean_object(!self.is_truthy(&operand));
         self.push(value)?;
         Ok(())
     }
 
-    fn execute_minus_operation(&mut self) -> Result<(), String> {
+    fn execute_minus_operation(&mut self) -> Result<(), MonkeyError> {
         let operand = self.pop()?;
 
         match &*operand {
             Object::INTEGER(i) => {
-                self.push(Rc::new(Object::INTEGER(-i)))?;
+                let result = i.checked_neg().ok_or(MonkeyError::IntegerOverflow)?;
+                self.push(Rc::new(Object::INTEGER(result)))?;
             }
-            _ => {
-                return Err("Unsupported type for minus operation".to_string());
+            Object::FLOAT(x) => {
+                self.push(Rc::new(Object::FLOAT(-x)))?;
             }
+            _ => return Err(MonkeyError::unsupported_prefix("-", &operand)),
         }
         Ok(())
     }
 
-    fn build_array(&self, start_index: usize, end_index: usize) -> Result<Rc<Object>, String> {
+    fn build_array(&self, start_index: usize, end_index: usize) -> Result<Rc<Object>, MonkeyError> {
         let mut elements: Vec<Object> = Vec::new();
         for i in start_index..end_index {
-            elements
-                .push((**(self.stack.get(i).ok_or("Unable to get element".to_string()))?).clone());
+            elements.push((**(self.stack.get(i).ok_or_else(Self::stack_underflow))?).clone());
         }
         Ok(Rc::new(Object::ARRAY(elements)))
     }
 
-    fn build_hashmap(&self, start_index: usize, end_index: usize) -> Result<Rc<Object>, String> {
+    fn build_hashmap(
+        &self,
+        start_index: usize,
+        end_index: usize,
+    ) -> Result<Rc<Object>, MonkeyError> {
         let mut elements: HashMap<Object, Object> = HashMap::new();
         for i in (start_index..end_index).step_by(2) {
-            let key = (**(self.stack.get(i).ok_or("Unable to get element".to_string()))?).clone();
-            let value = (**(self
-                .stack
-                .get(i + 1)
-                .ok_or("Unable to get element".to_string()))?)
-            .clone();
+            let key = (**(self.stack.get(i).ok_or_else(Self::stack_underflow))?).clone();
+            let value = (**(self.stack.get(i + 1).ok_or_else(Self::stack_underflow))?).clone();
             if !Object::is_hashable(&key) {
-                return Ok(Rc::new(Object::ERROR(format!(
-                    "Unusable as hashmap key: {key:?}"
-                ))));
+                return Err(MonkeyError::unusable_key(&key));
             }
             elements.insert(key, value);
         }
@@ -427,101 +802,211 @@ impl VM {
         &mut self,
         left: &Rc<Object>,
         index: &Rc<Object>,
-    ) -> Result<(), String> {
-        match (&**left, &**index) {
+    ) -> Result<(), MonkeyError> {
+        let element = Self::element(left, index)?;
+        self.push(Rc::new(element))
+    }
+
+    /// The element of an array or a hash at `index`, `NULL` if there is none.
+    fn element(left: &Object, index: &Object) -> Result<Object, MonkeyError> {
+        match (left, index) {
             (Object::ARRAY(elements), Object::INTEGER(i)) => {
                 if *i < 0 || *i >= elements.len() as i64 {
-                    self.push(Rc::new(Object::NULL))?;
+                    Ok(Object::NULL)
                 } else {
-                    let result = elements
+                    elements
                         .get(*i as usize)
-                        .ok_or("Index out of bounds".to_string())?;
-                    self.push(Rc::new(result.clone()))?;
+                        .cloned()
+                        .ok_or_else(|| MonkeyError::IndexError("Index out of bounds".to_string()))
                 }
             }
             (Object::HASHMAP(elements), _) => {
                 if !Object::is_hashable(index) {
-                    return Err("Unusable as hashmap key".to_string());
-                }
-                match elements.get(index) {
-                    Some(value) => {
-                        self.push(Rc::new(value.clone()))?;
-                    }
-                    None => {
-                        self.push(Rc::new(Object::NULL))?;
-                    }
+                    return Err(MonkeyError::unusable_key(index));
                 }
+                Ok(elements.get(index).cloned().unwrap_or(Object::NULL))
             }
 
-            _ => {
-                return Err("Unsupported types for index operation".to_string());
-            }
+            _ => Err(MonkeyError::unsupported_index(left, index)),
         }
-        Ok(())
     }
 
-    fn execute_call(&mut self, num_args: usize) -> Result<(), String> {
+    /// Replaces the element at the end of the `depth` indices below the new
+    /// value, see [`Opcode::SetIndex`].
+    fn execute_set_index(&mut self, depth: usize) -> Result<(), MonkeyError> {
+        let value = self.pop()?;
+        let start = self
+            .sp
+            .checked_sub(depth + 1)
+            .ok_or_else(Self::stack_underflow)?;
+        let indices: Vec<Object> = self.stack[start + 1..self.sp]
+            .iter()
+            .map(|index| index.as_ref().clone())
+            .collect();
+        let container = self.stack[start].as_ref().clone();
+        self.sp = start;
+
+        let container = container.with_element(&indices, value.as_ref().clone())?;
+        self.allocate()?;
+        self.push(value)?;
+        self.push(Rc::new(container))
+    }
+
+    /// Pushes the element at the end of the `depth` indices on top of the
+    /// stack, see [`Opcode::PeekIndex`].
+    fn execute_peek_index(&mut self, depth: usize) -> Result<(), MonkeyError> {
+        let start = self
+            .sp
+            .checked_sub(depth + 1)
+            .ok_or_else(Self::stack_underflow)?;
+        let mut element = Rc::clone(&self.stack[start]);
+        for index in &self.stack[start + 1..self.sp] {
+            element = Rc::new(Self::element(&element, index)?);
+        }
+        self.push(element)
+    }
+
+    fn execute_call(&mut self, num_args: usize) -> Result<(), MonkeyError> {
         let callee = self
-            .stack
-            .get(self.sp - 1 - num_args)
-            .ok_or("Stack underflow")?;
+            .sp
+            .checked_sub(1 + num_args)
+            .and_then(|index| self.stack.get(index))
+            .ok_or_else(Self::stack_underflow)?;
 
         match callee.as_ref().clone() {
             Object::CLOSURE(func) => self.call_closure(func, num_args),
-            Object::BUILTIN(func) => self.call_builtin_function(&func, num_args),
-            _ => Err("Calling non-function".to_string()),
+            Object::BUILTIN(func) => self.call_builtin_function(num_args, |args| func.call(args)),
+            Object::NATIVE(func) => self.call_builtin_function(num_args, |args| func.call(&args)),
+            callee => Err(MonkeyError::not_a_function(&callee)),
         }
     }
 
-    fn call_closure(&mut self, func: Closure, num_args: usize) -> Result<(), String> {
+    fn call_closure(&mut self, func: Closure, num_args: usize) -> Result<(), MonkeyError> {
         if num_args != func.function.num_parameters {
-            return Err(format!(
-                "Wrong number of arguments: want={}, got={}",
-                func.function.num_parameters, num_args
-            ));
+            return Err(MonkeyError::ArityError {
+                expected: func.function.num_parameters,
+                got: num_args,
+            });
         }
 
         let num_locals = func.function.num_locals;
+        self.reserve_stack(self.sp - num_args + num_locals)?;
         let frame = Frame::new(func, self.sp - num_args);
         self.sp = frame.base_pointer + num_locals;
-        self.push_frame(frame);
-        Ok(())
+        self.push_frame(frame)
+    }
+
+    /// Calls a closure in place of the running one, so that its value is
+    /// returned straight to the caller. Builtins and calls from the main
+    /// program run like any other call, the `OpReturnValue` after them does the
+    /// rest.
+    fn execute_tail_call(&mut self, num_args: usize) -> Result<(), MonkeyError> {
+        let callee = self
+            .sp
+            .checked_sub(1 + num_args)
+            .and_then(|index| self.stack.get(index))
+            .ok_or_else(Self::stack_underflow)?;
+
+        match callee.as_ref().clone() {
+            // The handlers of the frame would be lost if it was reused
+            Object::CLOSURE(func)
+                if self.frames_index > 1 && self.current_frame().handlers.is_empty() =>
+            {
+                if num_args != func.function.num_parameters {
+                    return Err(MonkeyError::ArityError {
+                        expected: func.function.num_parameters,
+                        got: num_args,
+                    });
+                }
+
+                let base_pointer = self.current_frame().base_pointer;
+                let num_locals = func.function.num_locals;
+                self.reserve_stack(base_pointer + num_locals)?;
+
+                // The callee and its arguments take the place of the current ones
+                let start = self.sp - 1 - num_args;
+                for i in 0..=num_args {
+                    self.stack[base_pointer - 1 + i] = Rc::clone(&self.stack[start + i]);
+                }
+                self.sp = base_pointer + num_locals;
+
+                let frame = self.current_frame();
+                frame.function = func;
+                frame.ip = -1;
+                // The closures of the previous call keep the cells they captured
+                frame.cells.clear();
+                Ok(())
+            }
+            _ => self.execute_call(num_args),
+        }
     }
 
+    /// Calls a builtin or a native function with the arguments on the stack.
     fn call_builtin_function(
         &mut self,
-        callee: &BuiltinFunction,
         num_args: usize,
-    ) -> Result<(), String> {
+        call: impl FnOnce(Vec<Object>) -> Result<Object, MonkeyError>,
+    ) -> Result<(), MonkeyError> {
         let mut args: Vec<Object> = Vec::new();
         for _ in 0..num_args {
             args.push(self.pop()?.as_ref().clone());
         }
         args.reverse();
 
-        let result = callee.call(args);
+        let result = call(args)?;
+        if let Object::STRING(_) | Object::ARRAY(_) | Object::HASHMAP(_) = result {
+            self.allocate()?;
+        }
 
         self.sp -= 1;
         self.push(Rc::new(result))?;
         Ok(())
     }
 
-    fn push_closure(&mut self, const_index: usize, num_free: usize) -> Result<(), String> {
-        match (*self.constants[const_index]).clone() {
+    fn push_closure(&mut self, const_index: usize, num_free: usize) -> Result<(), MonkeyError> {
+        match (*self.constant(const_index)?).clone() {
             Object::COMPILEDFUNCTION(func) => {
+                self.allocate()?;
+                // The instructions that follow capture its free variables
                 let mut closure = Closure::new(func);
-
-                for obj in &self.stack[self.sp - num_free..self.sp] {
-                    closure.add_free_variable(obj.as_ref().clone());
-                }
-
-                self.sp -= num_free;
+                closure.free.reserve(num_free);
                 self.push(Rc::new(Object::CLOSURE(closure)))
             }
-            x => Err(format!["Function expected, got {}", x.get_type()]),
+            x => Err(MonkeyError::InvalidBytecode(format!(
+                "Function expected, got {}",
+                x.get_type()
+            ))),
         }
     }
 
+    fn unsupported_binary_operation(left: &Object, op: Opcode, right: &Object) -> MonkeyError {
+        let operator = match op {
+            Opcode::Add => "+",
+            Opcode::Sub => "-",
+            Opcode::Mul => "*",
+            Opcode::Div => "/",
+            Opcode::Modulo => "%",
+            Opcode::Equal => "==",
+            Opcode::NotEqual => "!=",
+            Opcode::GreaterThan => ">",
+            Opcode::GreaterEqualThan => ">=",
+            Opcode::LessThan => "<",
+            Opcode::LessEqualThan => "<=",
+            Opcode::And => "&&",
+            Opcode::Or => "||",
+            _ => unreachable!("{op} is not a binary operator"),
+        };
+        MonkeyError::unsupported_infix(left, operator, right)
+    }
+
+    fn stack_underflow() -> MonkeyError {
+        MonkeyError::InvalidBytecode("Stack underflow".to_string())
+    }
+
+    fn unknown_local(index: usize) -> MonkeyError {
+        MonkeyError::InvalidBytecode(format!("Unknown local {index}"))
+    }
+
     fn native_boolean_to_boolean_object(&self, input: bool) -> Rc<Object> {
         if input {
             Rc::new(TRUE)
@@ -538,56 +1023,143 @@ impl VM {
         }
     }
 
-    fn push(&mut self, obj: Rc<Object>) -> Result<(), String> {
-        if self.sp >= STACK_SIZE {
-            Err("Stack overflow :(, you gotta fix this".to_string())
+    fn push(&mut self, obj: Rc<Object>) -> Result<(), MonkeyError> {
+        if self.sp >= self.config.max_stack {
+            return Err(self.exceed(Limit::Stack));
+        }
+        if self.sp == self.stack.len() {
+            self.stack.push(obj);
         } else {
             self.stack[self.sp] = obj;
-            self.sp += 1;
-            Ok(())
         }
+        self.sp += 1;
+        Ok(())
+    }
+
+    /// Makes room for `size` values on the stack.
+    fn reserve_stack(&mut self, size: usize) -> Result<(), MonkeyError> {
+        if size > self.config.max_stack {
+            return Err(self.exceed(Limit::Stack));
+        }
+        if size > self.stack.len() {
+            self.stack.resize(size, Rc::new(NULL));
+        }
+        Ok(())
+    }
+
+    /// Counts a string, array, hashmap or closure created by the program
+    /// against its allocation budget.
+    fn allocate(&mut self) -> Result<(), MonkeyError> {
+        self.allocations += 1;
+        match self.config.max_allocations {
+            Some(max) if self.allocations > max => Err(self.exceed(Limit::Allocations)),
+            _ => Ok(()),
+        }
+    }
+
+    fn count_instruction(&mut self) -> Result<(), MonkeyError> {
+        self.meter
+            .step()
+            .map_err(|err| self.exceed(Limit::from(err)))
+    }
+
+    /// The error for a program that went over `limit`.
+    fn exceed(&self, limit: Limit) -> MonkeyError {
+        let message = match limit {
+            Limit::Stack => format!(
+                "Stack overflow: the stack holds at most {} values",
+                self.config.max_stack
+            ),
+            Limit::Frames => format!(
+                "Stack overflow: at most {} calls can be running",
+                self.config.max_frames
+            ),
+            Limit::Allocations => format!(
+                "Out of allocations: a program can create at most {} objects",
+                self.config.max_allocations.unwrap_or_default()
+            ),
+            Limit::OutOfFuel => format!(
+                "Out of fuel: a program can run at most {} instructions",
+                self.meter.budget.fuel.unwrap_or_default()
+            ),
+            Limit::Interrupted => "Interrupted".to_string(),
+        };
+        MonkeyError::Limit { limit, message }
     }
 
-    fn pop(&mut self) -> Result<Rc<Object>, String> {
+    fn pop(&mut self) -> Result<Rc<Object>, MonkeyError> {
         if self.sp == 0 {
-            Err("Stack underflow".to_string())
+            Err(Self::stack_underflow())
         } else {
             self.sp -= 1;
             self.stack
                 .get(self.sp)
-                .ok_or("Stack underflow".to_string())
+                .ok_or_else(Self::stack_underflow)
                 .cloned()
         }
     }
 
     pub fn stack_top(&self) -> Option<Rc<Object>> {
-        self.stack.get(self.sp - 1).cloned()
+        let top = self.sp.checked_sub(1)?;
+        self.stack.get(top).cloned()
     }
 
-    fn cast_to_integer(&self, obj: &Rc<Object>) -> Result<i64, String> {
+    fn cast_to_integer(&self, obj: &Rc<Object>) -> Result<i64, MonkeyError> {
         match **obj {
             Object::INTEGER(i) => Ok(i),
-            _ => Err("Unable to cast to integer".to_string()),
+            _ => Err(MonkeyError::TypeError(
+                "Unable to cast to integer".to_string(),
+            )),
         }
     }
 
-    pub fn last_popped_stack_element(&self) -> Result<Rc<Object>, String> {
-        self.stack
+    /// Instructions run so far.
+    pub fn fuel_used(&self) -> u64 {
+        self.meter.used
+    }
+
+    pub fn last_popped_stack_element(&self) -> Result<Rc<Object>, MonkeyError> {
+        // The stack only grows when a value is pushed, a slot that was never
+        // used holds null
+        Ok(self
+            .stack
             .get(self.sp)
-            .ok_or("Stack underflow".to_string())
             .cloned()
+            .unwrap_or_else(|| Rc::new(NULL)))
     }
 
     fn current_frame(&mut self) -> &mut Frame {
         &mut self.frames[self.frames_index - 1]
     }
 
-    fn push_frame(&mut self, frame: Frame) {
+    fn push_frame(&mut self, frame: Frame) -> Result<(), MonkeyError> {
+        if self.frames_index >= self.config.max_frames {
+            return Err(self.exceed(Limit::Frames));
+        }
         self.frames.push(frame);
         self.frames_index += 1;
+        Ok(())
+    }
+
+    /// Leaves the running function, the stack goes back to what it was
+    /// before the call.
+    fn return_from_frame(&mut self) -> Result<(), MonkeyError> {
+        match self.pop_frame() {
+            Some(frame) => {
+                self.sp = frame.base_pointer - 1;
+                Ok(())
+            }
+            None => Err(MonkeyError::InvalidBytecode(
+                "There was no frame".to_string(),
+            )),
+        }
     }
 
+    /// Removes the innermost frame, the one of the main program stays.
     fn pop_frame(&mut self) -> Option<Frame> {
+        if self.frames_index <= 1 {
+            return None;
+        }
         self.frames_index -= 1;
         self.frames.pop()
     }
@@ -0,0 +1,137 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        compiler::{optimizer::optimize, test_utils::compile},
+        object::Object,
+        vm::{
+            test_utils::{run_vm_error_tests, run_vm_tests, VmTestCase},
+            VM,
+        },
+    };
+
+    /// Runs a program too deep for the evaluator with the plain and the
+    /// optimized VM.
+    fn run_deep(input: &str) -> Vec<Result<Object, String>> {
+        let bytecode = compile(input);
+
+        [bytecode.clone(), optimize(bytecode).unwrap()]
+            .into_iter()
+            .map(|bytecode| {
+                let mut vm = VM::new(bytecode);
+                vm.run().map_err(|err| err.error.to_string())?;
+                Ok(vm.last_popped_stack_element().unwrap().as_ref().clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_deep_tail_recursion() {
+        let tests = vec![
+            (
+                "let count = fn(n) { if (n == 0) { 0 } else { count(n - 1) } }; count(100000)",
+                Object::INTEGER(0),
+            ),
+            (
+                "let sum = fn(n, acc) { if (n == 0) { return acc; } return sum(n - 1, acc + n); };
+                sum(100000, 0)",
+                Object::INTEGER(5_000_050_000),
+            ),
+            (
+                r"
+                let even = fn(n) {
+                    let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } };
+                    if (n == 0) { true } else { odd(n - 1) }
+                };
+                even(100001)",
+                Object::BOOLEAN(false),
+            ),
+            (
+                r"
+                let build = fn(arr, n) { if (n == 0) { arr } else { build(push(arr, n), n - 1) } };
+                let foldl = fn (arr, initial, f) {
+                    let iter = fn (arr, result) {
+                        if (len(arr) == 0) {
+                            result
+                        } else {
+                            iter(rest(arr), f(result, first(arr)))
+                        }
+                    };
+                    iter(arr, initial)
+                };
+                foldl(build([], 3000), 0, fn(x, y) { x + y })",
+                Object::INTEGER(4_501_500),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            for got in run_deep(input) {
+                assert_eq!(got, Ok(expected.clone()), "wrong result for {input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tail_calls_after_long_jumps() {
+        // The jumps over the padding are widened before the tail calls are
+        // found, without them 3000 calls overflow the stack
+        let input = format!(
+            "let count = fn(n) {{ if (n > 0) {{ count(n - 1) }} else {{ {} 0 }} }}; count(3000)",
+            "1;".repeat(20000)
+        );
+        for got in run_deep(&input) {
+            assert_eq!(got, Ok(Object::INTEGER(0)));
+        }
+    }
+
+    #[test]
+    fn test_deep_recursion_outside_tail_position() {
+        let input =
+            "let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(100000)";
+        for got in run_deep(input) {
+            assert_eq!(
+                got,
+                Err("Stack overflow: the stack holds at most 2048 values".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let tests = vec![
+            VmTestCase {
+                input: "let f = fn(a, b) { a - b }; let g = fn(x) { f(x, 1) }; g(10)".to_string(),
+                expected: Object::INTEGER(9),
+            },
+            VmTestCase {
+                // The callee needs more locals than the caller
+                input: r"
+                let f = fn(a) { let b = a * 2; let c = b + 1; c };
+                let g = fn() { f(3) };
+                g()"
+                .to_string(),
+                expected: Object::INTEGER(7),
+            },
+            VmTestCase {
+                input: "let f = fn(x) { fn(y) { x + y } }; let g = fn(x) { f(x)(2) }; g(40)"
+                    .to_string(),
+                expected: Object::INTEGER(42),
+            },
+            VmTestCase {
+                input: "let f = fn(arr) { len(arr) }; 1 + f([1, 2, 3])".to_string(),
+                expected: Object::INTEGER(4),
+            },
+            VmTestCase {
+                input: "let f = fn(x) { if (x) { return first([1]); } len(\"ab\") }; [f(true), f(false)]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![Object::INTEGER(1), Object::INTEGER(2)]),
+            },
+        ];
+
+        run_vm_tests(tests);
+        run_vm_error_tests(vec![(
+            "let f = fn(a) { a }; let g = fn() { f(1, 2) }; g()",
+            "wrong number of arguments: want=1, got=2",
+        )]);
+    }
+}
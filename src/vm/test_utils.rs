@@ -1,5 +1,8 @@
 use crate::{
-    compiler::{code::Instructions, Compiler},
+    compiler::{code::Instructions, optimizer::optimize, test_utils::compile},
+    differential,
+    errors::MonkeyError,
+    interpreter::evaluator::Evaluator,
     object::{test_utils::check_constants, Object},
     parser::parse,
     vm::VM,
@@ -15,10 +18,10 @@ pub(crate) struct VmTestCase {
 pub(crate) fn run_vm_tests(tests: Vec<VmTestCase>) {
     for test in tests {
         println!("Running test: {}", test.input);
-        let program = parse(&test.input);
-        let mut compiler = Compiler::new();
-        compiler.compile(program).unwrap();
-        let bytecode = compiler.bytecode();
+        if let Err(mismatch) = differential::check(&test.input) {
+            panic!("{mismatch}");
+        }
+        let bytecode = compile(&test.input);
 
         for (i, constant) in bytecode.constants.iter().enumerate() {
             match constant {
@@ -31,20 +34,60 @@ pub(crate) fn run_vm_tests(tests: Vec<VmTestCase>) {
             }
         }
 
-        let mut vm = VM::new(bytecode);
+        // The optimized program must behave exactly like the original one
+        let optimized = optimize(bytecode.clone()).unwrap();
+        for bytecode in [bytecode, optimized] {
+            let mut vm = VM::new(bytecode);
+            if let Err(err) = vm.run() {
+                panic!("{}: {err}", test.input);
+            }
+            let got = vm.last_popped_stack_element().unwrap();
+            check_constants(std::slice::from_ref(&test.expected), &vec![got]);
+        }
+    }
+}
+
+/// Checks that every program fails with the expected message, in both engines
+/// and with optimized bytecode.
+#[allow(dead_code)]
+pub(crate) fn run_vm_error_tests(tests: Vec<(&str, &str)>) {
+    for (input, expected) in tests {
+        println!("Running test: {input}");
+        if let Err(mismatch) = differential::check(input) {
+            panic!("{mismatch}");
+        }
+        let bytecode = compile(input);
+
+        let optimized = optimize(bytecode.clone()).unwrap();
+        for bytecode in [bytecode, optimized] {
+            let mut vm = VM::new(bytecode);
+            match vm.run() {
+                Ok(()) => panic!("{input}: expected an error"),
+                Err(err) => assert_eq!(err.error.to_string(), expected, "{input}"),
+            }
+        }
+    }
+}
+
+/// Runs every program with both the evaluator and the VM, and checks that they
+/// agree with each other and with the expected result.
+#[allow(dead_code)]
+pub(crate) fn run_differential_tests(tests: Vec<VmTestCase>) {
+    for test in tests {
+        println!("Running test: {}", test.input);
+        let evaluated = Evaluator::new().eval(parse(&test.input)).unwrap();
+
+        let mut vm = VM::new(compile(&test.input));
         vm.run().unwrap();
         let got = vm.last_popped_stack_element().unwrap();
-        check_constants(&[test.expected], &vec![got]);
+
+        assert_eq!(evaluated, *got, "the evaluator and the VM disagree");
+        assert_eq!(evaluated, test.expected);
     }
 }
 
 #[allow(dead_code)]
-pub(crate) fn run_vm_with_error_output(input: &str) -> Result<(), String> {
-    let program = parse(input);
-    let mut compiler = Compiler::new();
-    compiler.compile(program).unwrap();
-    let bytecode = compiler.bytecode();
-
-    let mut vm = VM::new(bytecode);
-    vm.run()
+pub(crate) fn run_vm_with_error_output(input: &str) -> Result<(), MonkeyError> {
+    let mut vm = VM::new(compile(input));
+    vm.run().map_err(|err| err.error)
 }
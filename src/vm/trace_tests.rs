@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        compiler::{optimizer::optimize, test_utils::compile, Bytecode},
+        vm::VM,
+    };
+
+    fn run(bytecode: Bytecode) -> String {
+        let mut vm = VM::new(bytecode);
+        vm.run().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_stack_traces() {
+        let tests = vec![
+            ("1 + true", "type mismatch: INTEGER + BOOLEAN\n    at <main> (1:3)"),
+            (
+                r"
+let divide = fn(a, b) {
+    a / b
+};
+let half = fn(x) { divide(x, 0) + 1 };
+half(4);",
+                "division by zero\n    at divide (3:7)\n    at half (5:26)\n    at <main> (6:5)",
+            ),
+            (
+                "let x = 1;\nfn() { [1, 2][x] + -true }()",
+                "unknown operator: -BOOLEAN\n    at <anonymous> (2:20)\n    at <main> (2:27)",
+            ),
+            (
+                "let g = fn() { {}[fn() {}] };\nlet f = fn() { g() + 1 };\nf()",
+                "unusable as hash key: FUNCTION\n    at g (1:18)\n    at f (2:17)\n    at <main> (3:2)",
+            ),
+            (
+                // Identical functions still report their own name and position
+                "let a = fn() { first(1) };\nlet b = fn() { first(1) };\nb();",
+                "argument to `first` not supported, must be ARRAY, got INTEGER\n    at b (2:21)\n    at <main> (3:2)",
+            ),
+            (
+                // The frames of a recursion are printed once
+                "let f = fn(n) { if (n == 0) { 1 / 0 } else { 1 + f(n - 1) } };\nf(100)",
+                "division by zero\n    at f (1:33)\n    at f (1:51)\n    ... repeated 99 more times\n    at <main> (2:2)",
+            ),
+            (
+                "let f = fn(n) { if (n == 0) { 1 / 0 } else { 1 + f(n - 1) } };\nf(2)",
+                "division by zero\n    at f (1:33)\n    at f (1:51)\n    ... repeated 1 more time\n    at <main> (2:2)",
+            ),
+            (
+                // A tail call takes the place of its caller
+                "let g = fn() { 1 / 0 };\nlet f = fn() { return g(); };\nf()",
+                "division by zero\n    at g (1:18)\n    at <main> (3:2)",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let bytecode = compile(input);
+            assert_eq!(run(bytecode.clone()), expected, "wrong trace for {input}");
+
+            let optimized = optimize(bytecode.clone()).unwrap();
+            assert_eq!(
+                run(optimized),
+                expected,
+                "wrong optimized trace for {input}"
+            );
+
+            let loaded = Bytecode::deserialize(&bytecode.serialize().unwrap()).unwrap();
+            assert_eq!(run(loaded), expected, "wrong trace after loading {input}");
+        }
+    }
+
+    #[test]
+    fn test_trace_without_line_table() {
+        let mut bytecode = compile("let f = fn() { 1 / 0 }; f()");
+        bytecode.debug.lines.clear();
+
+        assert_eq!(
+            run(bytecode),
+            "division by zero\n    at f (1:18)\n    at <main>"
+        );
+    }
+}
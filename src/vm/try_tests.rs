@@ -0,0 +1,216 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        compiler::test_utils::compile,
+        errors::Limit,
+        object::Object,
+        vm::{
+            config::VmConfig,
+            test_utils::{run_vm_error_tests, run_vm_tests, VmTestCase},
+            VM,
+        },
+    };
+
+    #[test]
+    fn test_try_catch() {
+        let tests = vec![
+            VmTestCase {
+                input: "let x = 1; try { let x = 2; } catch (e) { let x = 3; } x".to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: "try { throw 5; } catch (e) { let x = e * 2; } x".to_string(),
+                expected: Object::INTEGER(10),
+            },
+            VmTestCase {
+                input: r#"try { throw "boom"; } catch (e) { let x = e; } x"#.to_string(),
+                expected: Object::STRING("boom".to_string()),
+            },
+            VmTestCase {
+                input: r#"try { first(1); } catch (e) { let x = e["kind"]; } x"#.to_string(),
+                expected: Object::STRING("TypeError".to_string()),
+            },
+            VmTestCase {
+                input: r#"try { 1 / 0; } catch (e) { let x = e["message"]; } x"#.to_string(),
+                expected: Object::STRING("division by zero".to_string()),
+            },
+            VmTestCase {
+                input: r#"try { [1][true]; } catch (e) { let x = e["kind"]; } x"#.to_string(),
+                expected: Object::STRING("IndexError".to_string()),
+            },
+            VmTestCase {
+                input: r#"try { len(1, 2); } catch (e) { let x = e["kind"]; } x"#.to_string(),
+                expected: Object::STRING("ArityError".to_string()),
+            },
+            // The code after the error does not run
+            VmTestCase {
+                input: "let x = 1; try { throw 2; let x = 3; } catch (e) {} x".to_string(),
+                expected: Object::INTEGER(1),
+            },
+            // Nested handlers, the innermost one catches and can throw again
+            VmTestCase {
+                input: r"
+                try {
+                    try { throw 1; } catch (e) { throw e + 1; }
+                } catch (e) { let x = e; }
+                x"
+                .to_string(),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: r"
+                try {
+                    try { let x = 1; } catch (e) { let x = 2; }
+                    throw 3;
+                } catch (e) { let y = e; }
+                x + y"
+                    .to_string(),
+                expected: Object::INTEGER(4),
+            },
+            // A try statement has no value
+            VmTestCase {
+                input: "let f = fn() { try { 1 } catch (e) { 2 } }; f()".to_string(),
+                expected: Object::NULL,
+            },
+            VmTestCase {
+                input: "if (true) { try { 1 } catch (e) { 2 } }".to_string(),
+                expected: Object::NULL,
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_errors_unwind_function_calls() {
+        let tests = vec![
+            VmTestCase {
+                input: r#"
+                let check = fn(x) { if (x < 0) { throw "negative"; } x };
+                let safe = fn(x) {
+                    try { return check(x) * 2; } catch (e) { return e; }
+                };
+                [safe(3), safe(-1)]"#
+                    .to_string(),
+                expected: Object::ARRAY(vec![
+                    Object::INTEGER(6),
+                    Object::STRING("negative".to_string()),
+                ]),
+            },
+            VmTestCase {
+                input: r"
+                let down = fn(n) { if (n == 0) { throw 0; } 1 + down(n - 1) };
+                try { down(10); } catch (e) { let x = e; }
+                [x, 1 + 2]"
+                    .to_string(),
+                expected: Object::ARRAY(vec![Object::INTEGER(0), Object::INTEGER(3)]),
+            },
+            // A call in tail position inside a try body keeps its handler
+            VmTestCase {
+                input: r"
+                let fail = fn() { throw 7; };
+                let f = fn() { try { return fail(); } catch (e) { return e; } };
+                f()"
+                .to_string(),
+                expected: Object::INTEGER(7),
+            },
+            // The stack is back to where it was when the try started
+            VmTestCase {
+                input: r"
+                let f = fn(a) { let b = a + 1; try { [1, 2, a + first(b)]; } catch (e) {} a + b };
+                f(1)"
+                    .to_string(),
+                expected: Object::INTEGER(3),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_loops_inside_try() {
+        let tests = vec![
+            VmTestCase {
+                input: r"
+                let i = 0;
+                let sum = 0;
+                while (i < 10) {
+                    let i = i + 1;
+                    try {
+                        if (i == 3) { continue; }
+                        if (i == 6) { break; }
+                        let sum = sum + i;
+                    } catch (e) {}
+                }
+                try { throw sum; } catch (e) { let x = e; }
+                x"
+                .to_string(),
+                expected: Object::INTEGER(12),
+            },
+            VmTestCase {
+                input: r"
+                let i = 0;
+                try {
+                    while (true) {
+                        let i = i + 1;
+                        if (i == 3) { break; }
+                    }
+                    throw i;
+                } catch (e) { let x = e; }
+                x"
+                .to_string(),
+                expected: Object::INTEGER(3),
+            },
+            VmTestCase {
+                input: r"
+                let i = 0;
+                let caught = 0;
+                while (i < 5) {
+                    let i = i + 1;
+                    try { throw i; } catch (e) { let caught = caught + e; }
+                }
+                caught"
+                    .to_string(),
+                expected: Object::INTEGER(15),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_uncaught_errors() {
+        run_vm_error_tests(vec![
+            ("throw 1;", "uncaught exception: 1"),
+            (r#"throw "boom";"#, "uncaught exception: \"boom\""),
+            (
+                "try { throw 1; } catch (e) { throw e + 1; }",
+                "uncaught exception: 2",
+            ),
+            ("try { 1 / 0; } catch (e) { 1 / 0; }", "division by zero"),
+            (
+                "let f = fn() { try { 1 } catch (e) { 2 } throw 3; }; f()",
+                "uncaught exception: 3",
+            ),
+            (
+                "let i = 0; while (i < 3) { let i = i + 1; try { break; } catch (e) {} } throw i;",
+                "uncaught exception: 1",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_limits_are_not_caught() {
+        let mut vm = VM::with_config(
+            compile("let f = fn(n) { 1 + f(n + 1) }; try { f(0); } catch (e) { 0 }"),
+            VmConfig {
+                max_frames: 50,
+                ..Default::default()
+            },
+        );
+
+        let err = vm.run().unwrap_err();
+        assert_eq!(err.error.limit(), Some(Limit::Frames));
+    }
+}
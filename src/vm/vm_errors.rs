@@ -0,0 +1,68 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::{errors::MonkeyError, lexer::token::Span};
+
+/// An error that stopped the VM, with the calls that were running when it
+/// happened.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub error: MonkeyError,
+    /// The function that failed first, then the one that called it, down to
+    /// the main program.
+    pub trace: Vec<TraceEntry>,
+}
+
+/// A function that was running when an error happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Name the function was bound to, `<main>` for the main program and
+    /// `<anonymous>` for a function without a name.
+    pub function: String,
+    /// Where the function was when the error happened, unknown if the
+    /// bytecode has no line table.
+    pub position: Option<Span>,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "at {}", self.function)?;
+        match self.position {
+            Some(span) => write!(f, " ({span})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Consecutive identical entries, like the ones of a deep recursion, are
+/// printed once with the number of times they repeat.
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.error)?;
+        for entries in self.trace.chunk_by(|a, b| a == b) {
+            write!(f, "\n    {}", entries[0])?;
+            match entries.len() - 1 {
+                0 => {}
+                1 => write!(f, "\n    ... repeated 1 more time")?,
+                repeated => write!(f, "\n    ... repeated {repeated} more times")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Drops the trace.
+impl From<RuntimeError> for MonkeyError {
+    fn from(err: RuntimeError) -> Self {
+        err.error
+    }
+}
@@ -4,6 +4,7 @@ mod tests {
     use std::collections::HashMap;
 
     use crate::{
+        errors::MonkeyError,
         object::Object,
         vm::test_utils::{run_vm_tests, run_vm_with_error_output, VmTestCase},
     };
@@ -93,10 +94,80 @@ mod tests {
 
         for test in tests {
             let result = run_vm_with_error_output(test);
-            assert!(result.is_err());
+            assert!(matches!(result, Err(MonkeyError::DivisionByZero)));
         }
     }
 
+    #[test]
+    fn test_integer_overflow() {
+        let tests = vec![
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4611686018427387904 * 2",
+            "-9223372036854775808 / -1",
+            "let min = -9223372036854775808; -min",
+            "let f = fn(x) { x * x }; f(3037000500)",
+        ];
+
+        for test in tests {
+            let result = run_vm_with_error_output(test);
+            assert!(
+                matches!(result, Err(MonkeyError::IntegerOverflow)),
+                "{test} did not overflow"
+            );
+        }
+
+        run_vm_tests(vec![
+            VmTestCase {
+                input: "9223372036854775806 + 1".to_string(),
+                expected: Object::INTEGER(i64::MAX),
+            },
+            VmTestCase {
+                input: "-9223372036854775807 - 1".to_string(),
+                expected: Object::INTEGER(i64::MIN),
+            },
+            VmTestCase {
+                input: "-9223372036854775808".to_string(),
+                expected: Object::INTEGER(i64::MIN),
+            },
+            VmTestCase {
+                input: "-9223372036854775808 % -1".to_string(),
+                expected: Object::INTEGER(0),
+            },
+            VmTestCase {
+                input: "let f = fn(x) { x * x }; f(3037000499)".to_string(),
+                expected: Object::INTEGER(9_223_372_030_926_249_001),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        assert!(matches!(
+            run_vm_with_error_output("5 + true"),
+            Err(MonkeyError::TypeError(_))
+        ));
+        assert!(matches!(
+            run_vm_with_error_output("1[0]"),
+            Err(MonkeyError::IndexError(_))
+        ));
+        assert!(matches!(
+            run_vm_with_error_output("fn(x) { x }(1, 2)"),
+            Err(MonkeyError::ArityError {
+                expected: 1,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            run_vm_with_error_output("first(1)"),
+            Err(MonkeyError::TypeError(_))
+        ));
+        assert!(matches!(
+            run_vm_with_error_output("1()"),
+            Err(MonkeyError::TypeError(_))
+        ));
+    }
+
     #[test]
     fn test_boolean_logic() {
         let tests = vec![
@@ -298,6 +369,14 @@ mod tests {
                 input: "\"mon\" + \"key\" + \"banana\"".to_string(),
                 expected: Object::STRING("monkeybanana".to_string()),
             },
+            VmTestCase {
+                input: r#""say \"hi\"\n" + "\u{1F412}\t\\""#.to_string(),
+                expected: Object::STRING("say \"hi\"\n\u{1F412}\t\\".to_string()),
+            },
+            VmTestCase {
+                input: r#"len("a\nb\u{e9}")"#.to_string(),
+                expected: Object::INTEGER(5),
+            },
         ];
 
         run_vm_tests(tests);
@@ -4,12 +4,11 @@ mod tests {
 
     use crate::{
         object::Object,
-        vm::test_utils::{run_vm_tests, VmTestCase},
+        vm::test_utils::{run_differential_tests, run_vm_tests, VmTestCase},
     };
 
-    #[test]
-    fn test_while_statements_without_break_or_continue() {
-        let tests = vec![
+    fn while_statements_without_break_or_continue_cases() -> Vec<VmTestCase> {
+        vec![
             VmTestCase {
                 input: r"
                     let a = 1;
@@ -43,16 +42,18 @@ mod tests {
                 .to_string(),
                 expected: Object::INTEGER(1),
             },
-        ];
-
-        run_vm_tests(tests);
+        ]
     }
 
     #[test]
-    fn test_while_clean_up() {
-        // This tests makes sure that a while statement clears the stack correctly (which is
-        // different from the conditional behavior)
-        let tests = vec![VmTestCase {
+    fn test_while_statements_without_break_or_continue() {
+        run_vm_tests(while_statements_without_break_or_continue_cases());
+    }
+
+    // This tests makes sure that a while statement clears the stack correctly (which is
+    // different from the conditional behavior)
+    fn while_clean_up_cases() -> Vec<VmTestCase> {
+        vec![VmTestCase {
             input: r"
                     let a = 0;
                     while (a < 10000){
@@ -63,14 +64,16 @@ mod tests {
                     "
             .to_string(),
             expected: Object::INTEGER(10000),
-        }];
-
-        run_vm_tests(tests);
+        }]
     }
 
     #[test]
-    fn test_break_from_while() {
-        let tests = vec![
+    fn test_while_clean_up() {
+        run_vm_tests(while_clean_up_cases());
+    }
+
+    fn break_from_while_cases() -> Vec<VmTestCase> {
+        vec![
             VmTestCase {
                 input: r"
                     let a = 0;
@@ -181,14 +184,16 @@ mod tests {
                 .to_string(),
                 expected: Object::INTEGER(25),
             },
-        ];
-
-        run_vm_tests(tests);
+        ]
     }
 
     #[test]
-    fn test_continue_from_while() {
-        let tests = vec![
+    fn test_break_from_while() {
+        run_vm_tests(break_from_while_cases());
+    }
+
+    fn continue_from_while_cases() -> Vec<VmTestCase> {
+        vec![
             VmTestCase {
                 input: r"
                     let a = 0;
@@ -274,14 +279,16 @@ mod tests {
                 .to_string(),
                 expected: Object::INTEGER(120),
             },
-        ];
-
-        run_vm_tests(tests);
+        ]
     }
 
     #[test]
-    fn test_continue_and_break_in_while() {
-        let tests = vec![VmTestCase {
+    fn test_continue_from_while() {
+        run_vm_tests(continue_from_while_cases());
+    }
+
+    fn continue_and_break_in_while_cases() -> Vec<VmTestCase> {
+        vec![VmTestCase {
             input: r"
                 let a = 0;
                 let c = 0;
@@ -299,7 +306,24 @@ mod tests {
                 c"
             .to_string(),
             expected: Object::INTEGER(8),
-        }];
-        run_vm_tests(tests);
+        }]
+    }
+
+    #[test]
+    fn test_continue_and_break_in_while() {
+        run_vm_tests(continue_and_break_in_while_cases());
+    }
+
+    #[test]
+    fn test_evaluator_agrees_with_vm() {
+        let tests = while_statements_without_break_or_continue_cases()
+            .into_iter()
+            .chain(while_clean_up_cases())
+            .chain(break_from_while_cases())
+            .chain(continue_from_while_cases())
+            .chain(continue_and_break_in_while_cases())
+            .collect();
+
+        run_differential_tests(tests);
     }
 }
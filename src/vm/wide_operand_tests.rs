@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::{
+        object::Object,
+        vm::test_utils::{run_vm_tests, VmTestCase},
+    };
+
+    /// Identifiers cannot contain digits, `i` is spelled with letters.
+    fn name(i: usize) -> String {
+        let mut name = String::from("v");
+        let mut i = i;
+        loop {
+            name.push((b'a' + (i % 26) as u8) as char);
+            i /= 26;
+            if i == 0 {
+                return name;
+            }
+        }
+    }
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(name).collect()
+    }
+
+    #[test]
+    fn test_many_locals_and_arguments() {
+        let names = names(300);
+        let lets: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("let {name} = {i};"))
+            .collect();
+        let arguments: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+
+        let tests = vec![
+            VmTestCase {
+                input: format!("fn() {{ {} {} }}()", lets.join(" "), names[299]),
+                expected: Object::INTEGER(299),
+            },
+            VmTestCase {
+                input: format!(
+                    "fn({}) {{ {} + {} }}({})",
+                    names.join(", "),
+                    names[1],
+                    names[299],
+                    arguments.join(", ")
+                ),
+                expected: Object::INTEGER(300),
+            },
+            VmTestCase {
+                input: format!(
+                    "fn() {{ {} fn() {{ [{}][299] }} }}()()",
+                    lets.join(" "),
+                    names.join(", ")
+                ),
+                expected: Object::INTEGER(299),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_jumps_past_two_bytes() {
+        let padding = "1;".repeat(20000);
+
+        let tests = vec![
+            VmTestCase {
+                input: format!("{padding} if (false) {{ 1 }} else {{ 2 }}"),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: format!(
+                    "{padding} let a = 0; while (true) {{ let a = a + 1; if (a == 3) {{ break; }} }} a"
+                ),
+                expected: Object::INTEGER(3),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+
+    #[test]
+    fn test_forward_jumps_over_two_bytes() {
+        let padding = "1;".repeat(20000);
+
+        let tests = vec![
+            VmTestCase {
+                input: format!("if (false) {{ {padding} 1 }} else {{ 2 }}"),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: format!("if (true) {{ {padding} 1 }} else {{ 2 }}"),
+                expected: Object::INTEGER(1),
+            },
+            VmTestCase {
+                input: format!(
+                    "let a = 0; while (a < 3) {{ let a = a + 1; if (a == 2) {{ break; }} {padding} }} a"
+                ),
+                expected: Object::INTEGER(2),
+            },
+            VmTestCase {
+                input: format!("try {{ {padding} throw 3; }} catch (e) {{ e }}"),
+                expected: Object::INTEGER(3),
+            },
+        ];
+
+        run_vm_tests(tests);
+    }
+}
@@ -0,0 +1,35 @@
+// Runs programs through both the evaluator and the VM and checks that they
+// agree, see `chimpanzee::differential`.
+
+mod generator;
+
+use std::fs;
+
+use chimpanzee::differential;
+
+#[test]
+fn test_monkey_examples() {
+    let mut paths: Vec<_> = fs::read_dir("monkey_examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        println!("Running example: {}", path.display());
+        let input = fs::read_to_string(&path).unwrap();
+        if let Err(mismatch) = differential::check(&input) {
+            panic!("{mismatch}");
+        }
+    }
+}
+
+#[test]
+fn test_generated_programs() {
+    for seed in 0..500 {
+        let input = generator::generate(seed);
+        if let Err(mismatch) = differential::check(&input) {
+            panic!("seed {seed}: {mismatch}");
+        }
+    }
+}
@@ -181,3 +181,19 @@ fn test_complex_arithmetic_integrity() {
 
     run_test(input);
 }
+
+#[test]
+fn test_comments_integrity() {
+    let input = r"
+    // Sum of the first n integers
+    let sum = fn(n) { /* recursive */
+        if (n == 0) {
+            return 0; // base case
+        }
+        n + sum(n - 1) // n / 2 would be wrong
+    };
+    sum(10) / 5; // 11
+    ";
+
+    run_test(input);
+}
@@ -0,0 +1,372 @@
+//! Random generator of well-formed Monkey programs.
+//!
+//! The generated programs are meant to be run by both engines, see
+//! [`check`](chimpanzee::differential::check). They always parse, they terminate (loops are
+//! bounded by a counter and functions never call themselves) and their
+//! integers stay small. They are mostly well typed, but not always: a runtime
+//! error is an acceptable outcome as long as both engines report it.
+
+/// Small xorshift generator, good enough to explore programs and reproducible
+/// from its seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Rng {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// Random number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// The kinds of values the generator builds expressions for. Arrays only hold
+/// integers and hashes map strings to integers, which keeps most of the
+/// generated programs meaningful.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Integer,
+    Boolean,
+    String,
+    Array,
+    Hash,
+}
+
+const TYPES: [Type; 5] = [
+    Type::Integer,
+    Type::Boolean,
+    Type::String,
+    Type::Array,
+    Type::Hash,
+];
+
+const STRINGS: [&str; 5] = ["", "a", "b", "monkey", "b c"];
+
+/// Maximum nesting of generated expressions.
+const MAX_DEPTH: usize = 3;
+
+/// Out of a hundred expressions, how many are built for a random type instead
+/// of the expected one, so that the error paths are exercised too.
+const ILL_TYPED_PERCENT: usize = 3;
+
+pub struct Generator {
+    rng: Rng,
+    /// Variables in scope with their type, the innermost scope last.
+    scopes: Vec<Vec<(String, Type)>>,
+    /// Functions in scope with their number of parameters. Parameters and
+    /// results are all integers.
+    functions: Vec<(String, usize)>,
+    counter: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+            scopes: vec![Vec::new()],
+            functions: Vec::new(),
+            counter: 0,
+        }
+    }
+
+    /// Generates a program made of a few statements that ends with an
+    /// expression, so that its value can be compared.
+    pub fn program(mut self) -> String {
+        let mut program = String::new();
+        for _ in 0..1 + self.rng.below(6) {
+            program.push_str(&self.statement(0));
+            program.push('\n');
+        }
+        let ty = *self.rng.pick(&TYPES);
+        program.push_str(&self.expression(ty, 0));
+        program.push_str(";\n");
+        program
+    }
+
+    /// Identifiers cannot contain digits, so the counter is spelled in
+    /// letters: `x_b`, `x_c`, ..., `x_ba`, ...
+    fn fresh_name(&mut self, prefix: &str) -> String {
+        self.counter += 1;
+        let mut suffix = String::new();
+        let mut n = self.counter;
+        while n > 0 {
+            suffix.insert(0, (b'a' + (n % 26) as u8) as char);
+            n /= 26;
+        }
+        format!("{prefix}_{suffix}")
+    }
+
+    fn declare(&mut self, name: &str, ty: Type) {
+        self.scopes.last_mut().unwrap().push((name.to_string(), ty));
+    }
+
+    fn variables(&self, ty: Type) -> Vec<String> {
+        self.scopes
+            .iter()
+            .flatten()
+            .filter(|(_, t)| *t == ty)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn statement(&mut self, depth: usize) -> String {
+        match self.rng.below(6) {
+            0 if depth == 0 => self.function_definition(),
+            1 if depth == 0 => self.while_loop(),
+            2 => {
+                let ty = *self.rng.pick(&TYPES);
+                format!("{};", self.expression(ty, depth))
+            }
+            _ => {
+                let ty = *self.rng.pick(&TYPES);
+                let value = self.expression(ty, depth);
+                let name = self.fresh_name("x");
+                self.declare(&name, ty);
+                format!("let {name} = {value};")
+            }
+        }
+    }
+
+    fn function_definition(&mut self) -> String {
+        let name = self.fresh_name("f");
+        let parameters: Vec<String> = (0..self.rng.below(3))
+            .map(|_| self.fresh_name("p"))
+            .collect();
+
+        self.scopes.push(Vec::new());
+        for parameter in &parameters {
+            self.declare(parameter, Type::Integer);
+        }
+        let mut body = String::new();
+        for _ in 0..self.rng.below(3) {
+            body.push_str(&self.statement(1));
+            body.push(' ');
+        }
+        let result = self.expression(Type::Integer, 1);
+        if self.rng.chance(1, 2) {
+            body.push_str(&format!("return {result};"));
+        } else {
+            body.push_str(&result);
+        }
+        self.scopes.pop();
+
+        // Declared after its body, functions never call themselves
+        self.functions.push((name.clone(), parameters.len()));
+        format!("let {name} = fn({}) {{ {body} }};", parameters.join(", "))
+    }
+
+    /// A loop that runs at most a few times: the condition only depends on a
+    /// counter that the body always increments first, `let` inside a loop body
+    /// updates the variable of the enclosing scope.
+    fn while_loop(&mut self) -> String {
+        let counter = self.fresh_name("i");
+        let accumulator = self.fresh_name("acc");
+        let bound = self.rng.below(5);
+
+        self.scopes.push(Vec::new());
+        self.declare(&counter, Type::Integer);
+        let value = self.expression(Type::Integer, 1);
+        let escape = match self.rng.below(4) {
+            0 => format!("if ({counter} == {}) {{ break; }}", self.rng.below(5)),
+            1 => format!("if ({counter} % 2 == 0) {{ continue; }}"),
+            _ => String::new(),
+        };
+        self.scopes.pop();
+
+        self.declare(&counter, Type::Integer);
+        self.declare(&accumulator, Type::Array);
+        format!(
+            "let {counter} = 0; let {accumulator} = []; \
+             while ({counter} < {bound}) {{ let {counter} = {counter} + 1; {escape} \
+             let {accumulator} = push({accumulator}, {value}); }}"
+        )
+    }
+
+    fn expression(&mut self, ty: Type, depth: usize) -> String {
+        let ty = if self.rng.chance(ILL_TYPED_PERCENT, 100) {
+            *self.rng.pick(&TYPES)
+        } else {
+            ty
+        };
+        if depth >= MAX_DEPTH || self.rng.chance(1, 4) {
+            return self.leaf(ty);
+        }
+        let depth = depth + 1;
+        if self.rng.chance(1, 8) {
+            return format!(
+                "if ({}) {{ {} }} else {{ {} }}",
+                self.expression(Type::Boolean, depth),
+                self.expression(ty, depth),
+                self.expression(ty, depth)
+            );
+        }
+        match ty {
+            Type::Integer => self.integer(depth),
+            Type::Boolean => self.boolean(depth),
+            Type::String => format!(
+                "({} + {})",
+                self.expression(Type::String, depth),
+                self.expression(Type::String, depth)
+            ),
+            Type::Array => match self.rng.below(4) {
+                0 => format!(
+                    "push({}, {})",
+                    self.expression(Type::Array, depth),
+                    self.expression(Type::Integer, depth)
+                ),
+                1 => format!("rest({})", self.expression(Type::Array, depth)),
+                2 => format!("values({})", self.expression(Type::Hash, depth)),
+                _ => {
+                    let elements: Vec<String> = (0..self.rng.below(4))
+                        .map(|_| self.expression(Type::Integer, depth))
+                        .collect();
+                    format!("[{}]", elements.join(", "))
+                }
+            },
+            Type::Hash => match self.rng.below(3) {
+                0 => format!(
+                    "add({}, {}, {})",
+                    self.expression(Type::Hash, depth),
+                    self.expression(Type::String, depth),
+                    self.expression(Type::Integer, depth)
+                ),
+                1 => format!(
+                    "delete({}, {})",
+                    self.expression(Type::Hash, depth),
+                    self.expression(Type::String, depth)
+                ),
+                _ => {
+                    let pairs: Vec<String> = (0..self.rng.below(3))
+                        .map(|_| {
+                            format!(
+                                "{}: {}",
+                                self.leaf(Type::String),
+                                self.expression(Type::Integer, depth)
+                            )
+                        })
+                        .collect();
+                    format!("{{{}}}", pairs.join(", "))
+                }
+            },
+        }
+    }
+
+    fn integer(&mut self, depth: usize) -> String {
+        match self.rng.below(7) {
+            0 => format!("(-{})", self.expression(Type::Integer, depth)),
+            1 => {
+                let collection = *self.rng.pick(&[Type::String, Type::Array, Type::Hash]);
+                format!("len({})", self.expression(collection, depth))
+            }
+            2 => {
+                let builtin = self.rng.pick(&["first", "last"]);
+                format!("{builtin}({})", self.expression(Type::Array, depth))
+            }
+            3 => match self.rng.below(2) {
+                0 => format!(
+                    "{}[{}]",
+                    self.expression(Type::Array, depth),
+                    self.expression(Type::Integer, depth)
+                ),
+                _ => format!(
+                    "{}[{}]",
+                    self.expression(Type::Hash, depth),
+                    self.expression(Type::String, depth)
+                ),
+            },
+            4 if !self.functions.is_empty() => {
+                let (name, arity) = self.rng.pick(&self.functions).clone();
+                let arguments: Vec<String> = (0..arity)
+                    .map(|_| self.expression(Type::Integer, depth))
+                    .collect();
+                format!("{name}({})", arguments.join(", "))
+            }
+            5 => self.product(),
+            _ => {
+                let operator = self.rng.pick(&["+", "-", "/", "%"]);
+                format!(
+                    "({} {operator} {})",
+                    self.expression(Type::Integer, depth),
+                    self.expression(Type::Integer, depth)
+                )
+            }
+        }
+    }
+
+    /// Products only ever involve literals: the other operators can at most
+    /// double the magnitude of their operands, which keeps integers small even
+    /// when programs reuse their variables.
+    fn product(&mut self) -> String {
+        format!("({} * {})", self.rng.below(20), self.rng.below(20))
+    }
+
+    fn boolean(&mut self, depth: usize) -> String {
+        match self.rng.below(5) {
+            0 => format!("(!{})", self.expression(Type::Boolean, depth)),
+            1 => {
+                let operator = self.rng.pick(&["<", ">", "<=", ">=", "==", "!="]);
+                format!(
+                    "({} {operator} {})",
+                    self.expression(Type::Integer, depth),
+                    self.expression(Type::Integer, depth)
+                )
+            }
+            2 => format!(
+                "contains({}, {})",
+                self.expression(Type::Hash, depth),
+                self.expression(Type::String, depth)
+            ),
+            _ => {
+                let operator = self.rng.pick(&["&&", "||", "==", "!="]);
+                format!(
+                    "({} {operator} {})",
+                    self.expression(Type::Boolean, depth),
+                    self.expression(Type::Boolean, depth)
+                )
+            }
+        }
+    }
+
+    fn leaf(&mut self, ty: Type) -> String {
+        let variables = self.variables(ty);
+        if !variables.is_empty() && self.rng.chance(1, 2) {
+            return self.rng.pick(&variables).clone();
+        }
+        match ty {
+            Type::Integer => self.rng.below(20).to_string(),
+            Type::Boolean => self.rng.pick(&["true", "false"]).to_string(),
+            Type::String => format!("\"{}\"", self.rng.pick(&STRINGS)),
+            Type::Array => "[]".to_string(),
+            Type::Hash => "{}".to_string(),
+        }
+    }
+}
+
+/// Generates the program for `seed`.
+pub fn generate(seed: u64) -> String {
+    Generator::new(seed).program()
+}